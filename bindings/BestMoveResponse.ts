@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type Error = "Deserialization" | "Unauthorized" | "InvalidTurn" | "InvalidMove";
+export type BestMoveResponse = { best_move: string, new_fen: string, };
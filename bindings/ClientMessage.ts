@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ClientMessage = { "kind": "Auth", "value": { game_id: string, user_id: string, 
+/**
+ * Sequence number of the last game message the client has seen, used
+ * to resume a dropped session without a full snapshot.
+ */
+last_seq?: number, 
+/**
+ * Newest protocol version the client speaks. Clients that predate
+ * version negotiation omit it and are treated as version 1.
+ */
+protocol_version?: number, } } | { "kind": "Spectate", "value": { game_id: string, user_id: string, last_seq?: number, protocol_version?: number, } } | { "kind": "Move", "value": string } | { "kind": "Premove", "value": string } | { "kind": "CancelPremove" } | { "kind": "RequestTakeback" } | { "kind": "AcceptTakeback" } | { "kind": "DeclineTakeback" } | { "kind": "Chat", "value": string } | { "kind": "Mute", "value": string } | { "kind": "Unmute", "value": string } | { "kind": "Ping" };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * The `"GameStatus"` enum of `GameState.Status`.
+ */
+export type DbGameStatus = "White Wins" | "Black Wins" | "Draw" | "On Going" | "Abort";
@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ErrorCode } from "./ErrorCode";
+import type { ErrorContext } from "./ErrorContext";
+
+export type Error = { code: ErrorCode, 
+/**
+ * Human-readable description, not meant to be matched on.
+ */
+message: string, 
+/**
+ * The part of the client message that was rejected, e.g. the move.
+ */
+input?: string, context?: ErrorContext, };
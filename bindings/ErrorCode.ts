@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Machine-readable reason a message was rejected.
+ */
+export type ErrorCode = "MalformedMessage" | "NotAuthenticated" | "GameNotFound" | "NotAPlayer" | "SpectatorNotAllowed" | "GameUnavailable" | "NotYourTurn" | "GameOver" | "MalformedMove" | "IllegalMove" | "TakebackNotAllowed" | "InvalidTakeback" | "InvalidChat" | "ChatRateLimited" | "ChatRejected" | "RateLimited" | "Flooding" | "AuthTimeout" | "ServerRestarting" | "UnsupportedProtocolVersion";
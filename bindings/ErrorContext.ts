@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Details that help a client explain or recover from an error.
+ */
+export type ErrorContext = { 
+/**
+ * Legal moves in the current position.
+ */
+legal_moves?: number, 
+/**
+ * Longest accepted chat message, in characters.
+ */
+max_length?: number, 
+/**
+ * Oldest protocol version the server accepts.
+ */
+min_protocol_version?: number, 
+/**
+ * Newest protocol version the server speaks.
+ */
+max_protocol_version?: number, };
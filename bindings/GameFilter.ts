@@ -0,0 +1,20 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ColorFilter } from "./ColorFilter";
+import type { ResultFilter } from "./ResultFilter";
+
+/**
+ * Narrows down the games of a user, every field is optional.
+ */
+export type GameFilter = { result?: ResultFilter, 
+/**
+ * The color the user played.
+ */
+color?: ColorFilter, 
+/**
+ * First day included, `YYYY-MM-DD`.
+ */
+from?: string, 
+/**
+ * Last day included, `YYYY-MM-DD`.
+ */
+to?: string, time_control?: string, };
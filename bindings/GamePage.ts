@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GameRecord } from "./GameRecord";
+
+export type GamePage = { games: Array<GameRecord>, 
+/**
+ * Pass as `offset` to fetch the next page, `None` on the last page.
+ */
+next_offset: number | null, };
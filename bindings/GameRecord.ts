@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DbGameStatus } from "./DbGameStatus";
+
+/**
+ * A game as stored in `GameState`, ongoing or finished.
+ */
+export type GameRecord = { game_id: string, white: string, black: string, status: DbGameStatus, created_at: string | null, 
+/**
+ * PGN `TimeControl` value such as `300+2`, `None` when untimed.
+ */
+time_control: string | null, moves: Array<string>, 
+/**
+ * Uploaded through `/games/import`. Nothing vouches for its players or
+ * result, so it is not part of their history.
+ */
+imported: boolean, };
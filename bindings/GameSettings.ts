@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type GameSettings = { 
+/**
+ * Rated games turn this off to forbid takebacks.
+ */
+allow_takebacks: boolean, 
+/**
+ * PGN `TimeControl` value such as `300+2`, informational only.
+ */
+time_control?: string, };
@@ -0,0 +1,25 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Color } from "./Color";
+import type { GameStatus } from "./GameStatus";
+
+/**
+ * Everything a (re)connecting client needs to render the game without
+ * replaying moves itself.
+ *
+ * There are no clocks or draw offers: the server does not run clocks (a
+ * game's `time_control` is only stored) and has no draw offer message, so
+ * neither has state to restore. Both belong here once they exist.
+ */
+export type GameSnapshot = { fen: string, moves: Array<string>, turn: Color, white_user_id: string, black_user_id: string, status: GameStatus, 
+/**
+ * The receiving player's color, `None` for spectators.
+ */
+color: Color | null, 
+/**
+ * The receiving player's queued premove, if any.
+ */
+premove: string | null, takeback_requested_by: Color | null, 
+/**
+ * Sequence number of the last game message reflected in this snapshot.
+ */
+seq: number, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GameSettings } from "./GameSettings";
+
+/**
+ * Body of ws_server's `POST /init`, which starts a game.
+ */
+export type InitBody = { game_id: string, white_user_id: string, black_user_id: string, white_rating?: number, black_rating?: number, settings?: GameSettings, };
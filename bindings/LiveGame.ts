@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Color } from "./Color";
+
+/**
+ * A game being played on any node of the cluster.
+ */
+export type LiveGame = { game_id: string, white_user_id: string, black_user_id: string, white_rating: number | null, black_rating: number | null, time_control: string | null, move_count: number, fen: string, turn: Color, spectators: number, created_at: string, };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { LiveGame } from "./LiveGame";
+
+export type LiveGamePage = { games: Array<LiveGame>, 
+/**
+ * Ongoing games in the cluster.
+ */
+total: number, 
+/**
+ * Pass as `offset` to fetch the next page, `None` on the last page.
+ */
+next_offset: number | null, };
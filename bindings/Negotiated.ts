@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * The protocol version both sides of a socket speak.
+ */
+export type Negotiated = { protocol_version: number, };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PgnTag } from "./PgnTag";
+
+/**
+ * A game that passed validation. Comments, NAGs and variations are checked
+ * but only the mainline is kept, in normalized SAN.
+ */
+export type ParsedGame = { tags: Array<PgnTag>, moves: Array<string>, 
+/**
+ * The game termination marker, `1-0`, `0-1`, `1/2-1/2` or `*`.
+ */
+result: string, };
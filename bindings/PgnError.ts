@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * The first problem found in a game; the rest of that game is skipped.
+ */
+export type PgnError = { 
+/**
+ * Position of the game in the file, starting at 1.
+ */
+game: number, line: number, column: number, message: string, input?: string, };
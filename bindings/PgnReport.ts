@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ParsedGame } from "./ParsedGame";
+import type { PgnError } from "./PgnError";
+
+export type PgnReport = { games: Array<ParsedGame>, errors: Array<PgnError>, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Result of a game from the point of view of the user whose games are listed.
+ */
+export type ResultFilter = "win" | "loss" | "draw" | "ongoing" | "aborted";
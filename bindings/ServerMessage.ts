@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ChatChannel } from "./ChatChannel";
+import type { Color } from "./Color";
+import type { Error } from "./Error";
+import type { GameSnapshot } from "./GameSnapshot";
+import type { Negotiated } from "./Negotiated";
+import type { Outcome } from "./Outcome";
+
+export type ServerMessage = { "kind": "Move", "value": string } | { "kind": "GameEnd", "value": Outcome } | { "kind": "Error", "value": Error } | { "kind": "AuthSuccess", "value": Negotiated | null } | { "kind": "GameSnapshot", "value": GameSnapshot } | { "kind": "Pong" } | { "kind": "SessionReplaced" } | { "kind": "PremoveDiscarded", "value": string } | { "kind": "TakebackRequested", "value": Color } | { "kind": "TakebackDeclined" } | { "kind": "Takeback", "value": { fen: string, moves: Array<string>, } } | { "kind": "Chat", "value": { from: string, text: string, 
+/**
+ * Unix timestamp in milliseconds.
+ */
+ts: number, channel: ChatChannel, } } | { "kind": "Redirect", "value": string } | { "kind": "ServerRestarting", "value": { reconnect_after: number, } };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Result of `GET /test`, which checks that Stockfish answers `uci`.
+ */
+export type TestResponse = { status: string, message: string, response: string, };
@@ -0,0 +1,74 @@
+use std::{fmt, time::Duration};
+
+use chessclouds_protocol::game;
+use tokio_tungstenite::tungstenite;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum Error {
+    Http(reqwest::Error),
+    WebSocket(Box<tungstenite::Error>),
+    /// A server message could not be parsed.
+    Malformed(serde_json::Error),
+    /// The server answered an HTTP request with an unexpected status.
+    Status(reqwest::StatusCode, String),
+    /// The game server refused to authenticate the socket.
+    Rejected(game::Error),
+    /// The matchmaking service could not find a match.
+    Matchmaking(String),
+    /// Too many requests; retry after the given delay if the server sent one.
+    RateLimited(Option<Duration>),
+    /// The server did not answer in time.
+    Timeout,
+    /// The connection is closed and can no longer send messages.
+    Closed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Http(e) => write!(f, "HTTP request failed: {e}"),
+            Error::WebSocket(e) => write!(f, "WebSocket error: {e}"),
+            Error::Malformed(e) => write!(f, "malformed server message: {e}"),
+            Error::Status(status, body) => write!(f, "unexpected status {status}: {body}"),
+            Error::Rejected(e) => write!(f, "rejected by the server: {e}"),
+            Error::Matchmaking(message) => write!(f, "matchmaking failed: {message}"),
+            Error::RateLimited(Some(retry_after)) => {
+                write!(f, "rate limited, retry after {}s", retry_after.as_secs())
+            }
+            Error::RateLimited(None) => write!(f, "rate limited"),
+            Error::Timeout => write!(f, "the server did not answer in time"),
+            Error::Closed => write!(f, "the connection is closed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(e) => Some(e),
+            Error::WebSocket(e) => Some(e.as_ref()),
+            Error::Malformed(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl From<tungstenite::Error> for Error {
+    fn from(e: tungstenite::Error) -> Self {
+        Error::WebSocket(Box::new(e))
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Malformed(e)
+    }
+}
@@ -0,0 +1,514 @@
+use std::{ops::Deref, time::Duration};
+
+use chessclouds_protocol::{
+    game::{ClientMessage, ErrorCode, GameStatus, InitBody, ServerMessage},
+    PROTOCOL_VERSION,
+};
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use tokio::{net::TcpStream, sync::mpsc, time::timeout};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{Message, Utf8Bytes},
+    MaybeTlsStream, WebSocketStream,
+};
+
+use crate::{Error, Result};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// How many `Redirect`s to follow while authenticating.
+const MAX_REDIRECTS: usize = 3;
+
+/// How a dropped connection is re-established.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Attempts before giving up, 0 disables reconnecting.
+    pub max_attempts: u32,
+    /// Delay before the first attempt, doubled after each failure.
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            max_attempts: 5,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Something that happened on a game connection.
+#[derive(Debug)]
+pub enum Event {
+    /// A message from the server. Game-wide messages, such as moves, carry
+    /// their sequence number.
+    Message {
+        seq: Option<u64>,
+        message: ServerMessage,
+    },
+    /// The socket dropped and the client is reconnecting.
+    Disconnected,
+    /// The session resumed on a new socket. It is followed by the messages
+    /// missed in between, or by a `GameSnapshot` if the server no longer has
+    /// all of them.
+    Reconnected,
+    /// The connection ended for good, with the reason if it was not closed
+    /// by the client, replaced by a newer session or over with the game. No
+    /// events follow.
+    Closed(Option<Error>),
+}
+
+/// A client for one ws_server, used to start games and open connections.
+#[derive(Debug, Clone)]
+pub struct GameClient {
+    http: reqwest::Client,
+    base_url: String,
+    reconnect: ReconnectPolicy,
+    auth_timeout: Duration,
+}
+
+impl GameClient {
+    /// A client for the ws_server at `base_url`, e.g. `http://localhost:8000`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        GameClient {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            reconnect: ReconnectPolicy::default(),
+            auth_timeout: Duration::from_secs(10),
+        }
+    }
+
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = policy;
+        self
+    }
+
+    /// How long to wait for the server to accept or reject `Auth`.
+    pub fn with_auth_timeout(mut self, auth_timeout: Duration) -> Self {
+        self.auth_timeout = auth_timeout;
+        self
+    }
+
+    /// The WebSocket endpoint of the server.
+    pub fn ws_url(&self) -> String {
+        let url = match self.base_url.split_once("://") {
+            Some(("https", rest)) => format!("wss://{rest}"),
+            Some((_, rest)) => format!("ws://{rest}"),
+            None => format!("ws://{}", self.base_url),
+        };
+        format!("{url}/ws")
+    }
+
+    /// Starts a game on this server without going through matchmaking.
+    pub async fn init_game(&self, body: &InitBody) -> Result<()> {
+        let response = self
+            .http
+            .post(format!("{}/init", self.base_url))
+            .json(body)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Status(status, response.text().await?));
+        }
+        Ok(())
+    }
+
+    /// Connects to `game_id` as one of its players.
+    pub async fn join(&self, game_id: &str, user_id: &str) -> Result<GameConnection> {
+        self.connect(Seat::new(game_id, user_id, false)).await
+    }
+
+    /// Connects to `game_id` as a spectator.
+    pub async fn spectate(&self, game_id: &str, user_id: &str) -> Result<GameConnection> {
+        self.connect(Seat::new(game_id, user_id, true)).await
+    }
+
+    async fn connect(&self, seat: Seat) -> Result<GameConnection> {
+        let mut session = Session {
+            url: self.ws_url(),
+            seat,
+            reconnect: self.reconnect.clone(),
+            auth_timeout: self.auth_timeout,
+        };
+        let (socket, protocol_version) = session.authenticate(None).await?;
+        let (tx_commands, rx_commands) = mpsc::unbounded_channel();
+        let (tx_events, rx_events) = mpsc::unbounded_channel();
+        tokio::spawn(run(session, socket, rx_commands, tx_events));
+        Ok(GameConnection {
+            sender: GameSender {
+                commands: tx_commands,
+            },
+            events: rx_events,
+            protocol_version,
+        })
+    }
+}
+
+/// An authenticated game connection. Messages are sent through the
+/// [`GameSender`] it dereferences to, and everything received is read with
+/// [`next_event`](Self::next_event).
+///
+/// A dropped socket is reconnected in the background and the session resumed
+/// from the last message seen; messages sent in the meantime are delivered
+/// once it is back. The connection closes when it is dropped.
+#[derive(Debug)]
+pub struct GameConnection {
+    sender: GameSender,
+    events: mpsc::UnboundedReceiver<Event>,
+    protocol_version: u32,
+}
+
+impl GameConnection {
+    /// The next event, or `None` after [`Event::Closed`].
+    pub async fn next_event(&mut self) -> Option<Event> {
+        self.events.recv().await
+    }
+
+    /// The next server message, skipping reconnection events. `None` once
+    /// the connection is closed.
+    pub async fn next_message(&mut self) -> Option<ServerMessage> {
+        loop {
+            match self.events.recv().await? {
+                Event::Message { message, .. } => return Some(message),
+                Event::Closed(_) => return None,
+                Event::Disconnected | Event::Reconnected => {}
+            }
+        }
+    }
+
+    /// The protocol version agreed on with the server.
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
+
+    /// A handle to send messages from another task.
+    pub fn sender(&self) -> GameSender {
+        self.sender.clone()
+    }
+
+    /// Closes the socket; pending events can still be read.
+    pub fn close(&self) {
+        let _ = self.sender.commands.send(Command::Close);
+    }
+}
+
+impl Deref for GameConnection {
+    type Target = GameSender;
+
+    fn deref(&self) -> &GameSender {
+        &self.sender
+    }
+}
+
+/// Sends messages on a [`GameConnection`]. Sending only fails once the
+/// connection is closed; the server answers rejected messages with an
+/// `Error` event.
+#[derive(Debug, Clone)]
+pub struct GameSender {
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl GameSender {
+    pub fn send(&self, message: ClientMessage) -> Result<()> {
+        self.commands
+            .send(Command::Send(message))
+            .map_err(|_| Error::Closed)
+    }
+
+    /// Plays a move in SAN, e.g. `Nf3`.
+    pub fn play(&self, san: &str) -> Result<()> {
+        self.send(ClientMessage::Move(san.to_string()))
+    }
+
+    pub fn premove(&self, san: &str) -> Result<()> {
+        self.send(ClientMessage::Premove(san.to_string()))
+    }
+
+    pub fn cancel_premove(&self) -> Result<()> {
+        self.send(ClientMessage::CancelPremove)
+    }
+
+    pub fn request_takeback(&self) -> Result<()> {
+        self.send(ClientMessage::RequestTakeback)
+    }
+
+    pub fn accept_takeback(&self) -> Result<()> {
+        self.send(ClientMessage::AcceptTakeback)
+    }
+
+    pub fn decline_takeback(&self) -> Result<()> {
+        self.send(ClientMessage::DeclineTakeback)
+    }
+
+    pub fn chat(&self, text: &str) -> Result<()> {
+        self.send(ClientMessage::Chat(text.to_string()))
+    }
+
+    pub fn mute(&self, user_id: &str) -> Result<()> {
+        self.send(ClientMessage::Mute(user_id.to_string()))
+    }
+
+    pub fn unmute(&self, user_id: &str) -> Result<()> {
+        self.send(ClientMessage::Unmute(user_id.to_string()))
+    }
+
+    pub fn ping(&self) -> Result<()> {
+        self.send(ClientMessage::Ping)
+    }
+}
+
+#[derive(Debug)]
+enum Command {
+    Send(ClientMessage),
+    Close,
+}
+
+#[derive(Debug, Clone)]
+struct Seat {
+    game_id: String,
+    user_id: String,
+    spectate: bool,
+}
+
+impl Seat {
+    fn new(game_id: &str, user_id: &str, spectate: bool) -> Self {
+        Seat {
+            game_id: game_id.to_string(),
+            user_id: user_id.to_string(),
+            spectate,
+        }
+    }
+
+    fn auth_message(&self, last_seq: Option<u64>) -> ClientMessage {
+        let (game_id, user_id) = (self.game_id.clone(), self.user_id.clone());
+        let protocol_version = Some(PROTOCOL_VERSION);
+        if self.spectate {
+            ClientMessage::Spectate {
+                game_id,
+                user_id,
+                last_seq,
+                protocol_version,
+            }
+        } else {
+            ClientMessage::Auth {
+                game_id,
+                user_id,
+                last_seq,
+                protocol_version,
+            }
+        }
+    }
+}
+
+/// Everything needed to (re)authenticate a connection.
+struct Session {
+    /// Updated when the server redirects to the node owning the game.
+    url: String,
+    seat: Seat,
+    reconnect: ReconnectPolicy,
+    auth_timeout: Duration,
+}
+
+impl Session {
+    /// Opens a socket and authenticates it, following redirects. Returns the
+    /// socket and the negotiated protocol version.
+    async fn authenticate(&mut self, last_seq: Option<u64>) -> Result<(Socket, u32)> {
+        for _ in 0..=MAX_REDIRECTS {
+            let (mut socket, _) = connect_async(self.url.as_str()).await?;
+            send(&mut socket, &self.seat.auth_message(last_seq)).await?;
+            let reply = timeout(self.auth_timeout, receive(&mut socket))
+                .await
+                .map_err(|_| Error::Timeout)?;
+            match reply? {
+                // servers that predate negotiation speak version 1
+                Some((_, ServerMessage::AuthSuccess(negotiated))) => {
+                    return Ok((socket, negotiated.map_or(1, |n| n.protocol_version)))
+                }
+                Some((_, ServerMessage::Redirect(url))) => self.url = url,
+                Some((_, ServerMessage::Error(err))) => return Err(Error::Rejected(err)),
+                _ => return Err(Error::Closed),
+            }
+        }
+        Err(Error::Closed)
+    }
+
+    /// Re-establishes a dropped session, waiting `delay` first if the server
+    /// asked for it.
+    async fn reconnect(
+        &mut self,
+        last_seq: Option<u64>,
+        delay: Option<Duration>,
+    ) -> Result<Socket> {
+        let mut backoff = self.reconnect.initial_delay;
+        let mut delay = delay.unwrap_or(backoff);
+        let mut last_error = Error::Closed;
+        for _ in 0..self.reconnect.max_attempts {
+            tokio::time::sleep(delay).await;
+            match self.authenticate(last_seq).await {
+                Ok((socket, _)) => return Ok(socket),
+                Err(Error::Rejected(err)) if !is_transient(err.code) => {
+                    return Err(Error::Rejected(err))
+                }
+                Err(e) => last_error = e,
+            }
+            backoff = (backoff * 2).min(self.reconnect.max_delay);
+            delay = backoff;
+        }
+        Err(last_error)
+    }
+}
+
+/// Rejections that may not happen on a later attempt.
+fn is_transient(code: ErrorCode) -> bool {
+    matches!(
+        code,
+        ErrorCode::GameUnavailable | ErrorCode::ServerRestarting
+    )
+}
+
+/// Why the socket stopped being pumped.
+enum Stop {
+    /// The client closed the connection.
+    Closed,
+    /// The server handed the seat to a newer session.
+    Replaced,
+    /// The game is over, there is nothing left to reconnect for.
+    Ended,
+    /// The socket dropped, reconnect after the delay the server asked for.
+    Dropped(Option<Duration>),
+}
+
+/// Owns the socket for the lifetime of the connection.
+async fn run(
+    mut session: Session,
+    mut socket: Socket,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    events: mpsc::UnboundedSender<Event>,
+) {
+    let mut last_seq = None;
+    loop {
+        match pump(&mut socket, &mut commands, &events, &mut last_seq).await {
+            Stop::Closed => {
+                let _ = socket.close(None).await;
+                return;
+            }
+            Stop::Replaced => {
+                let _ = events.send(Event::Closed(None));
+                return;
+            }
+            Stop::Ended => {
+                let _ = socket.close(None).await;
+                let _ = events.send(Event::Closed(None));
+                return;
+            }
+            Stop::Dropped(delay) => {
+                let _ = events.send(Event::Disconnected);
+                match session.reconnect(last_seq, delay).await {
+                    Ok(new_socket) => {
+                        socket = new_socket;
+                        let _ = events.send(Event::Reconnected);
+                    }
+                    Err(e) => {
+                        let _ = events.send(Event::Closed(Some(e)));
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Forwards commands to the socket and server messages to `events` until
+/// the connection stops.
+async fn pump(
+    socket: &mut Socket,
+    commands: &mut mpsc::UnboundedReceiver<Command>,
+    events: &mpsc::UnboundedSender<Event>,
+    last_seq: &mut Option<u64>,
+) -> Stop {
+    let mut reconnect_after = None;
+    loop {
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(Command::Send(message)) => {
+                    if send(socket, &message).await.is_err() {
+                        return Stop::Dropped(reconnect_after);
+                    }
+                }
+                Some(Command::Close) | None => return Stop::Closed,
+            },
+            received = receive(socket) => {
+                let (seq, message) = match received {
+                    Ok(Some(received)) => received,
+                    // skip what this client does not understand
+                    Err(Error::Malformed(_)) => continue,
+                    Ok(None) | Err(_) => return Stop::Dropped(reconnect_after),
+                };
+                match &message {
+                    ServerMessage::GameSnapshot(snapshot) => *last_seq = Some(snapshot.seq),
+                    ServerMessage::ServerRestarting { reconnect_after: secs } => {
+                        reconnect_after = Some(Duration::from_secs(*secs));
+                    }
+                    _ => {}
+                }
+                if seq.is_some() {
+                    *last_seq = seq;
+                }
+                let replaced = matches!(message, ServerMessage::SessionReplaced);
+                let ended = is_over(&message);
+                if events.send(Event::Message { seq, message }).is_err() {
+                    return Stop::Closed;
+                }
+                if replaced {
+                    return Stop::Replaced;
+                }
+                if ended {
+                    return Stop::Ended;
+                }
+            }
+        }
+    }
+}
+
+/// Whether `message` says the game is over, after which the server closes
+/// the socket.
+fn is_over(message: &ServerMessage) -> bool {
+    match message {
+        ServerMessage::GameSnapshot(snapshot) => matches!(snapshot.status, GameStatus::Ended(_)),
+        message => message.is_game_end(),
+    }
+}
+
+/// A server message, numbered if it is broadcast to the whole game.
+#[derive(Deserialize)]
+struct Incoming {
+    #[serde(default)]
+    seq: Option<u64>,
+    #[serde(flatten)]
+    message: ServerMessage,
+}
+
+async fn send(socket: &mut Socket, message: &ClientMessage) -> Result<()> {
+    let text = serde_json::to_string(message)?;
+    socket.send(Message::Text(Utf8Bytes::from(text))).await?;
+    Ok(())
+}
+
+/// The next server message, `None` once the socket is closed.
+async fn receive(socket: &mut Socket) -> Result<Option<(Option<u64>, ServerMessage)>> {
+    while let Some(frame) = socket.next().await {
+        match frame? {
+            Message::Text(text) => {
+                let incoming: Incoming = serde_json::from_str(text.as_str())?;
+                return Ok(Some((incoming.seq, incoming.message)));
+            }
+            Message::Close(_) => return Ok(None),
+            _ => {}
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,32 @@
+//! Async client for the ChessClouds services: find a match through the
+//! matchmaking service, then play or watch the game on a ws_server with
+//! typed events and automatic reconnection.
+//!
+//! ```no_run
+//! use chessclouds_client::{Event, GameClient, MatchmakingClient};
+//!
+//! # async fn run() -> chessclouds_client::Result<()> {
+//! let found = MatchmakingClient::new("http://localhost:8001")
+//!     .find_match("alice")
+//!     .await?;
+//! let mut game = GameClient::new("http://localhost:8000")
+//!     .join(&found.game_id, "alice")
+//!     .await?;
+//! game.play("e4")?;
+//! while let Some(event) = game.next_event().await {
+//!     if let Event::Message { message, .. } = event {
+//!         println!("{message:?}");
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod error;
+pub mod game;
+pub mod matchmaking;
+
+pub use chessclouds_protocol as protocol;
+pub use error::{Error, Result};
+pub use game::{Event, GameClient, GameConnection, GameSender, ReconnectPolicy};
+pub use matchmaking::{Match, MatchmakingClient};
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use chessclouds_protocol::{
+    matchmaking::{MatchRequest, MatchResponse},
+    Color,
+};
+use reqwest::{header, StatusCode};
+
+use crate::{Error, Result};
+
+/// A game the matchmaking service paired the user into.
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub game_id: String,
+    pub color: Color,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchmakingClient {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl MatchmakingClient {
+    /// A client for the matchmaking service at `base_url`, e.g.
+    /// `http://localhost:8001`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(reqwest::Client::new(), base_url)
+    }
+
+    pub fn with_http_client(http: reqwest::Client, base_url: impl Into<String>) -> Self {
+        MatchmakingClient {
+            http,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Queues `user_id` and waits until an opponent is found.
+    pub async fn find_match(&self, user_id: &str) -> Result<Match> {
+        let response = self
+            .http
+            .post(format!("{}/match", self.base_url))
+            .json(&MatchRequest {
+                user_id: user_id.to_string(),
+            })
+            .send()
+            .await?;
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response
+                .headers()
+                .get(header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok())
+                .map(Duration::from_secs);
+            return Err(Error::RateLimited(retry_after));
+        }
+        let body = response.text().await?;
+        match serde_json::from_str(&body) {
+            Ok(MatchResponse::Ok { game_id, color }) => Ok(Match { game_id, color }),
+            Ok(MatchResponse::Err(message)) => Err(Error::Matchmaking(message)),
+            Err(_) => Err(Error::Status(status, body)),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+pub use chessclouds_telemetry::{LogConfig, LogFormat};
+use config::{ConfigError, Environment, File, FileFormat};
+use serde::{Deserialize, Serialize};
+use std::{env, net::SocketAddr, path::PathBuf};
+
+/// Environment variables starting with this prefix override the config file,
+/// with `__` separating sections, e.g. `ENGINE_STOCKFISH__DEPTH=18`.
+pub const ENV_PREFIX: &str = "ENGINE";
+/// Read when it exists and no `--config` path is given.
+pub const DEFAULT_CONFIG_PATH: &str = "engine.toml";
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Config {
+    /// Address the HTTP API binds to.
+    pub host: String,
+    pub stockfish: StockfishConfig,
+    pub rate_limit: RateLimitConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct StockfishConfig {
+    /// Where socat exposes the Stockfish process, `host:port`.
+    pub addr: String,
+    /// Search depth passed to `go depth`.
+    pub depth: u32,
+    pub socket_timeout_secs: u64,
+    /// How long to wait for `uciok`, `readyok` and `bestmove`.
+    pub response_timeout_secs: u64,
+    /// `/readyz` reports Stockfish as unresponsive after this long.
+    pub health_check_timeout_secs: u64,
+}
+
+/// Per client IP token bucket on `/bestmove` and `/test`, which each start a
+/// Stockfish session.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RateLimitConfig {
+    pub burst: u32,
+    pub per_minute: u32,
+    /// Use the first `X-Forwarded-For` address as the client IP. Only enable
+    /// behind a proxy that sets the header.
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            host: "0.0.0.0:4000".into(),
+            stockfish: StockfishConfig {
+                addr: "127.0.0.1:4001".into(),
+                depth: 25,
+                socket_timeout_secs: 10,
+                response_timeout_secs: 30,
+                health_check_timeout_secs: 2,
+            },
+            rate_limit: RateLimitConfig {
+                burst: 10,
+                per_minute: 30,
+                trust_forwarded_for: false,
+            },
+            log: LogConfig {
+                format: LogFormat::Text,
+                filter: "info".into(),
+                otlp_endpoint: None,
+            },
+        }
+    }
+}
+
+impl Config {
+    /// Built-in defaults, overridden by the TOML file at `path` (or
+    /// `DEFAULT_CONFIG_PATH` if present), overridden by `ENGINE_*`
+    /// environment variables.
+    pub fn load(path: Option<PathBuf>) -> Result<Self, ConfigError> {
+        let file = match path {
+            Some(path) => File::from(path).format(FileFormat::Toml),
+            None => File::new(DEFAULT_CONFIG_PATH, FileFormat::Toml).required(false),
+        };
+        let config: Config = config::Config::builder()
+            .add_source(config::Config::try_from(&Config::default())?)
+            .add_source(file)
+            .add_source(
+                Environment::with_prefix(ENV_PREFIX)
+                    .prefix_separator("_")
+                    .separator("__")
+                    .try_parsing(true),
+            )
+            .build()?
+            .try_deserialize()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |msg: &str| Err(ConfigError::Message(msg.to_string()));
+        if self.host.parse::<SocketAddr>().is_err() {
+            return invalid("host must be an address like 0.0.0.0:4000");
+        }
+        // may be a host name, e.g. a separate Stockfish container
+        if !self
+            .stockfish
+            .addr
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+        {
+            return invalid("stockfish.addr must be host:port");
+        }
+        if self.stockfish.depth == 0 {
+            return invalid("stockfish.depth must be positive");
+        }
+        if self.stockfish.socket_timeout_secs == 0
+            || self.stockfish.response_timeout_secs == 0
+            || self.stockfish.health_check_timeout_secs == 0
+        {
+            return invalid("stockfish timeouts must be positive");
+        }
+        if self.rate_limit.burst == 0 || self.rate_limit.per_minute == 0 {
+            return invalid("rate_limit.burst and rate_limit.per_minute must be positive");
+        }
+        if let Err(msg) = self.log.validate() {
+            return invalid(msg);
+        }
+        Ok(())
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("config should serialize")
+    }
+}
+
+/// Command line options of the API binary.
+#[derive(Default)]
+pub struct Args {
+    /// `--config <path>`
+    pub config_path: Option<PathBuf>,
+    /// `--print-config`: print the effective configuration and exit.
+    pub print_config: bool,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, String> {
+        let mut args = Args::default();
+        let mut argv = env::args().skip(1);
+        while let Some(arg) = argv.next() {
+            match arg.as_str() {
+                "--config" => {
+                    args.config_path = Some(argv.next().ok_or("--config expects a path")?.into())
+                }
+                "--print-config" => args.print_config = true,
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+        Ok(args)
+    }
+}
@@ -1,85 +1,168 @@
-use hyper::{Body, Request, Response, Server, Method, StatusCode, header::HeaderValue};
+mod config;
+mod metrics;
+
+use crate::config::{Args, Config, StockfishConfig};
+use crate::metrics::Metrics;
+use chessclouds_protocol::engine::{
+    BestMoveResponse, ErrorResponse, MessageResponse, TestResponse,
+};
+use chessclouds_rate_limit::{client_ip, TokenBuckets};
+use hyper::server::conn::AddrStream;
 use hyper::service::{make_service_fn, service_fn};
+use hyper::{header::HeaderValue, Body, Method, Request, Response, Server, StatusCode};
 use regex::Regex;
-use serde::Serialize;
 use shakmaty::{fen::Fen, Chess, FromSetup, Position};
 use std::{
     convert::Infallible,
     io::{Read, Write},
-    net::TcpStream,
+    net::{IpAddr, SocketAddr, TcpStream},
+    process,
+    sync::Arc,
     time::{Duration, Instant},
 };
+use tracing::Instrument;
 use url::form_urlencoded;
-
-#[derive(Serialize)]
-struct MessageResponse {
-    message: String,
-}
-
-#[derive(Serialize)]
-struct TestResponse {
-    status: String,
-    message: String,
-    response: String,
-}
-
-#[derive(Serialize)]
-struct BestMoveResponse {
-    best_move: String,
-    new_fen: String,
-}
-
-#[derive(Serialize)]
-struct ErrorResponse {
-    status: String,
-    message: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    best_move: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    raw_response: Option<String>,
-}
+use uuid::Uuid;
 
 #[tokio::main]
 async fn main() {
-    let addr = ([0, 0, 0, 0], 4000).into();
-    println!("Starting server on http://{}", addr);
-    let make_svc = make_service_fn(|_conn| async { Ok::<_, Infallible>(service_fn(handle_request)) });
+    let args = Args::parse().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    let config = Config::load(args.config_path).unwrap_or_else(|e| {
+        eprintln!("invalid configuration: {}", e);
+        process::exit(1);
+    });
+    if args.print_config {
+        print!("{}", config.to_toml());
+        return;
+    }
+    let telemetry = chessclouds_telemetry::init(&config.log, "engine").unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let addr: SocketAddr = config.host.parse().expect("host is validated");
+    tracing::info!("Starting server on http://{}", addr);
+    let config = Arc::new(config);
+    let metrics = Arc::new(Metrics::new());
+    let limiter = Arc::new(TokenBuckets::new(
+        config.rate_limit.burst,
+        config.rate_limit.per_minute,
+    ));
+    let make_svc = make_service_fn(move |conn: &AddrStream| {
+        let peer = conn.remote_addr();
+        let config = config.clone();
+        let metrics = metrics.clone();
+        let limiter = limiter.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                handle_request(req, peer, config.clone(), metrics.clone(), limiter.clone())
+            }))
+        }
+    });
     let server = Server::bind(&addr).serve(make_svc);
     if let Err(e) = server.await {
-        eprintln!("Server error: {}", e);
+        tracing::error!("Server error: {}", e);
+    }
+    telemetry.shutdown();
+}
+
+/// Runs `route` in a span carrying the request id, taken from the
+/// `x-request-id` header or generated, and echoes the id in the response.
+async fn handle_request(
+    req: Request<Body>,
+    peer: SocketAddr,
+    config: Arc<Config>,
+    metrics: Arc<Metrics>,
+    limiter: Arc<TokenBuckets<IpAddr>>,
+) -> Result<Response<Body>, Infallible> {
+    let request_id = req
+        .headers()
+        .get("x-request-id")
+        .and_then(|id| id.to_str().ok())
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let span = tracing::info_span!("request", request_id = %request_id, method = %req.method(), path = req.uri().path());
+    let start = Instant::now();
+    let mut response = route(req, peer, config, metrics, limiter)
+        .instrument(span.clone())
+        .await?;
+    span.in_scope(|| {
+        tracing::info!(
+            status = response.status().as_u16(),
+            latency_ms = start.elapsed().as_millis() as u64,
+            "finished processing request"
+        )
+    });
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert("x-request-id", value);
     }
+    Ok(response)
 }
 
-async fn handle_request(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+async fn route(
+    req: Request<Body>,
+    peer: SocketAddr,
+    config: Arc<Config>,
+    metrics: Arc<Metrics>,
+    limiter: Arc<TokenBuckets<IpAddr>>,
+) -> Result<Response<Body>, Infallible> {
     if req.method() == Method::OPTIONS {
         return Ok(cors_preflight());
     }
+    if matches!(req.uri().path(), "/bestmove" | "/test") {
+        let forwarded_for = req
+            .headers()
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok());
+        let ip = client_ip(peer, forwarded_for, config.rate_limit.trust_forwarded_for);
+        if let Err(retry_after) = limiter.try_acquire(ip) {
+            tracing::warn!("Rate limited {}", peer);
+            metrics.rate_limited.inc();
+            return Ok(add_cors_headers(too_many_requests(retry_after)));
+        }
+    }
+    let path = match req.uri().path() {
+        path @ ("/" | "/test" | "/bestmove" | "/healthz" | "/readyz" | "/metrics") => path,
+        _ => "other",
+    };
+    metrics.requests.with_label_values(&[path]).inc();
     let response = match (req.method(), req.uri().path()) {
         (&Method::GET, "/") => {
-            let body = serde_json::to_string(&MessageResponse { message: "Stockfish API is running".into() }).unwrap();
+            let body = serde_json::to_string(&MessageResponse {
+                message: "Stockfish API is running".into(),
+            })
+            .unwrap();
             json_response(StatusCode::OK, body)
         }
-        (&Method::GET, "/test") => {
-            match connect_to_stockfish() {
-                Ok(mut sock) => {
-                    if let Err(e) = send_command(&mut sock, "uci") {
-                        return Ok(add_cors_headers(internal_error(&e.to_string())));
-                    }
-                    match receive_until(&mut sock, "uciok", Duration::from_secs(30)) {
-                        Ok(resp) => {
-                            let body = serde_json::to_string(&TestResponse {
-                                status: "success".into(),
-                                message: "Connected to Stockfish successfully".into(),
-                                response: resp,
-                            }).unwrap();
-                            json_response(StatusCode::OK, body)
-                        }
-                        Err(e) => add_cors_headers(internal_error(&e)),
+        (&Method::GET, "/test") => match connect_to_stockfish(&config.stockfish) {
+            Ok(mut sock) => {
+                if let Err(e) = send_command(&mut sock, "uci") {
+                    return Ok(add_cors_headers(internal_error(&e.to_string())));
+                }
+                match receive_until(
+                    &mut sock,
+                    "uciok",
+                    Duration::from_secs(config.stockfish.response_timeout_secs),
+                ) {
+                    Ok(resp) => {
+                        let body = serde_json::to_string(&TestResponse {
+                            status: "success".into(),
+                            message: "Connected to Stockfish successfully".into(),
+                            response: resp,
+                        })
+                        .unwrap();
+                        json_response(StatusCode::OK, body)
                     }
+                    Err(e) => add_cors_headers(internal_error(&e)),
                 }
-                Err(e) => add_cors_headers(internal_error(&format!("Failed to connect to Stockfish: {}", e))),
             }
-        }
+            Err(e) => add_cors_headers(internal_error(&format!(
+                "Failed to connect to Stockfish: {}",
+                e
+            ))),
+        },
         (&Method::GET, "/bestmove") => {
             let query = req.uri().query().unwrap_or("");
             let fen = form_urlencoded::parse(query.as_bytes())
@@ -89,17 +172,44 @@ async fn handle_request(req: Request<Body>) -> Result<Response<Body>, Infallible
             if fen.is_empty() {
                 return Ok(add_cors_headers(bad_request("Missing 'fen' parameter")));
             }
-            match get_best_move_logic(&fen) {
+            let timer = metrics.bestmove_seconds.start_timer();
+            let result = get_best_move_logic(&fen, &config.stockfish);
+            timer.observe_duration();
+            match result {
                 Ok(resp) => {
                     let body = serde_json::to_string(&resp).unwrap();
                     json_response(StatusCode::OK, body)
                 }
                 Err(err_resp) => {
+                    metrics.bestmove_errors.inc();
+                    tracing::warn!("bestmove failed: {}", err_resp.message);
                     let body = serde_json::to_string(&err_resp).unwrap();
                     json_response(StatusCode::OK, body)
                 }
             }
         }
+        (&Method::GET, "/healthz") => Response::builder()
+            .status(StatusCode::OK)
+            .body(Body::from("OK"))
+            .unwrap(),
+        (&Method::GET, "/readyz") => match check_stockfish_ready(&config.stockfish).await {
+            Ok(()) => Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::from("OK"))
+                .unwrap(),
+            Err(e) => {
+                tracing::error!("Readiness check failed: {}", e);
+                Response::builder()
+                    .status(StatusCode::SERVICE_UNAVAILABLE)
+                    .body(Body::from("Stockfish unresponsive"))
+                    .unwrap()
+            }
+        },
+        (&Method::GET, "/metrics") => Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", prometheus::TEXT_FORMAT)
+            .body(Body::from(metrics.encode()))
+            .unwrap(),
         _ => Response::builder()
             .status(StatusCode::NOT_FOUND)
             .body(Body::from("Not Found"))
@@ -113,15 +223,22 @@ fn cors_preflight() -> Response<Body> {
         .status(StatusCode::NO_CONTENT)
         .header("Access-Control-Allow-Origin", "*")
         .header("Access-Control-Allow-Methods", "GET, POST, OPTIONS")
-        .header("Access-Control-Allow-Headers", "content-type")
+        .header("Access-Control-Allow-Headers", "content-type, x-request-id")
         .body(Body::empty())
         .unwrap()
 }
 
 fn add_cors_headers(mut res: Response<Body>) -> Response<Body> {
-    res.headers_mut().insert("Access-Control-Allow-Origin", HeaderValue::from_static("*"));
-    res.headers_mut().insert("Access-Control-Allow-Methods", HeaderValue::from_static("GET, POST, OPTIONS"));
-    res.headers_mut().insert("Access-Control-Allow-Headers", HeaderValue::from_static("content-type"));
+    res.headers_mut()
+        .insert("Access-Control-Allow-Origin", HeaderValue::from_static("*"));
+    res.headers_mut().insert(
+        "Access-Control-Allow-Methods",
+        HeaderValue::from_static("GET, POST, OPTIONS"),
+    );
+    res.headers_mut().insert(
+        "Access-Control-Allow-Headers",
+        HeaderValue::from_static("content-type, x-request-id"),
+    );
     res
 }
 
@@ -140,6 +257,16 @@ fn internal_error(msg: &str) -> Response<Body> {
         .unwrap()
 }
 
+fn too_many_requests(retry_after: Duration) -> Response<Body> {
+    let body = serde_json::to_string(&err_resp("Too many requests")).unwrap();
+    Response::builder()
+        .status(StatusCode::TOO_MANY_REQUESTS)
+        .header("Content-Type", "application/json")
+        .header("Retry-After", retry_after.as_secs_f64().ceil().to_string())
+        .body(Body::from(body))
+        .unwrap()
+}
+
 fn bad_request(msg: &str) -> Response<Body> {
     Response::builder()
         .status(StatusCode::BAD_REQUEST)
@@ -147,54 +274,129 @@ fn bad_request(msg: &str) -> Response<Body> {
         .unwrap()
 }
 
-fn get_best_move_logic(fen: &str) -> Result<BestMoveResponse, ErrorResponse> {
-    let mut sock = connect_to_stockfish().map_err(|e| ErrorResponse { status: "error".into(), message: e.to_string(), best_move: None, raw_response: None })?;
+fn get_best_move_logic(
+    fen: &str,
+    stockfish: &StockfishConfig,
+) -> Result<BestMoveResponse, ErrorResponse> {
+    let timeout = Duration::from_secs(stockfish.response_timeout_secs);
+    let mut sock = connect_to_stockfish(stockfish).map_err(|e| ErrorResponse {
+        status: "error".into(),
+        message: e.to_string(),
+        best_move: None,
+        raw_response: None,
+    })?;
     send_command(&mut sock, "uci").map_err(|e| err_resp(&e.to_string()))?;
-    receive_until(&mut sock, "uciok", Duration::from_secs(30)).map_err(|e| err_resp(&e))?;
+    receive_until(&mut sock, "uciok", timeout).map_err(|e| err_resp(&e))?;
     send_command(&mut sock, "isready").map_err(|e| err_resp(&e.to_string()))?;
-    receive_until(&mut sock, "readyok", Duration::from_secs(30)).map_err(|e| err_resp(&e))?;
-    send_command(&mut sock, &format!("position fen {}", fen)).map_err(|e| err_resp(&e.to_string()))?;
-    send_command(&mut sock, "go depth 25").map_err(|e| err_resp(&e.to_string()))?;
-    let response = receive_until(&mut sock, "bestmove", Duration::from_secs(30)).map_err(|e| err_resp(&e))?;
+    receive_until(&mut sock, "readyok", timeout).map_err(|e| err_resp(&e))?;
+    send_command(&mut sock, &format!("position fen {}", fen))
+        .map_err(|e| err_resp(&e.to_string()))?;
+    send_command(&mut sock, &format!("go depth {}", stockfish.depth))
+        .map_err(|e| err_resp(&e.to_string()))?;
+    let response = receive_until(&mut sock, "bestmove", timeout).map_err(|e| err_resp(&e))?;
     let re = Regex::new(r"bestmove\s+(\w+)").unwrap();
     if let Some(caps) = re.captures(&response) {
         let best_move = caps.get(1).unwrap().as_str();
-        let fen_parsed = Fen::from_ascii(fen.as_bytes()).map_err(|e| err_resp(&format!("Invalid FEN: {}", e)))?;
-        let mut pos = Chess::from_setup(fen_parsed.into_setup(), shakmaty::CastlingMode::Standard).map_err(|e| err_resp(&format!("Invalid position: {}", e)))?;
+        let fen_parsed = Fen::from_ascii(fen.as_bytes())
+            .map_err(|e| err_resp(&format!("Invalid FEN: {}", e)))?;
+        let mut pos = Chess::from_setup(fen_parsed.into_setup(), shakmaty::CastlingMode::Standard)
+            .map_err(|e| err_resp(&format!("Invalid position: {}", e)))?;
         let src = &best_move[0..2];
         let dst = &best_move[2..4];
         if let (Ok(src_sq), Ok(dst_sq)) = (src.parse(), dst.parse()) {
-            if let Some(mv) = pos.legal_moves().into_iter().find(|m| m.from() == Some(src_sq) && m.to() == dst_sq) {
+            if let Some(mv) = pos
+                .legal_moves()
+                .into_iter()
+                .find(|m| m.from() == Some(src_sq) && m.to() == dst_sq)
+            {
                 pos.play_unchecked(&mv);
-                let new_fen = Fen::from_setup(pos.into_setup(shakmaty::EnPassantMode::Legal)).to_string();
-                return Ok(BestMoveResponse { best_move: best_move.to_string(), new_fen });
+                let new_fen =
+                    Fen::from_setup(pos.into_setup(shakmaty::EnPassantMode::Legal)).to_string();
+                return Ok(BestMoveResponse {
+                    best_move: best_move.to_string(),
+                    new_fen,
+                });
             } else {
-                return Err(ErrorResponse { status: "error".into(), message: "Best move is not legal".into(), best_move: Some(best_move.into()), raw_response: None });
+                return Err(ErrorResponse {
+                    status: "error".into(),
+                    message: "Best move is not legal".into(),
+                    best_move: Some(best_move.into()),
+                    raw_response: None,
+                });
             }
         } else {
             return Err(err_resp("Could not parse move coordinates"));
         }
     }
-    Err(ErrorResponse { status: "error".into(), message: "Could not find best move in response".into(), best_move: None, raw_response: Some(response) })
+    Err(ErrorResponse {
+        status: "error".into(),
+        message: "Could not find best move in response".into(),
+        best_move: None,
+        raw_response: Some(response),
+    })
 }
 
 fn err_resp(msg: &str) -> ErrorResponse {
-    ErrorResponse { status: "error".into(), message: msg.to_string(), best_move: None, raw_response: None }
+    ErrorResponse {
+        status: "error".into(),
+        message: msg.to_string(),
+        best_move: None,
+        raw_response: None,
+    }
 }
 
-fn connect_to_stockfish() -> Result<TcpStream, std::io::Error> {
-    let stream = TcpStream::connect("127.0.0.1:4001")?;
-    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
-    stream.set_write_timeout(Some(Duration::from_secs(10)))?;
+fn connect_to_stockfish(stockfish: &StockfishConfig) -> Result<TcpStream, std::io::Error> {
+    let stream = TcpStream::connect(&stockfish.addr)?;
+    let timeout = Duration::from_secs(stockfish.socket_timeout_secs);
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
     Ok(stream)
 }
 
+/// Cheaper than `/test`: `isready` is answered without the UCI handshake.
+/// Uses a Tokio socket so a stalled Stockfish does not block a runtime worker.
+async fn check_stockfish_ready(stockfish: &StockfishConfig) -> Result<(), String> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let timeout = Duration::from_secs(stockfish.health_check_timeout_secs);
+    let probe = async {
+        let mut sock = tokio::net::TcpStream::connect(&stockfish.addr)
+            .await
+            .map_err(|e| e.to_string())?;
+        sock.write_all(b"isready\n")
+            .await
+            .map_err(|e| e.to_string())?;
+        let mut buffer = String::new();
+        let mut chunk = [0; 4096];
+        loop {
+            let n = sock
+                .read(&mut chunk)
+                .await
+                .map_err(|e| format!("Failed to read from socket: {}", e))?;
+            if n == 0 {
+                return Err("Stockfish closed the connection".to_string());
+            }
+            buffer.push_str(&String::from_utf8_lossy(&chunk[..n]));
+            if buffer.contains("readyok") {
+                return Ok(());
+            }
+        }
+    };
+    tokio::time::timeout(timeout, probe)
+        .await
+        .map_err(|_| "Timeout waiting for 'readyok'".to_string())?
+}
+
 fn send_command(stream: &mut TcpStream, command: &str) -> Result<(), std::io::Error> {
     stream.write_all(format!("{}\n", command).as_bytes())?;
     Ok(())
 }
 
-fn receive_until(stream: &mut TcpStream, marker: &str, timeout: Duration) -> Result<String, String> {
+fn receive_until(
+    stream: &mut TcpStream,
+    marker: &str,
+    timeout: Duration,
+) -> Result<String, String> {
     let start = Instant::now();
     let mut buffer = String::new();
     while start.elapsed() < timeout {
@@ -207,9 +409,14 @@ fn receive_until(stream: &mut TcpStream, marker: &str, timeout: Duration) -> Res
                 }
             }
             Ok(_) => std::thread::sleep(Duration::from_millis(100)),
-            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut => std::thread::sleep(Duration::from_millis(100)),
+            Err(ref e)
+                if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::TimedOut =>
+            {
+                std::thread::sleep(Duration::from_millis(100))
+            }
             Err(e) => return Err(format!("Failed to read from socket: {}", e)),
         }
     }
     Err(format!("Timeout waiting for '{}'", marker))
-}
\ No newline at end of file
+}
@@ -0,0 +1,64 @@
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+
+/// Prometheus metrics of the API, exported at `/metrics`.
+pub struct Metrics {
+    registry: Registry,
+    /// Requests by route, unknown paths are counted as `other`.
+    pub requests: IntCounterVec,
+    /// Latency of a full best move search, including the UCI handshake.
+    pub bestmove_seconds: Histogram,
+    pub bestmove_errors: IntCounter,
+    pub rate_limited: IntCounter,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let requests = IntCounterVec::new(
+            Opts::new("engine_requests_total", "HTTP requests"),
+            &["path"],
+        )
+        .unwrap();
+        let bestmove_seconds = Histogram::with_opts(
+            HistogramOpts::new("engine_bestmove_seconds", "Time to compute a best move")
+                .buckets(vec![0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]),
+        )
+        .unwrap();
+        let bestmove_errors = IntCounter::new(
+            "engine_bestmove_errors_total",
+            "Best move requests that failed",
+        )
+        .unwrap();
+        let rate_limited = IntCounter::new(
+            "engine_rate_limited_total",
+            "Requests rejected by the rate limit",
+        )
+        .unwrap();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry
+            .register(Box::new(bestmove_seconds.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(bestmove_errors.clone()))
+            .unwrap();
+        registry.register(Box::new(rate_limited.clone())).unwrap();
+        Metrics {
+            registry,
+            requests,
+            bestmove_seconds,
+            bestmove_errors,
+            rate_limited,
+        }
+    }
+
+    /// All metrics in the Prometheus text format.
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("metrics should encode");
+        String::from_utf8(buffer).expect("metrics should be UTF-8")
+    }
+}
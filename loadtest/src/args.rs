@@ -0,0 +1,89 @@
+use std::{env, time::Duration};
+
+pub const USAGE: &str = "\
+Usage: loadtest [options]
+
+Matchmakes virtual players in pairs and has them play random legal games.
+
+Options:
+  --matchmaking <url>       matchmaking service [default: http://localhost:8001]
+  --server <url>            ws_server [default: http://localhost:8000]
+  --players <n>             virtual players, two per game [default: 100]
+  --ramp-up-secs <s>        spread the players' first requests over this long [default: 10]
+  --move-delay-ms <ms>      average time a player thinks before moving [default: 1000]
+  --max-plies <n>           leave games still going on after this many moves [default: 120]
+  --disconnect-rate <p>     chance a player reconnects before a move [default: 0.01]
+  --timeout-secs <s>        give up on a game when nothing arrives for this long [default: 30]
+  --seed <n>                seed of the random moves, random by default
+  --help                    print this help";
+
+pub struct Args {
+    pub matchmaking_url: String,
+    pub server_url: String,
+    pub players: usize,
+    pub ramp_up: Duration,
+    pub move_delay: Duration,
+    pub max_plies: usize,
+    pub disconnect_rate: f64,
+    pub timeout: Duration,
+    pub seed: Option<u64>,
+    pub help: bool,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args {
+            matchmaking_url: "http://localhost:8001".to_string(),
+            server_url: "http://localhost:8000".to_string(),
+            players: 100,
+            ramp_up: Duration::from_secs(10),
+            move_delay: Duration::from_millis(1000),
+            max_plies: 120,
+            disconnect_rate: 0.01,
+            timeout: Duration::from_secs(30),
+            seed: None,
+            help: false,
+        }
+    }
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, String> {
+        let mut args = Args::default();
+        let mut argv = env::args().skip(1);
+        while let Some(arg) = argv.next() {
+            let mut value = || argv.next().ok_or(format!("{arg} expects a value"));
+            match arg.as_str() {
+                "--matchmaking" => args.matchmaking_url = value()?,
+                "--server" => args.server_url = value()?,
+                "--players" => args.players = number(&arg, value()?)?,
+                "--ramp-up-secs" => args.ramp_up = Duration::from_secs(number(&arg, value()?)?),
+                "--move-delay-ms" => {
+                    args.move_delay = Duration::from_millis(number(&arg, value()?)?)
+                }
+                "--max-plies" => args.max_plies = number(&arg, value()?)?,
+                "--disconnect-rate" => args.disconnect_rate = number(&arg, value()?)?,
+                "--timeout-secs" => args.timeout = Duration::from_secs(number(&arg, value()?)?),
+                "--seed" => args.seed = Some(number(&arg, value()?)?),
+                "--help" => args.help = true,
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+        if args.players < 2 || args.players % 2 != 0 {
+            return Err("--players must be an even number of at least 2".to_string());
+        }
+        if !(0.0..=1.0).contains(&args.disconnect_rate) {
+            return Err("--disconnect-rate must be between 0 and 1".to_string());
+        }
+        if args.max_plies == 0 || args.timeout.is_zero() {
+            return Err("--max-plies and --timeout-secs must be positive".to_string());
+        }
+        Ok(args)
+    }
+}
+
+fn number<T: std::str::FromStr>(arg: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{arg} expects a number, got {value}"))
+}
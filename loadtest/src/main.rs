@@ -0,0 +1,86 @@
+//! Load generator for ws_server: matchmakes virtual players in pairs, has
+//! them play random legal games and reports latency percentiles and errors.
+
+mod args;
+mod player;
+mod stats;
+
+use std::{
+    process,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use args::{Args, USAGE};
+use chessclouds_client::{GameClient, MatchmakingClient};
+use player::{Player, Settings};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use stats::Stats;
+use tokio::{signal, task::JoinSet};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse().unwrap_or_else(|e| {
+        eprintln!("{e}\n\n{USAGE}");
+        process::exit(2);
+    });
+    if args.help {
+        println!("{USAGE}");
+        return;
+    }
+
+    let seed = args.seed.unwrap_or_else(|| rand::rng().random());
+    // user ids must not collide with players of earlier runs still queued
+    let run_id = seed % 1_000_000;
+    println!(
+        "{} players against {} (matchmaking {}), seed {seed}",
+        args.players, args.server_url, args.matchmaking_url
+    );
+
+    let settings = Arc::new(Settings {
+        matchmaking: MatchmakingClient::new(&args.matchmaking_url),
+        game: GameClient::new(&args.server_url).with_auth_timeout(args.timeout),
+        move_delay: args.move_delay,
+        max_plies: args.max_plies,
+        disconnect_rate: args.disconnect_rate,
+        timeout: args.timeout,
+    });
+    let stats = Arc::new(Stats::default());
+    let started = Instant::now();
+
+    let mut players = JoinSet::new();
+    for i in 0..args.players {
+        let player = Player {
+            user_id: format!("load-{run_id}-{i}"),
+            settings: settings.clone(),
+            stats: stats.clone(),
+            rng: StdRng::seed_from_u64(seed.wrapping_add(i as u64)),
+        };
+        let start_after = args.ramp_up.mul_f64(i as f64 / args.players as f64);
+        players.spawn(async move {
+            tokio::time::sleep(start_after).await;
+            player.run().await;
+        });
+    }
+
+    let mut progress = tokio::time::interval(PROGRESS_INTERVAL);
+    progress.tick().await;
+    loop {
+        tokio::select! {
+            joined = players.join_next() => match joined {
+                Some(Err(e)) if e.is_panic() => eprintln!("a player panicked: {e}"),
+                Some(_) => {}
+                None => break,
+            },
+            _ = progress.tick() => println!("{}", stats.progress(started.elapsed())),
+            _ = signal::ctrl_c() => {
+                println!("interrupted, reporting what was measured so far");
+                break;
+            }
+        }
+    }
+
+    println!("\n{}", stats.report(args.players, started.elapsed()));
+}
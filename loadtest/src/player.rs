@@ -0,0 +1,260 @@
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
+
+use chessclouds_client::{
+    protocol::game::{ErrorCode, ServerMessage},
+    Error, Event, GameClient, GameConnection, Match, MatchmakingClient,
+};
+use rand::{rngs::StdRng, seq::IndexedRandom, Rng};
+use shakmaty::{san::San, Chess, Color, Position};
+use tokio::time::{sleep, timeout};
+
+use crate::stats::{GameResult, Phase, Stats};
+
+/// Settings shared by every virtual player.
+pub struct Settings {
+    pub matchmaking: MatchmakingClient,
+    pub game: GameClient,
+    pub move_delay: Duration,
+    pub max_plies: usize,
+    pub disconnect_rate: f64,
+    pub timeout: Duration,
+}
+
+/// A simulated user: finds a match, then plays random legal moves until the
+/// game ends or `max_plies` moves were played.
+pub struct Player {
+    pub user_id: String,
+    pub settings: Arc<Settings>,
+    pub stats: Arc<Stats>,
+    pub rng: StdRng,
+}
+
+/// Why a player stopped waiting for messages.
+enum Stop {
+    GameEnd,
+    /// The move was dropped by the server's rate limit, try again later.
+    Retry,
+    Failed,
+}
+
+impl Player {
+    pub async fn run(mut self) {
+        let Some(found) = self.find_match().await else {
+            return;
+        };
+        self.stats.active_games.fetch_add(1, Ordering::Relaxed);
+        let result = self.play(&found).await;
+        self.stats.active_games.fetch_sub(1, Ordering::Relaxed);
+        self.stats.game_result(result);
+    }
+
+    async fn find_match(&mut self) -> Option<Match> {
+        loop {
+            self.stats.request();
+            let started = Instant::now();
+            match self.settings.matchmaking.find_match(&self.user_id).await {
+                Ok(found) => {
+                    self.stats.record(Phase::Match, started.elapsed());
+                    return Some(found);
+                }
+                Err(e @ Error::RateLimited(retry_after)) => {
+                    self.stats.client_error(Phase::Match, &e);
+                    sleep(retry_after.unwrap_or(Duration::from_secs(1))).await;
+                }
+                Err(e) => {
+                    self.stats.client_error(Phase::Match, &e);
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Joins the game and rebuilds the board from the snapshot it starts with.
+    async fn join(&mut self, game_id: &str) -> Option<(GameConnection, Chess)> {
+        self.stats.request();
+        let started = Instant::now();
+        let mut conn = match self.settings.game.join(game_id, &self.user_id).await {
+            Ok(conn) => conn,
+            Err(e) => {
+                self.stats.client_error(Phase::Join, &e);
+                return None;
+            }
+        };
+        match timeout(self.settings.timeout, conn.next_message()).await {
+            Ok(Some(ServerMessage::GameSnapshot(snapshot))) => {
+                self.stats.record(Phase::Join, started.elapsed());
+                match replay(&snapshot.moves) {
+                    Some(board) => Some((conn, board)),
+                    None => {
+                        self.stats.error(Phase::Join, "snapshot with illegal moves");
+                        None
+                    }
+                }
+            }
+            Ok(_) => {
+                self.stats.error(Phase::Join, "no snapshot");
+                None
+            }
+            Err(_) => {
+                self.stats.client_error(Phase::Join, &Error::Timeout);
+                None
+            }
+        }
+    }
+
+    async fn play(&mut self, found: &Match) -> GameResult {
+        let color = Color::from(found.color);
+        let Some((mut conn, mut board)) = self.join(&found.game_id).await else {
+            return GameResult::Failed;
+        };
+
+        loop {
+            if board.outcome().is_some() {
+                return GameResult::Finished;
+            }
+            if ply(&board) >= self.settings.max_plies {
+                return GameResult::CutOff;
+            }
+            if board.turn() != color {
+                match self.wait_for_move(&mut conn, &mut board, None).await {
+                    Ok(()) | Err(Stop::Retry) => continue,
+                    Err(Stop::GameEnd) => return GameResult::Finished,
+                    Err(Stop::Failed) => return GameResult::Failed,
+                }
+            }
+
+            if self.rng.random_bool(self.settings.disconnect_rate) {
+                self.stats.reconnects.fetch_add(1, Ordering::Relaxed);
+                conn.close();
+                drop(conn);
+                match self.join(&found.game_id).await {
+                    Some((new_conn, new_board)) => (conn, board) = (new_conn, new_board),
+                    None => return GameResult::Failed,
+                }
+                continue;
+            }
+
+            sleep(self.think_time()).await;
+            let legal_moves = board.legal_moves();
+            let m = legal_moves
+                .choose(&mut self.rng)
+                .expect("ongoing games have legal moves");
+            let san = San::from_move(&board, m).to_string();
+            self.stats.request();
+            if let Err(e) = conn.play(&san) {
+                self.stats.client_error(Phase::Move, &e);
+                return GameResult::Failed;
+            }
+            let sent = Instant::now();
+            match self.wait_for_move(&mut conn, &mut board, Some(&san)).await {
+                Ok(()) => self.stats.record(Phase::Move, sent.elapsed()),
+                Err(Stop::Retry) => sleep(self.settings.move_delay).await,
+                Err(Stop::GameEnd) => return GameResult::Finished,
+                Err(Stop::Failed) => return GameResult::Failed,
+            }
+        }
+    }
+
+    /// Waits for the next move and plays it on `board`. With `own` set,
+    /// waits for that move of this player to be broadcast instead.
+    async fn wait_for_move(
+        &mut self,
+        conn: &mut GameConnection,
+        board: &mut Chess,
+        own: Option<&str>,
+    ) -> Result<(), Stop> {
+        // the opponent thinks too
+        let deadline = self.settings.timeout + self.settings.move_delay * 2;
+        loop {
+            let event = match timeout(deadline, conn.next_event()).await {
+                Ok(Some(event)) => event,
+                Ok(None) => {
+                    self.stats.client_error(Phase::Move, &Error::Closed);
+                    return Err(Stop::Failed);
+                }
+                Err(_) => {
+                    self.stats.client_error(Phase::Move, &Error::Timeout);
+                    return Err(Stop::Failed);
+                }
+            };
+            let message = match event {
+                Event::Message { message, .. } => message,
+                Event::Disconnected => {
+                    self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+                Event::Reconnected => continue,
+                Event::Closed(e) => {
+                    let e = e.unwrap_or(Error::Closed);
+                    self.stats.client_error(Phase::Move, &e);
+                    return Err(Stop::Failed);
+                }
+            };
+            match message {
+                ServerMessage::Move(san) => {
+                    if !play_san(board, &san) {
+                        self.stats.error(Phase::Move, "illegal move broadcast");
+                        return Err(Stop::Failed);
+                    }
+                    if own.is_none_or(|own| own == san) {
+                        return Ok(());
+                    }
+                }
+                ServerMessage::GameEnd(_) => return Err(Stop::GameEnd),
+                ServerMessage::Error(err) => {
+                    self.stats.error(Phase::Move, format!("{:?}", err.code));
+                    // a rejected move of ours is never broadcast
+                    if own.is_some() {
+                        return Err(match err.code {
+                            ErrorCode::RateLimited => Stop::Retry,
+                            _ => Stop::Failed,
+                        });
+                    }
+                }
+                // a resumed session may start over with a snapshot
+                ServerMessage::GameSnapshot(snapshot) => match replay(&snapshot.moves) {
+                    Some(replayed) => *board = replayed,
+                    None => {
+                        self.stats.error(Phase::Move, "snapshot with illegal moves");
+                        return Err(Stop::Failed);
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+
+    /// Between half and one and a half times the configured move delay.
+    fn think_time(&mut self) -> Duration {
+        self.settings
+            .move_delay
+            .mul_f64(self.rng.random_range(0.5..1.5))
+    }
+}
+
+fn ply(board: &Chess) -> usize {
+    (board.fullmoves().get() as usize - 1) * 2 + usize::from(board.turn() == Color::Black)
+}
+
+fn play_san(board: &mut Chess, san: &str) -> bool {
+    let Some(m) = san
+        .parse::<San>()
+        .ok()
+        .and_then(|san| san.to_move(board).ok())
+    else {
+        return false;
+    };
+    board.play_unchecked(&m);
+    true
+}
+
+fn replay(moves: &[String]) -> Option<Chess> {
+    let mut board = Chess::default();
+    moves
+        .iter()
+        .all(|san| play_san(&mut board, san))
+        .then_some(board)
+}
@@ -0,0 +1,246 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use chessclouds_client::Error;
+
+/// Where in a player's life a request was made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    Match,
+    Join,
+    Move,
+}
+
+impl Phase {
+    fn name(self) -> &'static str {
+        match self {
+            Phase::Match => "match",
+            Phase::Join => "join",
+            Phase::Move => "move",
+        }
+    }
+}
+
+/// How a game a virtual player was in ended for them.
+pub enum GameResult {
+    /// Checkmate, stalemate or insufficient material.
+    Finished,
+    /// Left after `--max-plies` moves.
+    CutOff,
+    /// Abandoned after an error.
+    Failed,
+}
+
+/// Measurements of all virtual players, shared between their tasks.
+#[derive(Default)]
+pub struct Stats {
+    pub requests: AtomicU64,
+    pub moves: AtomicU64,
+    pub active_games: AtomicU64,
+    pub finished: AtomicU64,
+    pub cut_off: AtomicU64,
+    pub failed: AtomicU64,
+    /// Reconnects the players chose to make.
+    pub reconnects: AtomicU64,
+    /// Connections that dropped and were resumed by the client library.
+    pub dropped: AtomicU64,
+    match_times: Mutex<Vec<Duration>>,
+    join_times: Mutex<Vec<Duration>>,
+    move_times: Mutex<Vec<Duration>>,
+    errors: Mutex<BTreeMap<(Phase, String), u64>>,
+}
+
+impl Stats {
+    pub fn request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record(&self, phase: Phase, elapsed: Duration) {
+        let samples = match phase {
+            Phase::Match => &self.match_times,
+            Phase::Join => &self.join_times,
+            Phase::Move => {
+                self.moves.fetch_add(1, Ordering::Relaxed);
+                &self.move_times
+            }
+        };
+        samples.lock().unwrap().push(elapsed);
+    }
+
+    pub fn error(&self, phase: Phase, kind: impl Into<String>) {
+        let mut errors = self.errors.lock().unwrap();
+        *errors.entry((phase, kind.into())).or_default() += 1;
+    }
+
+    pub fn client_error(&self, phase: Phase, e: &Error) {
+        self.error(phase, error_kind(e));
+    }
+
+    /// Counts both players' games, each player reports its own.
+    pub fn game_result(&self, result: GameResult) {
+        let counter = match result {
+            GameResult::Finished => &self.finished,
+            GameResult::CutOff => &self.cut_off,
+            GameResult::Failed => &self.failed,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// One line of progress, printed while the test runs.
+    pub fn progress(&self, elapsed: Duration) -> String {
+        let moves = self.moves.load(Ordering::Relaxed);
+        format!(
+            "{:>5}s  {} games active, {} moves ({:.1}/s), {} errors",
+            elapsed.as_secs(),
+            self.active_games.load(Ordering::Relaxed) / 2,
+            moves,
+            moves as f64 / elapsed.as_secs_f64().max(1.0),
+            self.errors.lock().unwrap().values().sum::<u64>(),
+        )
+    }
+
+    pub fn report(&self, players: usize, elapsed: Duration) -> String {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let requests = load(&self.requests);
+        let moves = load(&self.moves);
+        let errors = self.errors.lock().unwrap();
+        let error_count: u64 = errors.values().sum();
+
+        let mut report = String::new();
+        writeln!(report, "players      {players} ({} games)", players / 2).unwrap();
+        writeln!(report, "duration     {:.1}s", elapsed.as_secs_f64()).unwrap();
+        // every game is counted by both of its players
+        writeln!(
+            report,
+            "games        {} finished, {} cut off, {} failed",
+            load(&self.finished) / 2,
+            load(&self.cut_off) / 2,
+            load(&self.failed).div_ceil(2),
+        )
+        .unwrap();
+        writeln!(
+            report,
+            "moves        {moves} ({:.1}/s)",
+            moves as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+        )
+        .unwrap();
+        writeln!(
+            report,
+            "reconnects   {} by players, {} dropped connections",
+            load(&self.reconnects),
+            load(&self.dropped)
+        )
+        .unwrap();
+        writeln!(
+            report,
+            "errors       {error_count} of {requests} requests ({:.2}%)",
+            100.0 * error_count as f64 / requests.max(1) as f64
+        )
+        .unwrap();
+        for ((phase, kind), count) in errors.iter() {
+            writeln!(report, "  {:<6} {kind}: {count}", phase.name()).unwrap();
+        }
+
+        writeln!(
+            report,
+            "\nlatency (ms)      count      p50      p90      p99      max"
+        )
+        .unwrap();
+        for (name, samples) in [
+            ("match", &self.match_times),
+            ("join", &self.join_times),
+            ("move round-trip", &self.move_times),
+        ] {
+            let mut samples = samples.lock().unwrap();
+            samples.sort_unstable();
+            write!(report, "{name:<16}{:>7}", samples.len()).unwrap();
+            for p in [0.5, 0.9, 0.99, 1.0] {
+                match percentile(&samples, p) {
+                    Some(d) => write!(report, " {:>8.1}", d.as_secs_f64() * 1000.0).unwrap(),
+                    None => write!(report, " {:>8}", "-").unwrap(),
+                }
+            }
+            report.push('\n');
+        }
+        report
+    }
+}
+
+/// Nearest-rank percentile of sorted samples.
+fn percentile(sorted: &[Duration], p: f64) -> Option<Duration> {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted.get(rank.max(1) - 1).copied()
+}
+
+/// Groups errors by what went wrong rather than by their full message.
+fn error_kind(e: &Error) -> String {
+    match e {
+        Error::Http(_) => "HTTP request failed".to_string(),
+        Error::WebSocket(_) => "WebSocket error".to_string(),
+        Error::Malformed(_) => "malformed server message".to_string(),
+        Error::Status(status, _) => format!("status {status}"),
+        Error::Rejected(err) => format!("{:?}", err.code),
+        Error::Matchmaking(message) => message.clone(),
+        Error::RateLimited(_) => "rate limited".to_string(),
+        Error::Timeout => "timeout".to_string(),
+        Error::Closed => "connection closed".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let samples: Vec<Duration> = (1..=100).map(ms).collect();
+        assert_eq!(percentile(&samples, 0.5), Some(ms(50)));
+        assert_eq!(percentile(&samples, 0.9), Some(ms(90)));
+        assert_eq!(percentile(&samples, 0.99), Some(ms(99)));
+        assert_eq!(percentile(&samples, 1.0), Some(ms(100)));
+
+        assert_eq!(percentile(&[ms(7)], 0.5), Some(ms(7)));
+        assert_eq!(percentile(&[ms(1), ms(2), ms(3)], 0.0), Some(ms(1)));
+        assert_eq!(percentile(&[], 0.5), None);
+    }
+
+    #[test]
+    fn report_counts_games_once_and_groups_errors() {
+        let stats = Stats::default();
+        for _ in 0..4 {
+            stats.request();
+        }
+        stats.record(Phase::Move, ms(30));
+        stats.record(Phase::Move, ms(10));
+        stats.record(Phase::Match, ms(200));
+        stats.game_result(GameResult::Finished);
+        stats.game_result(GameResult::Finished);
+        stats.client_error(Phase::Join, &Error::Timeout);
+        stats.client_error(Phase::Join, &Error::Timeout);
+
+        let report = stats.report(2, Duration::from_secs(1));
+        assert!(report.contains("games        1 finished, 0 cut off, 0 failed"));
+        assert!(report.contains("moves        2 (2.0/s)"));
+        assert!(report.contains("errors       2 of 4 requests (50.00%)"));
+        assert!(report.contains("  join   timeout: 2"));
+        let moves = report
+            .lines()
+            .find(|line| line.starts_with("move round-trip"))
+            .unwrap();
+        assert_eq!(
+            moves.split_whitespace().collect::<Vec<_>>(),
+            ["move", "round-trip", "2", "10.0", "30.0", "30.0", "30.0"]
+        );
+    }
+}
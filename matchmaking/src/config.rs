@@ -0,0 +1,158 @@
+use std::{env, net::SocketAddr, path::PathBuf};
+
+pub use chessclouds_telemetry::{LogConfig, LogFormat};
+use config::{ConfigError, Environment, File, FileFormat};
+use serde::{Deserialize, Serialize};
+
+/// Environment variables starting with this prefix override the config file,
+/// with `__` separating sections, e.g. `MATCHMAKING_DATABASE__MAX_CONNECTIONS=10`.
+pub const ENV_PREFIX: &str = "MATCHMAKING";
+/// Read when it exists and no `--config` path is given.
+pub const DEFAULT_CONFIG_PATH: &str = "matchmaking.toml";
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Config {
+    /// Address the HTTP listener binds to.
+    pub host: String,
+    pub database: DatabaseConfig,
+    pub queue: QueueConfig,
+    pub rate_limit: RateLimitConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DatabaseConfig {
+    pub max_connections: u32,
+    /// `/readyz` reports the database as unreachable after this long.
+    pub health_check_timeout_secs: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct QueueConfig {
+    /// Capacity of the channel notifying the matcher of new requests.
+    pub channel_size: usize,
+}
+
+/// Token buckets limiting `/match` requests: each user and each client IP
+/// may send `*_burst` requests at once and `*_per_minute` after that.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RateLimitConfig {
+    pub user_burst: u32,
+    pub user_per_minute: u32,
+    pub ip_burst: u32,
+    pub ip_per_minute: u32,
+    /// Use the first `X-Forwarded-For` address as the client IP. Only enable
+    /// behind a proxy that sets the header.
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            host: "0.0.0.0:8001".to_string(),
+            database: DatabaseConfig {
+                max_connections: 5,
+                health_check_timeout_secs: 2,
+            },
+            queue: QueueConfig { channel_size: 4096 },
+            rate_limit: RateLimitConfig {
+                user_burst: 5,
+                user_per_minute: 10,
+                ip_burst: 20,
+                ip_per_minute: 60,
+                trust_forwarded_for: false,
+            },
+            log: LogConfig {
+                format: LogFormat::Text,
+                filter: "info".to_string(),
+                otlp_endpoint: None,
+            },
+        }
+    }
+}
+
+impl Config {
+    /// Built-in defaults, overridden by the TOML file at `path` (or
+    /// `DEFAULT_CONFIG_PATH` if present), overridden by `MATCHMAKING_*`
+    /// environment variables.
+    pub fn load(path: Option<PathBuf>) -> Result<Self, ConfigError> {
+        let file = match path {
+            Some(path) => File::from(path).format(FileFormat::Toml),
+            None => File::new(DEFAULT_CONFIG_PATH, FileFormat::Toml).required(false),
+        };
+
+        let config: Config = config::Config::builder()
+            .add_source(config::Config::try_from(&Config::default())?)
+            .add_source(file)
+            .add_source(
+                Environment::with_prefix(ENV_PREFIX)
+                    .prefix_separator("_")
+                    .separator("__")
+                    .try_parsing(true),
+            )
+            .build()?
+            .try_deserialize()?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |msg: &str| Err(ConfigError::Message(msg.to_string()));
+
+        if self.host.parse::<SocketAddr>().is_err() {
+            return invalid("host must be an address like 0.0.0.0:8001");
+        }
+        if self.database.max_connections == 0 || self.database.health_check_timeout_secs == 0 {
+            return invalid(
+                "database.max_connections and database.health_check_timeout_secs must be positive",
+            );
+        }
+        if self.queue.channel_size == 0 {
+            return invalid("queue.channel_size must be positive");
+        }
+        let limits = &self.rate_limit;
+        if limits.user_burst == 0
+            || limits.user_per_minute == 0
+            || limits.ip_burst == 0
+            || limits.ip_per_minute == 0
+        {
+            return invalid("rate_limit bursts and rates must be positive");
+        }
+        if let Err(msg) = self.log.validate() {
+            return invalid(msg);
+        }
+        Ok(())
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("config should serialize")
+    }
+}
+
+/// Command line options of the service binary.
+#[derive(Default)]
+pub struct Args {
+    /// `--config <path>`
+    pub config_path: Option<PathBuf>,
+    /// `--print-config`: print the effective configuration and exit.
+    pub print_config: bool,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, String> {
+        let mut args = Args::default();
+        let mut argv = env::args().skip(1);
+        while let Some(arg) = argv.next() {
+            match arg.as_str() {
+                "--config" => {
+                    let path = argv.next().ok_or("--config expects a path")?;
+                    args.config_path = Some(path.into());
+                }
+                "--print-config" => args.print_config = true,
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+        Ok(args)
+    }
+}
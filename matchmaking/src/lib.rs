@@ -0,0 +1,309 @@
+pub mod config;
+pub mod metrics;
+pub mod rate_limit;
+
+use std::{
+    collections::VecDeque,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{config::Config, metrics::Metrics, rate_limit::RateLimits};
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use chessclouds_protocol::{
+    matchmaking::{MatchRequest, MatchResponse},
+    Color,
+};
+use chessclouds_store::{GameStore, NewGame};
+use sqlx::{Pool, Postgres};
+use tokio::sync::{mpsc, oneshot};
+use tower_http::{
+    cors::{AllowOrigin, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::Level;
+
+type ConcurrentQueue<T> = Arc<Mutex<VecDeque<T>>>;
+
+struct AutoDrop {
+    user_id: String,
+    queue: ConcurrentQueue<MatchingPlayer>,
+}
+
+impl Drop for AutoDrop {
+    fn drop(&mut self) {
+        tracing::info!(
+            "User {} closed connection, removing matching player record",
+            self.user_id
+        );
+        self.queue
+            .lock()
+            .unwrap()
+            .retain(|x| x.user_id != self.user_id)
+    }
+}
+
+pub struct MatchingPlayer {
+    pub user_id: String,
+    pub tx: oneshot::Sender<MatchResponse>,
+    pub queued_at: Instant,
+}
+
+async fn matcher(
+    mut rx: mpsc::Receiver<()>,
+    player_queue: ConcurrentQueue<MatchingPlayer>,
+    store: Arc<dyn GameStore>,
+    metrics: Arc<Metrics>,
+) {
+    while rx.recv().await.is_some() {
+        loop {
+            let (player1, player2) = {
+                let mut queue = player_queue.lock().unwrap();
+                if queue.len() < 2 {
+                    break;
+                }
+                (
+                    queue.pop_front().expect("Player should exist"),
+                    queue.pop_front().expect("Player should exist"),
+                )
+            };
+
+            let game_id = uuid::Uuid::new_v4();
+
+            let game = NewGame {
+                game_id,
+                white: player1.user_id.clone(),
+                black: player2.user_id.clone(),
+                time_control: None,
+                allow_takebacks: true,
+            };
+            if let Err(e) = store.create_game(game).await {
+                let _ = player1.tx.send(MatchResponse::Err(
+                    format!("DB insertion failed: {e}]").to_string(),
+                ));
+                let _ = player2.tx.send(MatchResponse::Err(
+                    format!("DB insertion failed: {e}").to_string(),
+                ));
+                metrics.match_failures.inc();
+                continue;
+            }
+
+            tracing::info!("Matched {} and {}", player1.user_id, player2.user_id);
+            metrics.matches.inc();
+            for player in [&player1, &player2] {
+                metrics
+                    .match_wait_seconds
+                    .observe(player.queued_at.elapsed().as_secs_f64());
+            }
+
+            if player1
+                .tx
+                .send(MatchResponse::Ok {
+                    game_id: game_id.to_string(),
+                    color: Color::White,
+                })
+                .is_err()
+            {
+                tracing::error!("Connection closed unexpectedly, proceeding anyway");
+            }
+            if player2
+                .tx
+                .send(MatchResponse::Ok {
+                    game_id: game_id.to_string(),
+                    color: Color::Black,
+                })
+                .is_err()
+            {
+                tracing::error!("Connection closed unexpectedly, proceeding anyway");
+            }
+        }
+    }
+}
+
+pub async fn post_match(
+    notify_tx: mpsc::Sender<()>,
+    player_queue: ConcurrentQueue<MatchingPlayer>,
+    limits: Arc<RateLimits>,
+    metrics: Arc<Metrics>,
+    peer: SocketAddr,
+    headers: HeaderMap,
+    Json(body): Json<MatchRequest>,
+) -> Response {
+    if let Err((limit, retry_after)) = limits.check(&body.user_id, peer, &headers) {
+        tracing::warn!("User {} exceeded the {limit} rate limit", body.user_id);
+        metrics.rate_limited.with_label_values(&[limit]).inc();
+        let retry_after = retry_after.as_secs_f64().ceil() as u64;
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, retry_after.to_string())],
+            Json(MatchResponse::Err("Too many requests".to_string())),
+        )
+            .into_response();
+    }
+
+    let (res_tx, res_rx) = oneshot::channel();
+
+    {
+        let mut queue = player_queue.lock().unwrap();
+
+        if queue.iter().any(|player| player.user_id == body.user_id) {
+            tracing::error!("User {} already in queue", body.user_id);
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(MatchResponse::Err("Player already in queue".to_string())),
+            )
+                .into_response();
+        }
+
+        queue.push_back(MatchingPlayer {
+            user_id: body.user_id.clone(),
+            tx: res_tx,
+            queued_at: Instant::now(),
+        });
+    }
+
+    tracing::info!("User {} requests a match", body.user_id);
+
+    #[allow(unused_variables)]
+    let auto_drop = AutoDrop {
+        user_id: body.user_id.clone(),
+        queue: player_queue.clone(),
+    };
+
+    notify_tx.send(()).await.unwrap();
+
+    let res = res_rx.await.unwrap();
+    if res.is_err() {
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(res)).into_response()
+    } else {
+        (StatusCode::OK, Json(res)).into_response()
+    }
+}
+
+pub async fn get_metrics(
+    player_queue: ConcurrentQueue<MatchingPlayer>,
+    metrics: Arc<Metrics>,
+) -> impl IntoResponse {
+    let queue_length = player_queue.lock().unwrap().len();
+    metrics.queue_length.set(queue_length as i64);
+    (
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        metrics.encode(),
+    )
+}
+
+/// Liveness: the process is up and serving requests.
+pub async fn get_healthz() -> (StatusCode, &'static str) {
+    (StatusCode::OK, "OK")
+}
+
+/// Readiness: the database games are created in is reachable.
+pub async fn get_readyz(pool: Pool<Postgres>, timeout: Duration) -> (StatusCode, &'static str) {
+    match tokio::time::timeout(timeout, sqlx::query("SELECT 1").execute(&pool)).await {
+        Ok(Ok(_)) => (StatusCode::OK, "OK"),
+        Ok(Err(e)) => {
+            tracing::error!("readiness check failed: {e}");
+            (StatusCode::SERVICE_UNAVAILABLE, "Database unreachable")
+        }
+        Err(_) => {
+            tracing::error!("readiness check timed out");
+            (StatusCode::SERVICE_UNAVAILABLE, "Database unreachable")
+        }
+    }
+}
+
+/// The routes of the service. Spawns the matcher, which pairs queued players
+/// and creates their games in `store`, so it must be called within a runtime.
+/// `pool` is only checked by `/readyz`.
+///
+/// Serve it with `into_make_service_with_connect_info::<SocketAddr>()`, the
+/// per-IP rate limit needs the peer address.
+pub fn app(config: &Config, pool: Pool<Postgres>, store: Arc<dyn GameStore>) -> Router {
+    let (notify_tx, notify_rx) = mpsc::channel(config.queue.channel_size);
+
+    let player_queue = Arc::new(Mutex::new(VecDeque::<MatchingPlayer>::new()));
+    let metrics = Arc::new(Metrics::new());
+    let limits = Arc::new(RateLimits::new(&config.rate_limit));
+
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::any())
+        .allow_methods([Method::POST])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::ACCEPT,
+            header::ORIGIN,
+            header::AUTHORIZATION,
+        ])
+        .allow_credentials(false);
+
+    let app = Router::new()
+        .route(
+            "/match",
+            post({
+                let cloned_queue = player_queue.clone();
+                let cloned_metrics = metrics.clone();
+                move |ConnectInfo(peer): ConnectInfo<SocketAddr>, headers: HeaderMap, body| {
+                    post_match(
+                        notify_tx,
+                        cloned_queue,
+                        limits,
+                        cloned_metrics,
+                        peer,
+                        headers,
+                        body,
+                    )
+                }
+            }),
+        )
+        .route(
+            "/metrics",
+            get({
+                let cloned_queue = player_queue.clone();
+                let cloned_metrics = metrics.clone();
+                move || get_metrics(cloned_queue, cloned_metrics)
+            }),
+        )
+        .route("/healthz", get(get_healthz))
+        .route(
+            "/readyz",
+            get({
+                let cloned_pool = pool.clone();
+                let timeout = Duration::from_secs(config.database.health_check_timeout_secs);
+                move || get_readyz(cloned_pool, timeout)
+            }),
+        )
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(|req: &Request<Body>| {
+                    let request_id = req
+                        .headers()
+                        .get("x-request-id")
+                        .and_then(|id| id.to_str().ok())
+                        .unwrap_or_default();
+                    tracing::info_span!(
+                        "request",
+                        request_id,
+                        method = %req.method(),
+                        path = req.uri().path(),
+                    )
+                })
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
+        // outermost so the id is set before the span above is created
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .layer(cors);
+
+    tokio::spawn(matcher(notify_rx, player_queue.clone(), store, metrics));
+
+    app
+}
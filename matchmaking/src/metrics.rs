@@ -0,0 +1,82 @@
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+
+/// Prometheus metrics of the service, exported at `/metrics`.
+pub struct Metrics {
+    registry: Registry,
+    /// Refreshed from the queue on every scrape.
+    pub queue_length: IntGauge,
+    pub matches: IntCounter,
+    /// Matches that could not be stored in the database.
+    pub match_failures: IntCounter,
+    /// Time each matched player spent in the queue.
+    pub match_wait_seconds: Histogram,
+    /// Rejected `/match` requests by exceeded limit (`ip`, `user`).
+    pub rate_limited: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let queue_length =
+            IntGauge::new("matchmaking_queue_length", "Players waiting for a match").unwrap();
+        let matches = IntCounter::new("matchmaking_matches_total", "Games created").unwrap();
+        let match_failures = IntCounter::new(
+            "matchmaking_match_failures_total",
+            "Matches that failed to create a game",
+        )
+        .unwrap();
+        let match_wait_seconds = Histogram::with_opts(
+            HistogramOpts::new(
+                "matchmaking_match_wait_seconds",
+                "Time a player waited in the queue before being matched",
+            )
+            .buckets(vec![0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0]),
+        )
+        .unwrap();
+
+        let rate_limited = IntCounterVec::new(
+            Opts::new(
+                "matchmaking_rate_limited_total",
+                "Match requests rejected by a rate limit",
+            ),
+            &["limit"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(queue_length.clone())).unwrap();
+        registry.register(Box::new(matches.clone())).unwrap();
+        registry.register(Box::new(match_failures.clone())).unwrap();
+        registry
+            .register(Box::new(match_wait_seconds.clone()))
+            .unwrap();
+        registry.register(Box::new(rate_limited.clone())).unwrap();
+
+        Metrics {
+            registry,
+            queue_length,
+            matches,
+            match_failures,
+            match_wait_seconds,
+            rate_limited,
+        }
+    }
+
+    /// All metrics in the Prometheus text format.
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("metrics should encode");
+        String::from_utf8(buffer).expect("metrics should be UTF-8")
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,42 @@
+use std::{net::SocketAddr, time::Duration};
+
+use axum::http::HeaderMap;
+use chessclouds_rate_limit::{client_ip, TokenBuckets};
+
+use crate::config::RateLimitConfig;
+
+/// Limits on `/match`, per user and per client IP.
+pub struct RateLimits {
+    user: TokenBuckets,
+    ip: TokenBuckets,
+    trust_forwarded_for: bool,
+}
+
+impl RateLimits {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        RateLimits {
+            user: TokenBuckets::new(config.user_burst, config.user_per_minute),
+            ip: TokenBuckets::new(config.ip_burst, config.ip_per_minute),
+            trust_forwarded_for: config.trust_forwarded_for,
+        }
+    }
+
+    /// Returns the exceeded limit, `"ip"` or `"user"`, and when to retry.
+    pub fn check(
+        &self,
+        user_id: &str,
+        peer: SocketAddr,
+        headers: &HeaderMap,
+    ) -> Result<(), (&'static str, Duration)> {
+        let forwarded_for = headers
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok());
+        let ip = client_ip(peer, forwarded_for, self.trust_forwarded_for);
+        self.ip
+            .try_acquire(ip.to_string())
+            .map_err(|retry_after| ("ip", retry_after))?;
+        self.user
+            .try_acquire(user_id.to_owned())
+            .map_err(|retry_after| ("user", retry_after))
+    }
+}
@@ -0,0 +1,24 @@
+//! Messages and types shared by the ChessClouds services and, through the
+//! generated TypeScript bindings, the web client.
+//!
+//! Types live in a module per protocol version; the crate root re-exports
+//! the current one. A breaking change gets a new module so servers can keep
+//! speaking the previous version to clients that have not been updated.
+
+pub mod v1;
+
+pub use v1::*;
+
+/// The protocol version spoken by this build.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The oldest protocol version servers still accept.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// The version to speak with a client that supports up to `client_version`,
+/// or `None` if it is older than [`MIN_PROTOCOL_VERSION`]. Clients that do
+/// not send a version predate negotiation and speak version 1.
+pub fn negotiate(client_version: Option<u32>) -> Option<u32> {
+    let version = client_version.unwrap_or(1).min(PROTOCOL_VERSION);
+    (version >= MIN_PROTOCOL_VERSION).then_some(version)
+}
@@ -0,0 +1,7 @@
+//! Version 1 of the protocol.
+
+pub mod engine;
+pub mod game;
+pub mod matchmaking;
+
+pub use game::{Color, Outcome};
@@ -0,0 +1,39 @@
+//! Responses of the Stockfish engine API.
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+#[derive(Serialize, Deserialize, Debug, Clone, TS)]
+#[ts(export)]
+pub struct MessageResponse {
+    pub message: String,
+}
+
+/// Result of `GET /test`, which checks that Stockfish answers `uci`.
+#[derive(Serialize, Deserialize, Debug, Clone, TS)]
+#[ts(export)]
+pub struct TestResponse {
+    pub status: String,
+    pub message: String,
+    pub response: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, TS)]
+#[ts(export)]
+pub struct BestMoveResponse {
+    pub best_move: String,
+    pub new_fen: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, TS)]
+#[ts(export)]
+pub struct ErrorResponse {
+    pub status: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub best_move: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub raw_response: Option<String>,
+}
@@ -0,0 +1,400 @@
+//! The WebSocket game protocol spoken with `ws_server`.
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// Body of ws_server's `POST /init`, which starts a game.
+#[derive(Serialize, Deserialize, Debug, Clone, TS)]
+#[ts(export)]
+pub struct InitBody {
+    pub game_id: String,
+    pub white_user_id: String,
+    pub black_user_id: String,
+    #[serde(default)]
+    #[ts(optional)]
+    pub white_rating: Option<u32>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub black_rating: Option<u32>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub settings: Option<GameSettings>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, TS)]
+#[ts(export)]
+pub struct GameSettings {
+    /// Rated games turn this off to forbid takebacks.
+    pub allow_takebacks: bool,
+    /// PGN `TimeControl` value such as `300+2`, informational only.
+    #[serde(default)]
+    #[ts(optional)]
+    pub time_control: Option<String>,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        GameSettings {
+            allow_takebacks: true,
+            time_control: None,
+        }
+    }
+}
+
+/// Machine-readable reason a message was rejected.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, TS)]
+#[ts(export)]
+pub enum ErrorCode {
+    /// The message is not valid JSON or not a known `ClientMessage`.
+    MalformedMessage,
+    /// The socket closed or sent no `Auth`/`Spectate` message.
+    NotAuthenticated,
+    /// No ongoing game has this id.
+    GameNotFound,
+    /// The user is not a player of this game.
+    NotAPlayer,
+    /// Spectators cannot move, premove or take back.
+    SpectatorNotAllowed,
+    /// The game exists but could not be loaded on this server.
+    GameUnavailable,
+    NotYourTurn,
+    GameOver,
+    /// The move is not valid SAN.
+    MalformedMove,
+    /// The move is valid SAN but not legal in the current position.
+    IllegalMove,
+    TakebackNotAllowed,
+    InvalidTakeback,
+    InvalidChat,
+    ChatRateLimited,
+    ChatRejected,
+    /// The message was dropped because the connection sent too many.
+    RateLimited,
+    /// The socket is closed for repeatedly exceeding the rate limit.
+    Flooding,
+    /// The socket is closed for not authenticating in time.
+    AuthTimeout,
+    ServerRestarting,
+    /// The client's protocol version is older than the server still accepts.
+    UnsupportedProtocolVersion,
+}
+
+/// Details that help a client explain or recover from an error.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, TS)]
+#[ts(export)]
+pub struct ErrorContext {
+    /// Legal moves in the current position.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub legal_moves: Option<u32>,
+    /// Longest accepted chat message, in characters.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub max_length: Option<u32>,
+    /// Oldest protocol version the server accepts.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub min_protocol_version: Option<u32>,
+    /// Newest protocol version the server speaks.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub max_protocol_version: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, TS)]
+#[ts(export)]
+pub struct Error {
+    pub code: ErrorCode,
+    /// Human-readable description, not meant to be matched on.
+    pub message: String,
+    /// The part of the client message that was rejected, e.g. the move.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub input: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub context: Option<ErrorContext>,
+}
+
+impl Error {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Error {
+            code,
+            message: message.into(),
+            input: None,
+            context: None,
+        }
+    }
+
+    pub fn with_input(mut self, input: impl Into<String>) -> Self {
+        self.input = Some(input.into());
+        self
+    }
+
+    pub fn with_legal_moves(mut self, legal_moves: usize) -> Self {
+        self.context
+            .get_or_insert_with(Default::default)
+            .legal_moves = Some(legal_moves as u32);
+        self
+    }
+
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.context.get_or_insert_with(Default::default).max_length = Some(max_length as u32);
+        self
+    }
+
+    pub fn with_protocol_versions(mut self, min: u32, max: u32) -> Self {
+        let context = self.context.get_or_insert_with(Default::default);
+        context.min_protocol_version = Some(min);
+        context.max_protocol_version = Some(max);
+        self
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)?;
+        if let Some(input) = &self.input {
+            write!(f, " ({input:?})")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, TS)]
+#[serde(tag = "kind", content = "value")]
+#[ts(export)]
+pub enum ClientMessage {
+    Auth {
+        game_id: String,
+        user_id: String,
+        /// Sequence number of the last game message the client has seen, used
+        /// to resume a dropped session without a full snapshot.
+        #[serde(default)]
+        #[ts(optional, type = "number")]
+        last_seq: Option<u64>,
+        /// Newest protocol version the client speaks. Clients that predate
+        /// version negotiation omit it and are treated as version 1.
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        protocol_version: Option<u32>,
+    },
+    /// Joins a game as a spectator. Spectators receive every game message
+    /// but can only chat on the spectator channel.
+    Spectate {
+        game_id: String,
+        user_id: String,
+        #[serde(default)]
+        #[ts(optional, type = "number")]
+        last_seq: Option<u64>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        protocol_version: Option<u32>,
+    },
+    Move(String),
+    /// Queues a move to be played as soon as the opponent has moved.
+    Premove(String),
+    CancelPremove,
+    RequestTakeback,
+    AcceptTakeback,
+    DeclineTakeback,
+    Chat(String),
+    /// Hides chat messages from the given user for the rest of the game.
+    Mute(String),
+    Unmute(String),
+    Ping,
+}
+
+// shakmaty does not derive serde for these, so the protocol has its own
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, TS)]
+#[ts(export)]
+pub enum Color {
+    Black,
+    White,
+}
+
+impl From<shakmaty::Color> for Color {
+    fn from(color: shakmaty::Color) -> Self {
+        match color {
+            shakmaty::Color::Black => Color::Black,
+            shakmaty::Color::White => Color::White,
+        }
+    }
+}
+
+impl From<Color> for shakmaty::Color {
+    fn from(color: Color) -> Self {
+        match color {
+            Color::Black => shakmaty::Color::Black,
+            Color::White => shakmaty::Color::White,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, TS)]
+#[ts(export)]
+pub enum Outcome {
+    Decisive { winner: Color },
+    Draw,
+}
+
+impl From<shakmaty::Outcome> for Outcome {
+    fn from(outcome: shakmaty::Outcome) -> Self {
+        match outcome {
+            shakmaty::Outcome::Decisive { winner } => Outcome::Decisive {
+                winner: winner.into(),
+            },
+            shakmaty::Outcome::Draw => Outcome::Draw,
+        }
+    }
+}
+
+impl From<Outcome> for shakmaty::Outcome {
+    fn from(outcome: Outcome) -> Self {
+        match outcome {
+            Outcome::Decisive { winner } => shakmaty::Outcome::Decisive {
+                winner: winner.into(),
+            },
+            Outcome::Draw => shakmaty::Outcome::Draw,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, TS)]
+#[serde(tag = "kind", content = "value")]
+#[ts(export)]
+pub enum GameStatus {
+    OnGoing,
+    Ended(Outcome),
+}
+
+/// Everything a (re)connecting client needs to render the game without
+/// replaying moves itself.
+///
+/// There are no clocks or draw offers: the server does not run clocks (a
+/// game's `time_control` is only stored) and has no draw offer message, so
+/// neither has state to restore. Both belong here once they exist.
+#[derive(Serialize, Deserialize, Debug, Clone, TS)]
+#[ts(export)]
+pub struct GameSnapshot {
+    pub fen: String,
+    pub moves: Vec<String>,
+    pub turn: Color,
+    pub white_user_id: String,
+    pub black_user_id: String,
+    pub status: GameStatus,
+    /// The receiving player's color, `None` for spectators.
+    pub color: Option<Color>,
+    /// The receiving player's queued premove, if any.
+    pub premove: Option<String>,
+    pub takeback_requested_by: Option<Color>,
+    /// Sequence number of the last game message reflected in this snapshot.
+    #[ts(type = "number")]
+    pub seq: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, TS)]
+#[ts(export)]
+pub enum ChatChannel {
+    Players,
+    Spectators,
+}
+
+/// The protocol version both sides of a socket speak.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, TS)]
+#[ts(export)]
+pub struct Negotiated {
+    pub protocol_version: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, TS)]
+#[serde(tag = "kind", content = "value")]
+#[ts(export)]
+pub enum ServerMessage {
+    Move(String),
+    GameEnd(Outcome),
+    Error(Error),
+    /// The socket is authenticated. Carries the negotiated version only
+    /// when the `Auth` or `Spectate` message offered one: clients that
+    /// predate negotiation get `null`, which they read as no payload.
+    AuthSuccess(Option<Negotiated>),
+    GameSnapshot(GameSnapshot),
+    Pong,
+    /// Sent right before the server closes a socket whose seat was taken over
+    /// by a newer connection of the same player.
+    SessionReplaced,
+    /// The queued premove was not legal after the opponent's move.
+    PremoveDiscarded(String),
+    TakebackRequested(Color),
+    TakebackDeclined,
+    /// The position after an accepted takeback.
+    Takeback {
+        fen: String,
+        moves: Vec<String>,
+    },
+    Chat {
+        from: String,
+        text: String,
+        /// Unix timestamp in milliseconds.
+        #[ts(type = "number")]
+        ts: u64,
+        channel: ChatChannel,
+    },
+    /// The game is hosted by another server, reconnect to this URL.
+    Redirect(String),
+    /// The server is shutting down; reconnect after the given number of
+    /// seconds to continue the game, possibly on another server.
+    ServerRestarting {
+        #[ts(type = "number")]
+        reconnect_after: u64,
+    },
+}
+
+impl ServerMessage {
+    pub fn is_game_end(&self) -> bool {
+        matches!(self, Self::GameEnd(_))
+    }
+}
+
+/// A game-wide message numbered in broadcast order.
+#[derive(Serialize, Deserialize, Debug, Clone, TS)]
+#[ts(export)]
+pub struct SequencedMessage {
+    #[ts(type = "number")]
+    pub seq: u64,
+    #[serde(flatten)]
+    #[ts(flatten)]
+    pub message: ServerMessage,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auth_success_stays_readable_by_clients_without_negotiation() {
+        let json = serde_json::to_string(&ServerMessage::AuthSuccess(None)).unwrap();
+        assert_eq!(json, r#"{"kind":"AuthSuccess","value":null}"#);
+
+        let negotiated = ServerMessage::AuthSuccess(Some(Negotiated {
+            protocol_version: 1,
+        }));
+        let json = serde_json::to_string(&negotiated).unwrap();
+        assert_eq!(
+            json,
+            r#"{"kind":"AuthSuccess","value":{"protocol_version":1}}"#
+        );
+
+        // as sent by servers that predate negotiation
+        let old: ServerMessage = serde_json::from_str(r#"{"kind":"AuthSuccess"}"#).unwrap();
+        assert!(matches!(old, ServerMessage::AuthSuccess(None)));
+    }
+}
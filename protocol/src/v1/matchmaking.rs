@@ -0,0 +1,26 @@
+//! The matchmaking service's `/match` endpoint.
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::Color;
+
+#[derive(Serialize, Deserialize, Debug, Clone, TS)]
+#[ts(export)]
+pub struct MatchRequest {
+    pub user_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, TS)]
+#[serde(tag = "result", content = "value")]
+#[ts(export)]
+pub enum MatchResponse {
+    Ok { game_id: String, color: Color },
+    Err(String),
+}
+
+impl MatchResponse {
+    pub fn is_err(&self) -> bool {
+        matches!(self, MatchResponse::Err(_))
+    }
+}
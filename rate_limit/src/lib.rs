@@ -0,0 +1,127 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// How often buckets that have refilled completely are forgotten.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct Buckets<K> {
+    by_key: HashMap<K, Bucket>,
+    pruned: Instant,
+}
+
+/// One token bucket per key: a key may burst up to `capacity` requests and
+/// regains `per_minute` tokens every minute.
+pub struct TokenBuckets<K = String> {
+    capacity: f64,
+    refill_per_sec: f64,
+    buckets: Mutex<Buckets<K>>,
+}
+
+impl<K: Eq + Hash> TokenBuckets<K> {
+    pub fn new(capacity: u32, per_minute: u32) -> Self {
+        TokenBuckets {
+            capacity: capacity as f64,
+            refill_per_sec: per_minute as f64 / 60.0,
+            buckets: Mutex::new(Buckets {
+                by_key: HashMap::new(),
+                pruned: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token from `key`'s bucket, or returns how long until the
+    /// next one is available.
+    pub fn try_acquire(&self, key: K) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if now.duration_since(buckets.pruned) > PRUNE_INTERVAL {
+            buckets
+                .by_key
+                .retain(|_, bucket| self.refilled(bucket, now) < self.capacity);
+            buckets.pruned = now;
+        }
+
+        let bucket = buckets.by_key.entry(key).or_insert(Bucket {
+            tokens: self.capacity,
+            updated: now,
+        });
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.refill_per_sec,
+            ))
+        }
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.refill_per_sec).min(self.capacity)
+    }
+}
+
+/// The address to rate limit `peer` by: the first `X-Forwarded-For` entry
+/// when `trust_forwarded_for` is set and it parses, otherwise the peer's.
+pub fn client_ip(
+    peer: SocketAddr,
+    forwarded_for: Option<&str>,
+    trust_forwarded_for: bool,
+) -> IpAddr {
+    let forwarded = || forwarded_for?.split(',').next()?.trim().parse().ok();
+    match trust_forwarded_for {
+        true => forwarded().unwrap_or(peer.ip()),
+        false => peer.ip(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_bursts_per_key() {
+        let buckets = TokenBuckets::new(2, 1);
+        assert!(buckets.try_acquire("a").is_ok());
+        assert!(buckets.try_acquire("a").is_ok());
+        let retry_after = buckets.try_acquire("a").unwrap_err();
+        assert!(retry_after > Duration::from_secs(59) && retry_after <= Duration::from_secs(60));
+        assert!(buckets.try_acquire("b").is_ok());
+    }
+
+    #[test]
+    fn refills_over_time() {
+        let buckets = TokenBuckets::new(1, 6000);
+        assert!(buckets.try_acquire(1).is_ok());
+        assert!(buckets.try_acquire(1).is_err());
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(buckets.try_acquire(1).is_ok());
+    }
+
+    #[test]
+    fn uses_forwarded_for_only_when_trusted() {
+        let peer: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+        let forwarded = Some("203.0.113.7, 10.0.0.2");
+        assert_eq!(client_ip(peer, forwarded, false), peer.ip());
+        assert_eq!(
+            client_ip(peer, forwarded, true),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(client_ip(peer, Some("garbage"), true), peer.ip());
+        assert_eq!(client_ip(peer, None, true), peer.ip());
+    }
+}
@@ -0,0 +1,149 @@
+//! Persistence of games, shared by ws_server and matchmaking.
+//!
+//! Services talk to a [`GameStore`] instead of the `GameState` table, so
+//! they can run against [`PgGameStore`] in production and [`MemoryGameStore`]
+//! in tests or on a single node without Postgres.
+
+mod memory;
+pub mod pgn;
+mod postgres;
+
+use std::fmt;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use shakmaty::{Color, Outcome};
+use ts_rs::TS;
+use uuid::Uuid;
+
+pub use memory::MemoryGameStore;
+pub use postgres::PgGameStore;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// A game with this id already exists.
+    Duplicate(Uuid),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Duplicate(game_id) => write!(f, "game {game_id} already exists"),
+            Error::Database(e) => write!(f, "database error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Duplicate(_) => None,
+            Error::Database(e) => Some(e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(e: sqlx::Error) -> Self {
+        Error::Database(e)
+    }
+}
+
+/// The `"GameStatus"` enum of `GameState.Status`.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, TS)]
+// quoted, the type was created with a case-sensitive name
+#[sqlx(type_name = "\"GameStatus\"")]
+#[ts(export, rename = "DbGameStatus")]
+pub enum GameStatus {
+    #[sqlx(rename = "White Wins")]
+    #[serde(rename = "White Wins")]
+    WhiteWins,
+    #[sqlx(rename = "Black Wins")]
+    #[serde(rename = "Black Wins")]
+    BlackWins,
+    #[sqlx(rename = "Draw")]
+    #[serde(rename = "Draw")]
+    Draw,
+    #[sqlx(rename = "On Going")]
+    #[serde(rename = "On Going")]
+    OnGoing,
+    #[sqlx(rename = "Abort")]
+    #[serde(rename = "Abort")]
+    Aborted,
+}
+
+impl From<Outcome> for GameStatus {
+    fn from(outcome: Outcome) -> Self {
+        match outcome {
+            Outcome::Decisive {
+                winner: Color::White,
+            } => GameStatus::WhiteWins,
+            Outcome::Decisive {
+                winner: Color::Black,
+            } => GameStatus::BlackWins,
+            Outcome::Draw => GameStatus::Draw,
+        }
+    }
+}
+
+/// A game about to be played, e.g. between two matched players.
+#[derive(Debug, Clone)]
+pub struct NewGame {
+    pub game_id: Uuid,
+    pub white: String,
+    pub black: String,
+    /// PGN `TimeControl` value such as `300+2`, `None` when untimed.
+    pub time_control: Option<String>,
+    pub allow_takebacks: bool,
+}
+
+/// An accepted takeback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Takeback {
+    pub requested_by: Color,
+    /// The moves taken back, in the order they were played.
+    pub undone: Vec<String>,
+}
+
+/// An ongoing game as last persisted.
+#[derive(Debug, Clone)]
+pub struct StoredGame {
+    pub game_id: Uuid,
+    pub white: String,
+    pub black: String,
+    pub moves: Vec<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub time_control: Option<String>,
+    pub allow_takebacks: bool,
+    /// Oldest first.
+    pub takebacks: Vec<Takeback>,
+}
+
+/// Where games live between the moment they are matched and their end.
+///
+/// Writes only affect ongoing games: moving, finishing or aborting a game
+/// that is unknown or already over does nothing.
+#[async_trait]
+pub trait GameStore: Send + Sync {
+    async fn create_game(&self, game: NewGame) -> Result<()>;
+
+    /// The game if it exists and is still going on.
+    async fn load_game(&self, game_id: Uuid) -> Result<Option<StoredGame>>;
+
+    async fn append_move(&self, game_id: Uuid, san: &str) -> Result<()>;
+
+    /// Replaces all moves, e.g. after a takeback undid some of them.
+    async fn save_moves(&self, game_id: Uuid, moves: &[String]) -> Result<()>;
+
+    /// Removes the moves `takeback` undid and records it.
+    async fn take_back(&self, game_id: Uuid, takeback: &Takeback) -> Result<()>;
+
+    async fn finish_game(&self, game_id: Uuid, outcome: Outcome) -> Result<()>;
+
+    /// Marks a game nobody plays anymore as aborted.
+    async fn abort_game(&self, game_id: Uuid) -> Result<()>;
+}
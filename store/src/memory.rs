@@ -0,0 +1,106 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use shakmaty::Outcome;
+use uuid::Uuid;
+
+use crate::{Error, GameStatus, GameStore, NewGame, Result, StoredGame, Takeback};
+
+struct MemoryGame {
+    game: StoredGame,
+    status: GameStatus,
+}
+
+/// Games kept in process memory and lost on restart. Clones share the same
+/// games, so one store can back several services of a test.
+#[derive(Clone, Default)]
+pub struct MemoryGameStore {
+    games: Arc<Mutex<HashMap<Uuid, MemoryGame>>>,
+}
+
+impl MemoryGameStore {
+    /// The status of any game, including finished and aborted ones.
+    pub fn status(&self, game_id: Uuid) -> Option<GameStatus> {
+        let games = self.games.lock().unwrap();
+        games.get(&game_id).map(|game| game.status)
+    }
+
+    /// Applies `f` to the game if it is still going on.
+    fn update(&self, game_id: Uuid, f: impl FnOnce(&mut MemoryGame)) {
+        let mut games = self.games.lock().unwrap();
+        if let Some(game) = games.get_mut(&game_id) {
+            if game.status == GameStatus::OnGoing {
+                f(game);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl GameStore for MemoryGameStore {
+    async fn create_game(&self, game: NewGame) -> Result<()> {
+        let mut games = self.games.lock().unwrap();
+        if games.contains_key(&game.game_id) {
+            return Err(Error::Duplicate(game.game_id));
+        }
+        let stored = StoredGame {
+            game_id: game.game_id,
+            white: game.white,
+            black: game.black,
+            moves: Vec::new(),
+            created_at: Some(Utc::now()),
+            time_control: game.time_control,
+            allow_takebacks: game.allow_takebacks,
+            takebacks: Vec::new(),
+        };
+        games.insert(
+            game.game_id,
+            MemoryGame {
+                game: stored,
+                status: GameStatus::OnGoing,
+            },
+        );
+        Ok(())
+    }
+
+    async fn load_game(&self, game_id: Uuid) -> Result<Option<StoredGame>> {
+        let games = self.games.lock().unwrap();
+        Ok(games
+            .get(&game_id)
+            .filter(|game| game.status == GameStatus::OnGoing)
+            .map(|game| game.game.clone()))
+    }
+
+    async fn append_move(&self, game_id: Uuid, san: &str) -> Result<()> {
+        self.update(game_id, |game| game.game.moves.push(san.to_owned()));
+        Ok(())
+    }
+
+    async fn save_moves(&self, game_id: Uuid, moves: &[String]) -> Result<()> {
+        self.update(game_id, |game| game.game.moves = moves.to_vec());
+        Ok(())
+    }
+
+    async fn take_back(&self, game_id: Uuid, takeback: &Takeback) -> Result<()> {
+        self.update(game_id, |game| {
+            let moves = &mut game.game.moves;
+            moves.truncate(moves.len().saturating_sub(takeback.undone.len()));
+            game.game.takebacks.push(takeback.clone());
+        });
+        Ok(())
+    }
+
+    async fn finish_game(&self, game_id: Uuid, outcome: Outcome) -> Result<()> {
+        self.update(game_id, |game| game.status = outcome.into());
+        Ok(())
+    }
+
+    async fn abort_game(&self, game_id: Uuid) -> Result<()> {
+        self.update(game_id, |game| game.status = GameStatus::Aborted);
+        Ok(())
+    }
+}
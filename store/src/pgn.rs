@@ -0,0 +1,52 @@
+//! The movetext format moves are stored in.
+
+use std::fmt::Write;
+
+/// Numbered movetext without a result, e.g. `1. e4 e5 2. Nf3`, as stored in
+/// `GameState.PGN`.
+pub fn movetext(moves: &[String]) -> String {
+    let mut pgn = String::new();
+    for (i, san) in moves.iter().enumerate() {
+        if i > 0 {
+            pgn.push(' ');
+        }
+        if i % 2 == 0 {
+            write!(pgn, "{}. ", i / 2 + 1).unwrap();
+        }
+        pgn.push_str(san);
+    }
+    pgn
+}
+
+/// SAN moves of a PGN movetext, skipping tags, move numbers, comments,
+/// annotations and the result.
+pub fn moves_from_pgn(pgn: &str) -> Vec<String> {
+    let movetext: String = pgn
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('['))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut moves = Vec::new();
+    let mut in_comment = false;
+    for token in movetext.split_whitespace() {
+        if in_comment {
+            in_comment = !token.ends_with('}');
+            continue;
+        }
+        if token.starts_with('{') {
+            in_comment = !token.ends_with('}');
+            continue;
+        }
+        if matches!(token, "*" | "1-0" | "0-1" | "1/2-1/2") {
+            continue;
+        }
+        // strip move numbers glued to the move, e.g. `1.e4` or `3...Nf6`
+        let token = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        if token.is_empty() || token.starts_with('$') {
+            continue;
+        }
+        moves.push(token.to_owned());
+    }
+    moves
+}
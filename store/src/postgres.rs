@@ -0,0 +1,172 @@
+use async_trait::async_trait;
+use shakmaty::Outcome;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    pgn::{moves_from_pgn, movetext},
+    Error, GameStatus, GameStore, NewGame, Result, StoredGame, Takeback,
+};
+
+/// Games in the `GameState` table.
+#[derive(Clone)]
+pub struct PgGameStore {
+    pool: Pool<Postgres>,
+}
+
+impl PgGameStore {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        PgGameStore { pool }
+    }
+
+    async fn set_status(&self, game_id: Uuid, status: GameStatus) -> Result<()> {
+        sqlx::query!(
+            "UPDATE GameState SET Status = $2 WHERE GameID = $1 AND Status = $3",
+            game_id,
+            status as _,
+            GameStatus::OnGoing as _
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl GameStore for PgGameStore {
+    async fn create_game(&self, game: NewGame) -> Result<()> {
+        let inserted = sqlx::query!(
+            r#"INSERT INTO GameState (GameID, Black, White, PGN, TimeControl, AllowTakebacks) VALUES ($1, $2, $3, '', $4, $5)"#,
+            game.game_id,
+            game.black,
+            game.white,
+            game.time_control,
+            game.allow_takebacks
+        )
+        .execute(&self.pool)
+        .await;
+        match inserted {
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                Err(Error::Duplicate(game.game_id))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn load_game(&self, game_id: Uuid) -> Result<Option<StoredGame>> {
+        let row = sqlx::query!(
+            r#"SELECT GameID, Black, White, PGN, CreatedAt, TimeControl, AllowTakebacks FROM GameState WHERE GameId = $1 AND Status = $2"#,
+            game_id,
+            GameStatus::OnGoing as _
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let takebacks = sqlx::query!(
+            "SELECT RequestedBy, Undone FROM GameTakeback WHERE GameID = $1 ORDER BY ID",
+            game_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .filter_map(|takeback| {
+            Some(Takeback {
+                requested_by: takeback.requestedby.parse().ok()?,
+                undone: takeback.undone,
+            })
+        })
+        .collect();
+        Ok(Some(StoredGame {
+            game_id: row.gameid,
+            white: row.white,
+            black: row.black,
+            moves: moves_from_pgn(&row.pgn),
+            created_at: row.createdat.map(|created_at| created_at.and_utc()),
+            time_control: row.timecontrol,
+            allow_takebacks: row.allowtakebacks,
+            takebacks,
+        }))
+    }
+
+    async fn append_move(&self, game_id: Uuid, san: &str) -> Result<()> {
+        // the move number depends on the moves so far, lock them meanwhile
+        let mut tx = self.pool.begin().await?;
+        let Some(row) = sqlx::query!(
+            "SELECT PGN FROM GameState WHERE GameID = $1 AND Status = $2 FOR UPDATE",
+            game_id,
+            GameStatus::OnGoing as _
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(());
+        };
+        let mut moves = moves_from_pgn(&row.pgn);
+        moves.push(san.to_owned());
+        sqlx::query!(
+            "UPDATE GameState SET PGN = $2 WHERE GameID = $1",
+            game_id,
+            movetext(&moves)
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn save_moves(&self, game_id: Uuid, moves: &[String]) -> Result<()> {
+        sqlx::query!(
+            "UPDATE GameState SET PGN = $2 WHERE GameID = $1 AND Status = $3",
+            game_id,
+            movetext(moves),
+            GameStatus::OnGoing as _
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn take_back(&self, game_id: Uuid, takeback: &Takeback) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let Some(row) = sqlx::query!(
+            "SELECT PGN FROM GameState WHERE GameID = $1 AND Status = $2 FOR UPDATE",
+            game_id,
+            GameStatus::OnGoing as _
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(());
+        };
+        let mut moves = moves_from_pgn(&row.pgn);
+        moves.truncate(moves.len().saturating_sub(takeback.undone.len()));
+        sqlx::query!(
+            "UPDATE GameState SET PGN = $2 WHERE GameID = $1",
+            game_id,
+            movetext(&moves)
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "INSERT INTO GameTakeback (GameID, RequestedBy, Undone) VALUES ($1, $2, $3)",
+            game_id,
+            takeback.requested_by.to_string(),
+            &takeback.undone
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn finish_game(&self, game_id: Uuid, outcome: Outcome) -> Result<()> {
+        self.set_status(game_id, outcome.into()).await
+    }
+
+    async fn abort_game(&self, game_id: Uuid) -> Result<()> {
+        self.set_status(game_id, GameStatus::Aborted).await
+    }
+}
@@ -0,0 +1,92 @@
+use chessclouds_store::{
+    Error, GameStatus, GameStore, MemoryGameStore, NewGame, PgGameStore, Takeback,
+};
+use shakmaty::{Color, Outcome};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+fn new_game() -> NewGame {
+    NewGame {
+        game_id: Uuid::new_v4(),
+        white: "white".to_string(),
+        black: "black".to_string(),
+        time_control: Some("300+2".to_string()),
+        allow_takebacks: false,
+    }
+}
+
+/// The behavior every store must have, whatever keeps the games.
+async fn check_store(store: &dyn GameStore) {
+    let game = new_game();
+    let game_id = game.game_id;
+    store.create_game(game.clone()).await.unwrap();
+    assert!(matches!(
+        store.create_game(game).await,
+        Err(Error::Duplicate(id)) if id == game_id
+    ));
+
+    let stored = store.load_game(game_id).await.unwrap().unwrap();
+    assert_eq!(stored.white, "white");
+    assert_eq!(stored.black, "black");
+    assert_eq!(stored.time_control.as_deref(), Some("300+2"));
+    assert!(stored.moves.is_empty());
+    assert!(stored.created_at.is_some());
+    assert!(!stored.allow_takebacks);
+    assert!(stored.takebacks.is_empty());
+
+    for san in ["e4", "e5", "Nf3"] {
+        store.append_move(game_id, san).await.unwrap();
+    }
+    let stored = store.load_game(game_id).await.unwrap().unwrap();
+    assert_eq!(stored.moves, ["e4", "e5", "Nf3"]);
+
+    let moves = ["e4".to_string(), "e5".to_string()];
+    store.save_moves(game_id, &moves).await.unwrap();
+    let stored = store.load_game(game_id).await.unwrap().unwrap();
+    assert_eq!(stored.moves, moves);
+
+    let takeback = Takeback {
+        requested_by: Color::Black,
+        undone: vec!["e5".to_string()],
+    };
+    store.take_back(game_id, &takeback).await.unwrap();
+    let stored = store.load_game(game_id).await.unwrap().unwrap();
+    assert_eq!(stored.moves, ["e4"]);
+    assert_eq!(stored.takebacks, [takeback]);
+
+    // finished games are no longer loaded and cannot change
+    let outcome = Outcome::Decisive {
+        winner: Color::Black,
+    };
+    store.finish_game(game_id, outcome).await.unwrap();
+    assert!(store.load_game(game_id).await.unwrap().is_none());
+    store.append_move(game_id, "Nf3").await.unwrap();
+    store.abort_game(game_id).await.unwrap();
+    assert!(store.load_game(game_id).await.unwrap().is_none());
+
+    let game = new_game();
+    let game_id = game.game_id;
+    store.create_game(game).await.unwrap();
+    store.abort_game(game_id).await.unwrap();
+    assert!(store.load_game(game_id).await.unwrap().is_none());
+
+    assert!(store.load_game(Uuid::new_v4()).await.unwrap().is_none());
+    store.append_move(Uuid::new_v4(), "e4").await.unwrap();
+}
+
+#[tokio::test]
+async fn memory_store() {
+    let store = MemoryGameStore::default();
+    check_store(&store).await;
+
+    let game = new_game();
+    let game_id = game.game_id;
+    store.create_game(game).await.unwrap();
+    store.finish_game(game_id, Outcome::Draw).await.unwrap();
+    assert_eq!(store.status(game_id), Some(GameStatus::Draw));
+}
+
+#[sqlx::test(migrations = "../ws_server/migrations")]
+async fn postgres_store(pool: PgPool) {
+    check_store(&PgGameStore::new(pool)).await;
+}
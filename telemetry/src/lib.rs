@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line, including the fields of the current spans.
+    Json,
+}
+
+/// The `[log]` section shared by every service's configuration.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    /// `EnvFilter` directives such as `info,ws_server=debug`; `RUST_LOG`
+    /// takes precedence when set.
+    pub filter: String,
+    /// OTLP/HTTP traces endpoint, e.g. `http://localhost:4318/v1/traces`.
+    /// Requires the `otlp` feature.
+    pub otlp_endpoint: Option<String>,
+}
+
+impl LogConfig {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if EnvFilter::try_new(&self.filter).is_err() {
+            return Err("log.filter is not a valid filter");
+        }
+        if self.otlp_endpoint.is_some() && !cfg!(feature = "otlp") {
+            return Err("log.otlp_endpoint requires building with the otlp feature");
+        }
+        Ok(())
+    }
+}
+
+/// Keeps span export running, call `shutdown` before exiting to flush it.
+#[derive(Default)]
+pub struct Telemetry {
+    #[cfg(feature = "otlp")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+/// Installs the global `tracing` subscriber: text or JSON logs to stdout,
+/// plus OTLP span export when `log.otlp_endpoint` is set.
+pub fn init(config: &LogConfig, service: &'static str) -> Result<Telemetry, String> {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.filter));
+    let logs = match config.format {
+        LogFormat::Text => fmt::layer().boxed(),
+        LogFormat::Json => fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+    };
+    let registry = tracing_subscriber::registry().with(filter).with(logs);
+
+    #[cfg(feature = "otlp")]
+    if let Some(endpoint) = &config.otlp_endpoint {
+        use opentelemetry::trace::TracerProvider;
+        use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+        use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()
+            .map_err(|e| format!("building OTLP exporter failed: {e}"))?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().with_service_name(service).build())
+            .build();
+        registry
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer(service)))
+            .init();
+        return Ok(Telemetry {
+            provider: Some(provider),
+        });
+    }
+
+    let _ = service;
+    registry.init();
+    Ok(Telemetry::default())
+}
+
+impl Telemetry {
+    pub fn shutdown(self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.provider {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("flushing spans failed: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(filter: &str) -> LogConfig {
+        LogConfig {
+            format: LogFormat::Text,
+            filter: filter.to_string(),
+            otlp_endpoint: None,
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_filters() {
+        assert!(config("info,ws_server=debug").validate().is_ok());
+        assert!(config("info,ws_server=loud").validate().is_err());
+    }
+
+    #[test]
+    fn otlp_endpoint_needs_the_feature() {
+        let config = LogConfig {
+            otlp_endpoint: Some("http://localhost:4318/v1/traces".to_string()),
+            ..config("info")
+        };
+        assert_eq!(config.validate().is_ok(), cfg!(feature = "otlp"));
+    }
+}
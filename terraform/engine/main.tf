@@ -85,7 +85,7 @@ resource "aws_lb_target_group" "engine_tg" {
 
   health_check {
     enabled             = true
-    path                = "/"
+    path                = "/readyz"
     port                = "traffic-port"
     healthy_threshold   = 3
     unhealthy_threshold = 3
@@ -144,7 +144,7 @@ resource "aws_ecs_task_definition" "engine_task" {
       ],
       
       healthCheck = {
-        command     = ["CMD-SHELL", "wget --no-verbose --tries=1 --spider http://localhost:80/ || exit 1"]
+        command     = ["CMD-SHELL", "wget --no-verbose --tries=1 --spider http://localhost:80/healthz || exit 1"]
         interval    = 30
         timeout     = 5
         retries     = 3
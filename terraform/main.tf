@@ -2,6 +2,7 @@ module "websocket" {
   source          = "./websocket"
   database_url    = var.database_url
   websocket_image = var.websocket_image
+  node_url        = var.websocket_node_url
 }
 
 module "matchmaking" {
@@ -20,6 +20,12 @@ variable "websocket_url" {
   default     = ""
 }
 
+variable "websocket_node_url" {
+  description = "WS_SERVER_CLUSTER__NODE_URL, the URL other WebSocket instances redirect clients to"
+  type        = string
+  default     = ""
+}
+
 variable "matchmaking_url" {
   description = "NEXT_PUBLIC_MATCHMAKING_SERVER_URL"
   type        = string
@@ -5,5 +5,14 @@ sudo yum install -y docker
 sudo service docker start
 sudo usermod -a -G docker ec2-user
 docker pull ${websocket_image}
-docker run -d -p 80:8000 -e DATABASE_URL="${database_url}" ${websocket_image}
 
+# the server refuses to start on a wildcard host without a node URL, so fall
+# back to the instance's public address when none is configured
+NODE_URL="${node_url}"
+if [ -z "$NODE_URL" ]; then
+  TOKEN=$(curl -s -X PUT "http://169.254.169.254/latest/api/token" -H "X-aws-ec2-metadata-token-ttl-seconds: 300")
+  PUBLIC_IP=$(curl -s -H "X-aws-ec2-metadata-token: $TOKEN" "http://169.254.169.254/latest/meta-data/public-ipv4")
+  NODE_URL="ws://$PUBLIC_IP/ws"
+fi
+
+docker run -d -p 80:8000 -e DATABASE_URL="${database_url}" -e WS_SERVER_CLUSTER__NODE_URL="$NODE_URL" ${websocket_image}
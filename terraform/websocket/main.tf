@@ -10,7 +10,8 @@ resource "aws_instance" "app" {
 
   user_data = templatefile("${path.module}/init.sh", {
     websocket_image = var.websocket_image,
-    database_url    = var.database_url
+    database_url    = var.database_url,
+    node_url        = var.node_url
   })
 
   tags = {
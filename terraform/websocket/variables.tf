@@ -7,4 +7,10 @@ variable "websocket_image" {
   description = "Docker image for websocket server"
   type        = string
   default     = "public.ecr.aws/h9h4w9x1/websocketchesscloud:latest"
+}
+
+variable "node_url" {
+  description = "WebSocket URL other instances redirect clients to (WS_SERVER_CLUSTER__NODE_URL), defaults to the instance's public address"
+  type        = string
+  default     = ""
 }
\ No newline at end of file
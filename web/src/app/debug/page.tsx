@@ -15,11 +15,7 @@ import { MatchMakingService } from '@/lib/matchmakingService';
 
 export type Color = 'Black' | 'White';
 export type GameOutcome = { Decisive: { winner: Color } } | 'Draw';
-export type ErrorType =
-  | 'Deserialization'
-  | 'Unauthorized'
-  | 'InvalidTurn'
-  | 'InvalidMove';
+export type ErrorType = { code: string; message: string; input?: string };
 export type ClientMessage =
   | { kind: 'Auth'; value: { game_id: string; user_id: string } }
   | { kind: 'Move'; value: string };
@@ -27,8 +23,8 @@ export type ServerMessage =
   | { kind: 'Move'; value: string }
   | { kind: 'GameEnd'; value: GameOutcome }
   | { kind: 'Error'; value: ErrorType }
-  | { kind: 'AuthSuccess' }
-  | { kind: 'MoveHistory'; value: string[] };
+  | { kind: 'AuthSuccess'; value: { protocol_version: number } | null }
+  | { kind: 'GameSnapshot'; value: { fen: string; moves: string[] } };
 
 export default function DevPage() {
   const [host, setHost] = useState('localhost');
@@ -81,9 +77,10 @@ export default function DevPage() {
             setAuthenticated(true);
             logMessage('Authentication successful');
           } else if (data.kind === 'Error') {
-            logMessage(`Error: ${data.value}`);
-          } else if (data.kind === 'MoveHistory') {
-            logMessage(`Move history: ${data.value.join(', ')}`);
+            logMessage(`Error ${data.value.code}: ${data.value.message}`);
+          } else if (data.kind === 'GameSnapshot') {
+            logMessage(`Position: ${data.value.fen}`);
+            logMessage(`Move history: ${data.value.moves.join(', ')}`);
           } else if (data.kind === 'Move') {
             logMessage(`Move: ${data.value}`);
           } else if (data.kind === 'GameEnd') {
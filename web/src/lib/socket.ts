@@ -166,8 +166,8 @@ export default function useSocket(
                 if (onAuth) onAuth();
                 break;
 
-              case 'MoveHistory':
-                if (onHistory) onHistory(message.value);
+              case 'GameSnapshot':
+                if (onHistory) onHistory(message.value.moves);
                 break;
 
               case 'GameEnd':
@@ -175,12 +175,7 @@ export default function useSocket(
                 break;
 
               case 'Error':
-                if (onError)
-                  onError(
-                    typeof message.value === 'string'
-                      ? message.value
-                      : String(message.value)
-                  );
+                if (onError) onError(message.value.message);
                 break;
             }
           } catch (error) {
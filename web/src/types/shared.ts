@@ -21,6 +21,23 @@ export type ErrorType =
   | 'Connection error'
   | 'Connection closed';
 
+export interface ServerError {
+  code: string;
+  message: string;
+  input?: string;
+  context?: { legal_moves?: number; max_length?: number };
+}
+
+export interface GameSnapshot {
+  fen: string;
+  moves: Array<string>;
+  turn: Color;
+  white_user_id: string;
+  black_user_id: string;
+  status: { kind: 'OnGoing' } | { kind: 'Ended'; value: GameOutcome | 'Draw' };
+  color: Color;
+}
+
 export type ClientMessage =
   | { kind: 'Auth'; value: { game_id: string; user_id: string } }
   | { kind: 'Move'; value: string }
@@ -29,9 +46,9 @@ export type ClientMessage =
 export type ServerMessage =
   | { kind: 'Move'; value: string }
   | { kind: 'GameEnd'; value: GameOutcome | 'Draw' }
-  | { kind: 'Error'; value: Error }
-  | { kind: 'AuthSuccess' }
-  | { kind: 'MoveHistory'; value: Array<string> }
+  | { kind: 'Error'; value: ServerError }
+  | { kind: 'AuthSuccess'; value: { protocol_version: number } | null }
+  | { kind: 'GameSnapshot'; value: GameSnapshot }
   | { kind: 'Pong' };
 
 export type MoveCallback = (move: string) => void;
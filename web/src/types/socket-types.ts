@@ -1,3 +1,5 @@
+import type { GameSnapshot } from './shared';
+
 export type Color = 'Black' | 'White';
 export type GameOutcome =
   | { type: 'Decisive'; winner: 'w' | 'b' }
@@ -15,5 +17,5 @@ export type ServerMessage =
   | { kind: 'Move'; value: string }
   | { kind: 'GameEnd'; value: GameOutcome }
   | { kind: 'Error'; value: ErrorType }
-  | { kind: 'AuthSuccess' }
-  | { kind: 'MoveHistory'; value: string[] };
+  | { kind: 'AuthSuccess'; value: { protocol_version: number } | null }
+  | { kind: 'GameSnapshot'; value: GameSnapshot };
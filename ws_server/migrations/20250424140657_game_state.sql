@@ -0,0 +1,18 @@
+-- Idempotent so that databases created by the former Prisma migrations can
+-- adopt this history as is.
+DO $$ BEGIN
+    CREATE TYPE "GameStatus" AS ENUM ('White Wins', 'Black Wins', 'Draw', 'On Going', 'Abort');
+EXCEPTION
+    WHEN duplicate_object THEN NULL;
+END $$;
+
+CREATE TABLE IF NOT EXISTS "gamestate" (
+    "gameid" UUID NOT NULL,
+    "white" TEXT NOT NULL,
+    "black" TEXT NOT NULL,
+    "pgn" TEXT NOT NULL,
+    "createdat" TIMESTAMP(6) DEFAULT CURRENT_TIMESTAMP,
+    "status" "GameStatus" NOT NULL DEFAULT 'On Going',
+
+    CONSTRAINT "gamestate_pkey" PRIMARY KEY ("gameid")
+);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS "gamechat" (
+    "id" BIGSERIAL NOT NULL,
+    "gameid" UUID NOT NULL,
+    "userid" TEXT NOT NULL,
+    "channel" TEXT NOT NULL,
+    "text" TEXT NOT NULL,
+    "censored" BOOLEAN NOT NULL DEFAULT false,
+    "sentat" TIMESTAMP(6) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    CONSTRAINT "gamechat_pkey" PRIMARY KEY ("id"),
+    CONSTRAINT "gamechat_gameid_fkey" FOREIGN KEY ("gameid") REFERENCES "gamestate"("gameid") ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS "gamechat_gameid_idx" ON "gamechat"("gameid");
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS "gameowner" (
+    "gameid" UUID NOT NULL,
+    "nodeid" TEXT NOT NULL,
+    "nodeurl" TEXT NOT NULL,
+    "heartbeat" TIMESTAMP(6) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    CONSTRAINT "gameowner_pkey" PRIMARY KEY ("gameid"),
+    CONSTRAINT "gameowner_gameid_fkey" FOREIGN KEY ("gameid") REFERENCES "gamestate"("gameid") ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS "gameowner_nodeid_idx" ON "gameowner"("nodeid");
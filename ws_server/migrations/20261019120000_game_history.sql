@@ -0,0 +1,4 @@
+ALTER TABLE "gamestate" ADD COLUMN IF NOT EXISTS "timecontrol" TEXT;
+
+CREATE INDEX IF NOT EXISTS "gamestate_white_createdat_idx" ON "gamestate"("white", "createdat");
+CREATE INDEX IF NOT EXISTS "gamestate_black_createdat_idx" ON "gamestate"("black", "createdat");
@@ -0,0 +1,2 @@
+-- uploaded games name arbitrary players and results, they stay out of player histories
+ALTER TABLE "gamestate" ADD COLUMN IF NOT EXISTS "imported" BOOLEAN NOT NULL DEFAULT FALSE;
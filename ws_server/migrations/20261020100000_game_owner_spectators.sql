@@ -0,0 +1,2 @@
+-- refreshed with the heartbeat so any node can rank the live games of the cluster
+ALTER TABLE "gameowner" ADD COLUMN IF NOT EXISTS "spectators" INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,14 @@
+ALTER TABLE "gamestate" ADD COLUMN IF NOT EXISTS "allowtakebacks" BOOLEAN NOT NULL DEFAULT true;
+
+CREATE TABLE IF NOT EXISTS "gametakeback" (
+    "id" BIGSERIAL NOT NULL,
+    "gameid" UUID NOT NULL,
+    "requestedby" TEXT NOT NULL,
+    "undone" TEXT[] NOT NULL,
+    "takenat" TIMESTAMP(6) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    CONSTRAINT "gametakeback_pkey" PRIMARY KEY ("id"),
+    CONSTRAINT "gametakeback_gameid_fkey" FOREIGN KEY ("gameid") REFERENCES "gamestate"("gameid") ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS "gametakeback_gameid_idx" ON "gametakeback"("gameid");
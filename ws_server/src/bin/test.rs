@@ -1,251 +1,171 @@
 use std::time::Duration;
 
 use axum::body::Bytes;
-use futures_util::{
-    stream::{SplitSink, SplitStream},
-    SinkExt, StreamExt,
+use chessclouds_client::{
+    protocol::{
+        game::{ClientMessage, InitBody},
+        PROTOCOL_VERSION,
+    },
+    GameClient, GameConnection,
 };
-use tokio::{net::TcpStream, time::sleep};
+use futures_util::{SinkExt, StreamExt};
+use tokio::time::timeout;
 use tokio_tungstenite::{
     connect_async,
     tungstenite::{Message, Utf8Bytes},
-    MaybeTlsStream, WebSocketStream,
 };
-use ws_server::{message::ClientMessage, route::init::InitBody, HOST};
+use ws_server::config::Config;
 
-const GAME_ID: &'static str = "game";
-const WHITE_ID: &'static str = "white";
-const BLACK_ID: &'static str = "black";
+const GAME_ID: &str = "game";
+const WHITE_ID: &str = "white";
+const BLACK_ID: &str = "black";
 
-async fn send_msg(
-    writer: &mut SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
-    msg: ClientMessage,
-) -> anyhow::Result<()> {
-    let msg = serde_json::to_string(&msg)?;
-    writer.send(Message::Text(Utf8Bytes::from(&msg))).await?;
-    sleep(Duration::from_millis(500)).await;
+/// Sends frames the client library would never send and prints the replies.
+async fn send_raw(url: &str, frames: Vec<Message>) -> anyhow::Result<()> {
+    let (mut socket, _) = connect_async(url).await?;
+    for frame in frames {
+        socket.send(frame).await?;
+    }
+    while let Ok(Some(Ok(msg))) = timeout(Duration::from_millis(500), socket.next()).await {
+        println!("Received: {msg}");
+    }
     Ok(())
 }
 
-fn spawn_reader(mut reader: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>) {
-    tokio::spawn(async move {
-        while let Some(Ok(Message::Text(text))) = reader.next().await {
-            println!("Received: {}", text.to_string());
+async fn join(client: &GameClient, user_id: &str) -> Option<GameConnection> {
+    match client.join(GAME_ID, user_id).await {
+        Ok(conn) => {
+            println!("Authenticated {user_id}");
+            Some(conn)
         }
-    });
+        Err(e) => {
+            println!("Received: {e}");
+            None
+        }
+    }
 }
 
-async fn connect_socket(
-    url: &str,
-) -> anyhow::Result<SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>> {
-    let (socket, _) = connect_async(url).await?;
-    let (writer, reader) = socket.split();
-
-    spawn_reader(reader);
+/// Prints what `conn` receives until it is quiet for a while.
+async fn print_received(conn: &mut GameConnection) {
+    while let Ok(Some(event)) = timeout(Duration::from_millis(500), conn.next_event()).await {
+        println!("Received: {event:?}");
+    }
+}
 
-    Ok(writer)
+fn auth(user_id: &str) -> String {
+    let msg = ClientMessage::Auth {
+        game_id: GAME_ID.to_string(),
+        user_id: user_id.to_string(),
+        last_seq: None,
+        protocol_version: Some(PROTOCOL_VERSION),
+    };
+    serde_json::to_string(&msg).unwrap()
 }
 
-async fn test_auth(url: &str) -> anyhow::Result<()> {
+async fn test_auth(client: &GameClient) -> anyhow::Result<()> {
+    let url = client.ws_url();
     {
         println!("should fail: not Message::Text");
-        let mut writer = connect_socket(url).await?;
-
-        writer
-            .send(Message::Ping(Bytes::from_static(b"abc")))
-            .await?;
-
-        writer.close().await?;
+        send_raw(&url, vec![Message::Ping(Bytes::from_static(b"abc"))]).await?;
     }
     {
         println!("should fail: deserialization");
-        let mut writer = connect_socket(url).await?;
-
-        writer
-            .send(Message::Text(Utf8Bytes::from_static("abc")))
-            .await?;
-
-        writer.close().await?;
+        send_raw(&url, vec![Message::Text(Utf8Bytes::from_static("abc"))]).await?;
     }
     {
         println!("should fail: not-existent game");
-        let mut writer = connect_socket(url).await?;
-
-        send_msg(
-            &mut writer,
-            ClientMessage::Auth {
-                game_id: "abc".to_string(),
-                user_id: WHITE_ID.to_string(),
-            },
-        )
-        .await?;
-
-        writer.close().await?;
+        if let Err(e) = client.join("abc", WHITE_ID).await {
+            println!("Received: {e}");
+        }
     }
     {
         println!("should fail: user not black or white");
-        let mut writer = connect_socket(url).await?;
-
-        send_msg(
-            &mut writer,
-            ClientMessage::Auth {
-                game_id: GAME_ID.to_string(),
-                user_id: "brown".to_string(),
-            },
-        )
-        .await?;
-
-        writer.close().await?;
+        join(client, "brown").await;
     }
     {
         println!("should succeed: move, auth white");
-        let mut writer = connect_socket(url).await?;
-
-        send_msg(&mut writer, ClientMessage::Move("e4".to_string())).await?;
-        send_msg(
-            &mut writer,
-            ClientMessage::Auth {
-                game_id: GAME_ID.to_string(),
-                user_id: WHITE_ID.to_string(),
-            },
+        let mv = serde_json::to_string(&ClientMessage::Move("e4".to_string()))?;
+        send_raw(
+            &url,
+            vec![
+                Message::Text(Utf8Bytes::from(mv)),
+                Message::Text(Utf8Bytes::from(auth(WHITE_ID))),
+            ],
         )
         .await?;
-
-        writer.close().await?;
     }
     {
         println!("should succeed: auth black");
-        let mut writer = connect_socket(url).await?;
-
-        send_msg(
-            &mut writer,
-            ClientMessage::Auth {
-                game_id: GAME_ID.to_string(),
-                user_id: BLACK_ID.to_string(),
-            },
-        )
-        .await?;
-
-        writer.close().await?;
+        join(client, BLACK_ID).await;
     }
     {
-        println!("should fail: white already connected");
-        let mut writer1 = connect_socket(url).await?;
-        let mut writer2 = connect_socket(url).await?;
-
-        send_msg(
-            &mut writer1,
-            ClientMessage::Auth {
-                game_id: GAME_ID.to_string(),
-                user_id: WHITE_ID.to_string(),
-            },
-        )
-        .await?;
-        send_msg(
-            &mut writer2,
-            ClientMessage::Auth {
-                game_id: GAME_ID.to_string(),
-                user_id: WHITE_ID.to_string(),
-            },
-        )
-        .await?;
-
-        writer1.close().await?;
-        writer2.close().await?;
+        println!("should replace the first session: white connects twice");
+        let first = join(client, WHITE_ID).await;
+        let second = join(client, WHITE_ID).await;
+        for mut conn in first.into_iter().chain(second) {
+            print_received(&mut conn).await;
+        }
     }
     {
-        println!("should fail: black already connected");
-        let mut writer1 = connect_socket(url).await?;
-        let mut writer2 = connect_socket(url).await?;
-
-        send_msg(
-            &mut writer1,
-            ClientMessage::Auth {
-                game_id: GAME_ID.to_string(),
-                user_id: BLACK_ID.to_string(),
-            },
-        )
-        .await?;
-        send_msg(
-            &mut writer2,
-            ClientMessage::Auth {
-                game_id: GAME_ID.to_string(),
-                user_id: BLACK_ID.to_string(),
-            },
-        )
-        .await?;
-
-        writer1.close().await?;
-        writer2.close().await?;
+        println!("should replace the first session: black connects twice");
+        let first = join(client, BLACK_ID).await;
+        let second = join(client, BLACK_ID).await;
+        for mut conn in first.into_iter().chain(second) {
+            print_received(&mut conn).await;
+        }
     }
     Ok(())
 }
 
-async fn test_moves(url: &str) -> anyhow::Result<()> {
-    {
-        let mut writer_black = connect_socket(url).await?;
-        let mut writer_white = connect_socket(url).await?;
-
-        send_msg(
-            &mut writer_white,
-            ClientMessage::Auth {
-                game_id: GAME_ID.to_string(),
-                user_id: WHITE_ID.to_string(),
-            },
-        )
-        .await?;
-        send_msg(
-            &mut writer_black,
-            ClientMessage::Auth {
-                game_id: GAME_ID.to_string(),
-                user_id: BLACK_ID.to_string(),
-            },
-        )
-        .await?;
-
-        println!("should fail: deserialization");
-        writer_black
-            .send(Message::Text(Utf8Bytes::from_static("abc")))
-            .await?;
-        println!("should fail: invalid turn");
-        send_msg(&mut writer_black, ClientMessage::Move("e5".to_string())).await?;
-        println!("should fail: invalid move");
-        send_msg(&mut writer_white, ClientMessage::Move("abc".to_string())).await?;
-        println!("should fail: invalid move");
-        send_msg(&mut writer_white, ClientMessage::Move("e5".to_string())).await?;
-        println!("should succeed");
-        send_msg(&mut writer_white, ClientMessage::Move("d4".to_string())).await?;
-        println!("should fail: invalid turn");
-        send_msg(&mut writer_white, ClientMessage::Move("e4".to_string())).await?;
-
-        writer_white.close().await?;
-        writer_black.close().await?;
-    }
+async fn test_moves(client: &GameClient) -> anyhow::Result<()> {
+    let (Some(mut white), Some(mut black)) =
+        (join(client, WHITE_ID).await, join(client, BLACK_ID).await)
+    else {
+        anyhow::bail!("both players should be able to connect");
+    };
+    print_received(&mut white).await;
+    print_received(&mut black).await;
+
+    println!("should fail: invalid turn");
+    black.play("e5")?;
+    print_received(&mut black).await;
+    println!("should fail: invalid move");
+    white.play("abc")?;
+    print_received(&mut white).await;
+    println!("should fail: invalid move");
+    white.play("e5")?;
+    print_received(&mut white).await;
+    println!("should succeed");
+    white.play("d4")?;
+    print_received(&mut white).await;
+    println!("should fail: invalid turn");
+    white.play("e4")?;
+    print_received(&mut white).await;
 
     Ok(())
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let url = format!("ws://{HOST}/ws",);
+    // talk to a server started with the same configuration
+    let host = Config::load(None)?.host;
+    let client = GameClient::new(format!("http://{host}"));
 
     // initialize new game
-    let init_body = InitBody {
-        game_id: GAME_ID.to_string(),
-        white_user_id: WHITE_ID.to_string(),
-        black_user_id: BLACK_ID.to_string(),
-    };
-
-    let client = reqwest::Client::new();
     client
-        .post(format!("http://{HOST}/init"))
-        .json(&init_body)
-        .send()
+        .init_game(&InitBody {
+            game_id: GAME_ID.to_string(),
+            white_user_id: WHITE_ID.to_string(),
+            black_user_id: BLACK_ID.to_string(),
+            white_rating: None,
+            black_rating: None,
+            settings: None,
+        })
         .await?;
 
-    test_auth(&url).await?;
+    test_auth(&client).await?;
 
-    test_moves(&url).await?;
+    test_moves(&client).await?;
 
     Ok(())
 }
@@ -0,0 +1,124 @@
+use std::collections::HashSet;
+
+use chessclouds_protocol::game::ChatChannel;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+pub enum FilterVerdict {
+    Allow,
+    /// Deliver the given text in place of the original.
+    Censor(String),
+    Reject,
+}
+
+/// Moderation hook run on every chat message before it is broadcast.
+pub trait ChatFilter: Send + Sync {
+    fn check(&self, text: &str) -> FilterVerdict;
+}
+
+/// Masks banned words (case-insensitive, whole words only) with asterisks.
+pub struct WordListFilter {
+    words: HashSet<String>,
+}
+
+impl WordListFilter {
+    pub fn new(words: impl IntoIterator<Item = String>) -> Self {
+        WordListFilter {
+            words: words
+                .into_iter()
+                .map(|w| w.trim().to_lowercase())
+                .filter(|w| !w.is_empty())
+                .collect(),
+        }
+    }
+}
+
+impl ChatFilter for WordListFilter {
+    fn check(&self, text: &str) -> FilterVerdict {
+        let mut censored = String::with_capacity(text.len());
+        let mut word = String::new();
+        let mut changed = false;
+
+        let mut flush = |word: &mut String, out: &mut String| {
+            if self.words.contains(&word.to_lowercase()) {
+                out.extend(word.chars().map(|_| '*'));
+                changed = true;
+            } else {
+                out.push_str(word);
+            }
+            word.clear();
+        };
+
+        for c in text.chars() {
+            if c.is_alphanumeric() {
+                word.push(c);
+            } else {
+                flush(&mut word, &mut censored);
+                censored.push(c);
+            }
+        }
+        flush(&mut word, &mut censored);
+
+        if changed {
+            FilterVerdict::Censor(censored)
+        } else {
+            FilterVerdict::Allow
+        }
+    }
+}
+
+/// Keeps the original text of a chat message for moderation review.
+pub async fn store_chat(
+    pool: &Pool<Postgres>,
+    game_id: Uuid,
+    user_id: &str,
+    channel: ChatChannel,
+    text: &str,
+    censored: bool,
+) -> Result<(), sqlx::Error> {
+    let channel = match channel {
+        ChatChannel::Players => "Players",
+        ChatChannel::Spectators => "Spectators",
+    };
+    sqlx::query!(
+        "INSERT INTO GameChat (GameID, UserID, Channel, Text, Censored) VALUES ($1, $2, $3, $4, $5)",
+        game_id,
+        user_id,
+        channel,
+        text,
+        censored
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter() -> WordListFilter {
+        WordListFilter::new([" Darn ".to_string(), String::new()])
+    }
+
+    fn check(text: &str) -> Option<String> {
+        match filter().check(text) {
+            FilterVerdict::Allow => None,
+            FilterVerdict::Censor(censored) => Some(censored),
+            FilterVerdict::Reject => panic!("the word list never rejects"),
+        }
+    }
+
+    #[test]
+    fn masks_banned_words_in_any_case() {
+        assert_eq!(check("darn it").as_deref(), Some("**** it"));
+        assert_eq!(check("DARN, Darn!").as_deref(), Some("****, ****!"));
+    }
+
+    #[test]
+    fn only_masks_whole_words() {
+        assert_eq!(check("darnedest"), None);
+        assert_eq!(check("good game"), None);
+        assert_eq!(check(""), None);
+    }
+}
@@ -0,0 +1,201 @@
+use std::time::Duration;
+
+use chessclouds_protocol::game::ServerMessage;
+use chrono::NaiveDateTime;
+use futures_util::future::join_all;
+use shakmaty::Position;
+use uuid::Uuid;
+
+use crate::{
+    config::StoreKind,
+    db::GameStatus,
+    state::{AppState, StoreWriter},
+};
+
+/// This ws_server instance, as seen by the other instances.
+pub struct Node {
+    pub id: String,
+    /// Public WebSocket URL clients are redirected to for games owned here.
+    pub url: String,
+}
+
+pub enum Ownership {
+    Owned,
+    /// The game is live on another node reachable at this URL.
+    Redirect(String),
+}
+
+/// Nodes keeping games in memory cannot share them, each is on its own and
+/// owns every game it loads.
+pub fn is_clustered(state: &AppState) -> bool {
+    state.config.database.store == StoreKind::Postgres
+}
+
+/// Takes ownership of `game_id` unless another node holds it with a recent
+/// heartbeat. Exactly one node owns a game at any time.
+pub async fn claim_game(state: &AppState, game_id: Uuid) -> Result<Ownership, sqlx::Error> {
+    if !is_clustered(state) {
+        return Ok(Ownership::Owned);
+    }
+    let node = &state.node;
+    sqlx::query!(
+        r#"INSERT INTO GameOwner (GameID, NodeID, NodeURL) VALUES ($1, $2, $3)
+        ON CONFLICT (GameID) DO UPDATE
+        SET NodeID = EXCLUDED.NodeID, NodeURL = EXCLUDED.NodeURL, Heartbeat = now()
+        WHERE GameOwner.NodeID = EXCLUDED.NodeID
+            OR GameOwner.Heartbeat < now() - make_interval(secs => $4)"#,
+        game_id,
+        node.id,
+        node.url,
+        state.config.cluster.ownership_timeout_secs as f64
+    )
+    .execute(&state.pool)
+    .await?;
+
+    let owner = sqlx::query!(
+        "SELECT NodeID, NodeURL FROM GameOwner WHERE GameID = $1",
+        game_id
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    if owner.nodeid == node.id {
+        Ok(Ownership::Owned)
+    } else {
+        Ok(Ownership::Redirect(owner.nodeurl))
+    }
+}
+
+pub async fn release_game(state: &AppState, game_id: Uuid) -> Result<(), sqlx::Error> {
+    if !is_clustered(state) {
+        return Ok(());
+    }
+    sqlx::query!(
+        "DELETE FROM GameOwner WHERE GameID = $1 AND NodeID = $2",
+        game_id,
+        state.node.id
+    )
+    .execute(&state.pool)
+    .await?;
+    Ok(())
+}
+
+/// An ongoing game claimed by a live node, possibly this one.
+pub struct OwnedGame {
+    pub game_id: Uuid,
+    pub white: String,
+    pub black: String,
+    pub pgn: String,
+    pub time_control: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+    /// As of the owner's last heartbeat.
+    pub spectators: i32,
+}
+
+/// The ongoing games of the whole cluster, as far as their owners keep
+/// heartbeating.
+pub async fn owned_games(state: &AppState) -> Result<Vec<OwnedGame>, sqlx::Error> {
+    sqlx::query_as!(
+        OwnedGame,
+        r#"SELECT GameState.GameID AS game_id, White AS white, Black AS black, PGN AS pgn,
+            TimeControl AS time_control, CreatedAt AS created_at, Spectators AS spectators
+        FROM GameOwner JOIN GameState ON GameState.GameID = GameOwner.GameID
+        WHERE GameState.Status = $1
+            AND GameOwner.Heartbeat >= now() - make_interval(secs => $2)"#,
+        GameStatus::OnGoing as _,
+        state.config.cluster.ownership_timeout_secs as f64
+    )
+    .fetch_all(&state.pool)
+    .await
+}
+
+/// Keeps this node's claims alive; claims of a node that stops heartbeating
+/// can be taken over after `cluster.ownership_timeout_secs`. Also publishes
+/// the spectator counts other nodes rank live games by.
+pub async fn heartbeat(state: AppState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(
+        state.config.cluster.heartbeat_interval_secs,
+    ));
+    loop {
+        interval.tick().await;
+        let (mut game_ids, mut spectators) = (Vec::new(), Vec::new());
+        state.active_games.scan(|game_id, game| {
+            if let Ok(game_id) = Uuid::parse_str(game_id) {
+                game_ids.push(game_id);
+                spectators.push(game.spectators.len() as i32);
+            }
+        });
+        if let Err(e) = sqlx::query!(
+            r#"UPDATE GameOwner SET Heartbeat = now(), Spectators = COALESCE(
+                (SELECT counts.spectators
+                FROM unnest($2::uuid[], $3::int4[]) AS counts(game_id, spectators)
+                WHERE counts.game_id = GameOwner.GameID),
+                0)
+            WHERE NodeID = $1"#,
+            state.node.id,
+            &game_ids,
+            &spectators
+        )
+        .execute(&state.pool)
+        .await
+        {
+            tracing::error!("ownership heartbeat failed: {e}");
+        }
+    }
+}
+
+/// Persists every game owned by this node and releases its claims, so that
+/// clients reconnecting through the load balancer can resume on another node.
+pub async fn hand_off(state: &AppState) {
+    let mut games = Vec::new();
+    let mut flushed = Vec::new();
+    state.active_games.scan(|game_id, game| {
+        // games created through `/init` only live in memory
+        if let Ok(game_uuid) = Uuid::parse_str(game_id) {
+            games.push((game_uuid, game.moves.clone(), game.board.outcome()));
+        }
+        flushed.extend(game.store_writer.as_ref().map(StoreWriter::flushed));
+    });
+    // the final state written below must not be overwritten by a late move
+    join_all(flushed).await;
+
+    tracing::info!("handing off {} games", games.len());
+    for (game_id, moves, outcome) in games {
+        // moves first, finishing the game makes it read-only
+        if let Err(e) = state.store.save_moves(game_id, &moves).await {
+            tracing::error!("persisting game {game_id} failed: {e}");
+            continue;
+        }
+        if let Some(outcome) = outcome {
+            if let Err(e) = state.store.finish_game(game_id, outcome).await {
+                tracing::error!("persisting the result of {game_id} failed: {e}");
+            }
+        }
+    }
+
+    if !is_clustered(state) {
+        return;
+    }
+    if let Err(e) = sqlx::query!("DELETE FROM GameOwner WHERE NodeID = $1", state.node.id)
+        .execute(&state.pool)
+        .await
+    {
+        tracing::error!("releasing game ownership failed: {e}");
+    }
+}
+
+/// Refuses new games and moves, waits for the moves being processed, tells
+/// every client to reconnect later and hands all games off.
+pub async fn drain(state: &AppState) {
+    tracing::info!("draining");
+    state.drain.start().await;
+
+    state.active_games.retain(|_, game| {
+        game.broadcast(ServerMessage::ServerRestarting {
+            reconnect_after: state.config.cluster.reconnect_after_secs,
+        });
+        true
+    });
+
+    hand_off(state).await;
+}
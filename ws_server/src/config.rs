@@ -0,0 +1,374 @@
+use std::{env, net::SocketAddr, path::PathBuf};
+
+pub use chessclouds_telemetry::{LogConfig, LogFormat};
+use config::{ConfigError, Environment, File, FileFormat};
+use serde::{Deserialize, Serialize};
+
+/// Environment variables starting with this prefix override the config file,
+/// with `__` separating sections, e.g. `WS_SERVER_CHAT__MAX_LENGTH=140`.
+pub const ENV_PREFIX: &str = "WS_SERVER";
+/// Read when it exists and no `--config` path is given.
+pub const DEFAULT_CONFIG_PATH: &str = "ws_server.toml";
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Config {
+    /// Address the HTTP and WebSocket listener binds to.
+    pub host: String,
+    pub database: DatabaseConfig,
+    pub game: GameConfig,
+    pub chat: ChatConfig,
+    pub connection: ConnectionConfig,
+    pub cluster: ClusterConfig,
+    pub import: ImportConfig,
+    pub render: RenderConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKind {
+    Postgres,
+    /// Games live in memory only, for development on a single node without
+    /// Postgres. Chat, history and imports still need the database.
+    Memory,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DatabaseConfig {
+    /// Where games are persisted.
+    pub store: StoreKind,
+    pub max_connections: u32,
+    /// Apply pending migrations at startup. Turn off when deployments run
+    /// `ws_server --migrate` as a separate step.
+    pub run_migrations: bool,
+    /// `/readyz` reports the database as unreachable after this long.
+    pub health_check_timeout_secs: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GameConfig {
+    /// Capacity of the broadcast channel of a game and of each connection's
+    /// outgoing queue.
+    pub channel_capacity: usize,
+    /// Broadcast messages kept per game for resuming sessions.
+    pub replay_log_length: usize,
+    /// How long a game stays in memory after both players disconnect.
+    pub clean_up_after_secs: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ChatConfig {
+    pub max_length: usize,
+    /// Messages a single connection may send per `rate_window_secs`.
+    pub rate_limit: usize,
+    pub rate_window_secs: u64,
+    /// Words masked by the default `WordListFilter`.
+    #[serde(default)]
+    pub banned_words: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ConnectionConfig {
+    /// Sockets that have not sent `Auth` or `Spectate` by then are closed.
+    pub auth_timeout_secs: u64,
+    /// Larger WebSocket messages close the socket.
+    pub max_message_bytes: usize,
+    /// Messages of any kind a connection may send per `rate_window_secs`.
+    pub rate_limit: usize,
+    pub rate_window_secs: u64,
+    /// The socket is closed once this many messages within one
+    /// `rate_window_secs` were over the limit.
+    pub max_rate_violations: usize,
+    /// How often the server sends a WebSocket ping.
+    pub ping_interval_secs: u64,
+    /// Connections that sent nothing, not even a pong, for this long are
+    /// considered dead and their seat is freed.
+    pub idle_timeout_secs: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ClusterConfig {
+    /// Public WebSocket URL other instances redirect clients to, defaults to
+    /// `ws://{host}/ws`. Required when `host` is a wildcard address such as
+    /// `0.0.0.0`, which clients cannot connect to.
+    pub node_url: Option<String>,
+    pub heartbeat_interval_secs: u64,
+    /// Claims not refreshed for this long can be taken over by another node.
+    pub ownership_timeout_secs: u64,
+    /// Sent to clients in `ServerRestarting` when this node shuts down.
+    pub reconnect_after_secs: u64,
+}
+
+/// Limits of uploaded PGN files.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ImportConfig {
+    pub max_bytes: usize,
+    pub max_games: usize,
+}
+
+/// Limits of the animated GIFs of `/games/{game_id}/gif`, where every frame
+/// is rasterized and quantized.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RenderConfig {
+    /// Longer games skip positions evenly, keeping the first and the last.
+    pub gif_max_frames: usize,
+    /// Largest side in pixels a GIF may be requested at.
+    pub gif_max_size: u32,
+    /// Total size of the GIFs of finished games kept in memory.
+    pub gif_cache_bytes: usize,
+    /// GIFs a client IP may have rendered at once, and per minute after that.
+    /// Cached GIFs are not counted.
+    pub gif_burst: u32,
+    pub gif_per_minute: u32,
+    /// Use the first `X-Forwarded-For` address as the client IP. Only enable
+    /// behind a proxy that sets the header.
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            host: "0.0.0.0:8000".to_string(),
+            database: DatabaseConfig {
+                store: StoreKind::Postgres,
+                max_connections: 5,
+                run_migrations: true,
+                health_check_timeout_secs: 2,
+            },
+            game: GameConfig {
+                channel_capacity: 64,
+                replay_log_length: 256,
+                clean_up_after_secs: 600,
+            },
+            chat: ChatConfig {
+                max_length: 280,
+                rate_limit: 5,
+                rate_window_secs: 10,
+                banned_words: Vec::new(),
+            },
+            connection: ConnectionConfig {
+                auth_timeout_secs: 10,
+                max_message_bytes: 4096,
+                rate_limit: 20,
+                rate_window_secs: 5,
+                max_rate_violations: 20,
+                ping_interval_secs: 15,
+                idle_timeout_secs: 45,
+            },
+            cluster: ClusterConfig {
+                node_url: None,
+                heartbeat_interval_secs: 10,
+                ownership_timeout_secs: 30,
+                reconnect_after_secs: 5,
+            },
+            import: ImportConfig {
+                max_bytes: 1024 * 1024,
+                max_games: 100,
+            },
+            render: RenderConfig {
+                gif_max_frames: 150,
+                gif_max_size: 480,
+                gif_cache_bytes: 64 * 1024 * 1024,
+                gif_burst: 5,
+                gif_per_minute: 10,
+                trust_forwarded_for: false,
+            },
+            log: LogConfig {
+                format: LogFormat::Text,
+                filter: "info".to_string(),
+                otlp_endpoint: None,
+            },
+        }
+    }
+}
+
+impl Config {
+    /// Built-in defaults, overridden by the TOML file at `path` (or
+    /// `DEFAULT_CONFIG_PATH` if present), overridden by `WS_SERVER_*`
+    /// environment variables.
+    pub fn load(path: Option<PathBuf>) -> Result<Self, ConfigError> {
+        let file = match path {
+            Some(path) => File::from(path).format(FileFormat::Toml),
+            None => File::new(DEFAULT_CONFIG_PATH, FileFormat::Toml).required(false),
+        };
+
+        let config: Config = config::Config::builder()
+            .add_source(config::Config::try_from(&Config::default())?)
+            .add_source(file)
+            .add_source(
+                Environment::with_prefix(ENV_PREFIX)
+                    .prefix_separator("_")
+                    .separator("__")
+                    .list_separator(",")
+                    .with_list_parse_key("chat.banned_words")
+                    .try_parsing(true),
+            )
+            .build()?
+            .try_deserialize()?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |msg: &str| Err(ConfigError::Message(msg.to_string()));
+
+        if self.host.parse::<SocketAddr>().is_err() {
+            return invalid("host must be an address like 0.0.0.0:8000");
+        }
+        if self.database.max_connections == 0 || self.database.health_check_timeout_secs == 0 {
+            return invalid(
+                "database.max_connections and database.health_check_timeout_secs must be positive",
+            );
+        }
+        if self.game.channel_capacity == 0 || self.game.replay_log_length == 0 {
+            return invalid("game.channel_capacity and game.replay_log_length must be positive");
+        }
+        if self.chat.max_length == 0 || self.chat.rate_limit == 0 || self.chat.rate_window_secs == 0
+        {
+            return invalid(
+                "chat.max_length, chat.rate_limit and chat.rate_window_secs must be positive",
+            );
+        }
+        if self.connection.auth_timeout_secs == 0
+            || self.connection.max_message_bytes == 0
+            || self.connection.rate_limit == 0
+            || self.connection.rate_window_secs == 0
+            || self.connection.max_rate_violations == 0
+        {
+            return invalid("connection limits must be positive");
+        }
+        if self.connection.ping_interval_secs == 0
+            || self.connection.idle_timeout_secs <= self.connection.ping_interval_secs
+        {
+            return invalid(
+                "connection.idle_timeout_secs must be longer than a positive connection.ping_interval_secs",
+            );
+        }
+        match &self.cluster.node_url {
+            Some(url) if !url.starts_with("ws://") && !url.starts_with("wss://") => {
+                return invalid("cluster.node_url must be a ws:// or wss:// URL");
+            }
+            // nodes keeping games in memory never redirect anyone
+            None if self.database.store == StoreKind::Postgres
+                && self
+                    .host
+                    .parse::<SocketAddr>()
+                    .is_ok_and(|addr| addr.ip().is_unspecified()) =>
+            {
+                return invalid(
+                    "cluster.node_url must be set when host is a wildcard address, clients are redirected to it",
+                );
+            }
+            _ => {}
+        }
+        if self.cluster.heartbeat_interval_secs == 0
+            || self.cluster.ownership_timeout_secs <= self.cluster.heartbeat_interval_secs
+        {
+            return invalid(
+                "cluster.ownership_timeout_secs must be longer than a positive cluster.heartbeat_interval_secs",
+            );
+        }
+        if self.import.max_bytes == 0 || self.import.max_games == 0 {
+            return invalid("import.max_bytes and import.max_games must be positive");
+        }
+        let render = &self.render;
+        if render.gif_max_frames < 2 {
+            return invalid("render.gif_max_frames must be at least 2");
+        }
+        if render.gif_max_size == 0 || render.gif_burst == 0 || render.gif_per_minute == 0 {
+            return invalid(
+                "render.gif_max_size, render.gif_burst and render.gif_per_minute must be positive",
+            );
+        }
+        if let Err(msg) = self.log.validate() {
+            return invalid(msg);
+        }
+        Ok(())
+    }
+
+    pub fn node_url(&self) -> String {
+        self.cluster
+            .node_url
+            .clone()
+            .unwrap_or_else(|| format!("ws://{}/ws", self.host))
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("config should serialize")
+    }
+}
+
+/// Command line options of the server binary.
+#[derive(Default)]
+pub struct Args {
+    /// `--config <path>`
+    pub config_path: Option<PathBuf>,
+    /// `--print-config`: print the effective configuration and exit.
+    pub print_config: bool,
+    /// `--migrate`: apply pending database migrations and exit.
+    pub migrate: bool,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, String> {
+        let mut args = Args::default();
+        let mut argv = env::args().skip(1);
+        while let Some(arg) = argv.next() {
+            match arg.as_str() {
+                "--config" => {
+                    let path = argv.next().ok_or("--config expects a path")?;
+                    args.config_path = Some(path.into());
+                }
+                "--print-config" => args.print_config = true,
+                "--migrate" => args.migrate = true,
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+        Ok(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_hosts_need_a_node_url() {
+        let mut config = Config::default();
+        assert!(config.validate().is_err());
+
+        config.cluster.node_url = Some("wss://ws-1.example.com/ws".to_string());
+        assert!(config.validate().is_ok());
+        assert_eq!(config.node_url(), "wss://ws-1.example.com/ws");
+
+        config.cluster.node_url = Some("https://ws-1.example.com/ws".to_string());
+        assert!(config.validate().is_err());
+
+        config.cluster.node_url = None;
+        config.host = "10.0.0.7:8000".to_string();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.node_url(), "ws://10.0.0.7:8000/ws");
+
+        config.host = "[::]:8000".to_string();
+        assert!(config.validate().is_err());
+
+        // nothing is redirected to memory nodes
+        config.database.store = StoreKind::Memory;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn gifs_keep_at_least_the_first_and_last_positions() {
+        let mut config = Config {
+            host: "127.0.0.1:8000".to_string(),
+            ..Config::default()
+        };
+        assert!(config.validate().is_ok());
+        config.render.gif_max_frames = 1;
+        assert!(config.validate().is_err());
+        config.render.gif_max_frames = 2;
+        config.render.gif_per_minute = 0;
+        assert!(config.validate().is_err());
+    }
+}
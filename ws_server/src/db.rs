@@ -0,0 +1,6 @@
+use sqlx::migrate::Migrator;
+
+pub use chessclouds_store::GameStatus;
+
+/// The schema of every table the services use, see `migrations/`.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
@@ -0,0 +1,240 @@
+use chessclouds_store::pgn::{moves_from_pgn, movetext};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use futures_util::{Stream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::{db::GameStatus, pgn::import::ParsedGame};
+
+/// A game as stored in `GameState`, ongoing or finished.
+#[derive(Serialize, Debug, Clone, TS)]
+#[ts(export)]
+pub struct GameRecord {
+    pub game_id: String,
+    pub white: String,
+    pub black: String,
+    pub status: GameStatus,
+    pub created_at: Option<NaiveDateTime>,
+    /// PGN `TimeControl` value such as `300+2`, `None` when untimed.
+    pub time_control: Option<String>,
+    pub moves: Vec<String>,
+    /// Uploaded through `/games/import`. Nothing vouches for its players or
+    /// result, so it is not part of their history.
+    pub imported: bool,
+}
+
+struct GameRow {
+    game_id: Uuid,
+    white: String,
+    black: String,
+    pgn: String,
+    status: GameStatus,
+    created_at: Option<NaiveDateTime>,
+    time_control: Option<String>,
+    imported: bool,
+}
+
+impl From<GameRow> for GameRecord {
+    fn from(row: GameRow) -> Self {
+        GameRecord {
+            game_id: row.game_id.to_string(),
+            white: row.white,
+            black: row.black,
+            status: row.status,
+            created_at: row.created_at,
+            time_control: row.time_control,
+            moves: moves_from_pgn(&row.pgn),
+            imported: row.imported,
+        }
+    }
+}
+
+/// Result of a game from the point of view of the user whose games are listed.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, TS)]
+#[serde(rename_all = "lowercase")]
+#[ts(export, rename_all = "lowercase")]
+pub enum ResultFilter {
+    Win,
+    Loss,
+    Draw,
+    Ongoing,
+    Aborted,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, TS)]
+#[serde(rename_all = "lowercase")]
+#[ts(export, rename_all = "lowercase")]
+pub enum ColorFilter {
+    White,
+    Black,
+}
+
+/// Narrows down the games of a user, every field is optional.
+#[derive(Deserialize, Debug, Clone, Default, TS)]
+#[ts(export)]
+pub struct GameFilter {
+    #[ts(optional)]
+    pub result: Option<ResultFilter>,
+    /// The color the user played.
+    #[ts(optional)]
+    pub color: Option<ColorFilter>,
+    /// First day included, `YYYY-MM-DD`.
+    #[ts(optional)]
+    pub from: Option<NaiveDate>,
+    /// Last day included, `YYYY-MM-DD`.
+    #[ts(optional)]
+    pub to: Option<NaiveDate>,
+    #[ts(optional)]
+    pub time_control: Option<String>,
+}
+
+pub async fn find_game(
+    pool: &Pool<Postgres>,
+    game_id: Uuid,
+) -> Result<Option<GameRecord>, sqlx::Error> {
+    let row = sqlx::query_as!(
+        GameRow,
+        r#"SELECT GameID AS game_id, White AS white, Black AS black, PGN AS pgn,
+            Status AS "status: GameStatus", CreatedAt AS created_at, TimeControl AS time_control,
+            Imported AS imported
+        FROM GameState WHERE GameID = $1"#,
+        game_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(GameRecord::from))
+}
+
+/// Where a listing resumes: the position of the last game already listed.
+#[derive(Debug, Clone, Copy)]
+pub struct Cursor {
+    created_at: Option<NaiveDateTime>,
+    game_id: Uuid,
+}
+
+impl From<&GameRecord> for Cursor {
+    fn from(game: &GameRecord) -> Self {
+        Cursor {
+            created_at: game.created_at,
+            game_id: Uuid::parse_str(&game.game_id).expect("game ids should be UUIDs"),
+        }
+    }
+}
+
+/// Games of `user_id` matching `filter`, newest first, starting after
+/// `after` if given. `limit: None` returns every remaining game.
+pub fn find_user_games<'a>(
+    pool: &'a Pool<Postgres>,
+    user_id: &str,
+    filter: &GameFilter,
+    after: Option<Cursor>,
+    limit: Option<i64>,
+    offset: i64,
+) -> impl Stream<Item = Result<GameRecord, sqlx::Error>> + 'a {
+    let color = filter.color.map(|color| match color {
+        ColorFilter::White => "white",
+        ColorFilter::Black => "black",
+    });
+    // the status the game must have if the user played white, and if black
+    let statuses = filter.result.map(|result| match result {
+        ResultFilter::Win => (GameStatus::WhiteWins, GameStatus::BlackWins),
+        ResultFilter::Loss => (GameStatus::BlackWins, GameStatus::WhiteWins),
+        ResultFilter::Draw => (GameStatus::Draw, GameStatus::Draw),
+        ResultFilter::Ongoing => (GameStatus::OnGoing, GameStatus::OnGoing),
+        ResultFilter::Aborted => (GameStatus::Aborted, GameStatus::Aborted),
+    });
+    let status_as_white = statuses.map(|(white, _)| white);
+    let status_as_black = statuses.map(|(_, black)| black);
+    let from = filter.from.map(|day| day.and_time(NaiveTime::MIN));
+    // `to` is inclusive, so compare against the start of the next day
+    let until = filter
+        .to
+        .and_then(|day| day.succ_opt())
+        .map(|day| day.and_time(NaiveTime::MIN));
+
+    sqlx::query_as!(
+        GameRow,
+        r#"SELECT GameID AS game_id, White AS white, Black AS black, PGN AS pgn,
+            Status AS "status: GameStatus", CreatedAt AS created_at, TimeControl AS time_control,
+            Imported AS imported
+        FROM GameState
+        WHERE (White = $1 OR Black = $1)
+            AND NOT Imported
+            AND ($2::TEXT IS NULL
+                OR ($2 = 'white' AND White = $1)
+                OR ($2 = 'black' AND Black = $1))
+            AND ($3::"GameStatus" IS NULL
+                OR (White = $1 AND Status = $3)
+                OR (Black = $1 AND Status = $4))
+            AND ($5::TIMESTAMP IS NULL OR CreatedAt >= $5)
+            AND ($6::TIMESTAMP IS NULL OR CreatedAt < $6)
+            AND ($7::TEXT IS NULL OR TimeControl = $7)
+            AND ($10::UUID IS NULL
+                OR ($11::TIMESTAMP IS NULL AND CreatedAt IS NULL AND GameID > $10)
+                OR ($11::TIMESTAMP IS NOT NULL
+                    AND (CreatedAt IS NULL
+                        OR CreatedAt < $11
+                        OR (CreatedAt = $11 AND GameID > $10))))
+        ORDER BY CreatedAt DESC NULLS LAST, GameID
+        LIMIT $8 OFFSET $9"#,
+        user_id,
+        color,
+        status_as_white as _,
+        status_as_black as _,
+        from,
+        until,
+        filter.time_control,
+        limit,
+        offset,
+        after.map(|cursor| cursor.game_id),
+        after.and_then(|cursor| cursor.created_at)
+    )
+    .fetch(pool)
+    .map_ok(GameRecord::from)
+}
+
+/// Stores validated imported games, all or none, flagged as imported so they
+/// stay out of player histories. Games dated by a complete `Date` tag keep
+/// that date as `CreatedAt`.
+pub async fn insert_games(
+    pool: &Pool<Postgres>,
+    games: &[ParsedGame],
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let mut game_ids = Vec::with_capacity(games.len());
+    for game in games {
+        let game_id = Uuid::new_v4();
+        let status = match game.result.as_str() {
+            "1-0" => GameStatus::WhiteWins,
+            "0-1" => GameStatus::BlackWins,
+            "1/2-1/2" => GameStatus::Draw,
+            // an unfinished upload must not be resumed as a live game
+            _ => GameStatus::Aborted,
+        };
+        let created_at = game
+            .tag("Date")
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y.%m.%d").ok())
+            .map(|date| date.and_time(NaiveTime::MIN));
+        let time_control = game
+            .tag("TimeControl")
+            .filter(|&time_control| !matches!(time_control, "" | "-" | "?"));
+        sqlx::query!(
+            r#"INSERT INTO GameState (GameID, White, Black, PGN, Status, CreatedAt, TimeControl, Imported)
+            VALUES ($1, $2, $3, $4, $5, COALESCE($6::TIMESTAMP, LOCALTIMESTAMP), $7, TRUE)"#,
+            game_id,
+            game.tag("White").unwrap_or("?"),
+            game.tag("Black").unwrap_or("?"),
+            movetext(&game.moves),
+            status as _,
+            created_at,
+            time_control
+        )
+        .execute(&mut *tx)
+        .await?;
+        game_ids.push(game_id);
+    }
+    tx.commit().await?;
+    Ok(game_ids)
+}
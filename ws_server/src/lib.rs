@@ -1,8 +1,11 @@
-pub mod message;
+pub mod chat;
+pub mod cluster;
+pub mod config;
+pub mod db;
+pub mod history;
+pub mod metrics;
+pub mod pgn;
+pub mod rate_limit;
+pub mod render;
 pub mod route;
 pub mod state;
-
-pub const MAX_CHANNEL_CAPACITY: usize = 64;
-pub const HOST: &'static str = "0.0.0.0:8000";
-pub const MAX_DB_CONNECTIONS: u32 = 5;
-pub const DEFERRED_CLEAN_UP_DURATION: u64 = 600;
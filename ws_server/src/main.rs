@@ -1,55 +1,110 @@
-use axum::{
-    http::{header, Method},
-    routing::{any, get, post},
-    Router,
-};
 use dotenvy::dotenv;
-use scc::HashMap;
-use sqlx::postgres::PgPoolOptions;
-use std::{env, sync::Arc};
-use tower_http::cors::{AllowOrigin, CorsLayer};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use std::{env, net::SocketAddr, process};
+use tokio::signal;
 use ws_server::{
-    route::{games::get_games, init::post_init, ws::ws_handler},
+    cluster,
+    config::{Args, Config, StoreKind},
+    db, route,
     state::AppState,
-    HOST, MAX_DB_CONNECTIONS,
 };
 
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
     dotenv().ok();
 
-    let cors = CorsLayer::new()
-        .allow_origin(AllowOrigin::any())
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
-        .allow_headers([
-            header::CONTENT_TYPE,
-            header::ACCEPT,
-            header::ORIGIN,
-            header::AUTHORIZATION,
-        ])
-        .allow_credentials(false);
-
-    let pool = PgPoolOptions::new()
-        .max_connections(MAX_DB_CONNECTIONS)
-        .connect(&env::var("DATABASE_URL").expect("expecting DATABASE_URL in .env"))
-        .await
-        .unwrap();
+    let args = Args::parse().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        process::exit(2);
+    });
+    let config = Config::load(args.config_path).unwrap_or_else(|e| {
+        eprintln!("invalid configuration: {e}");
+        process::exit(1);
+    });
+    if args.print_config {
+        print!("{}", config.to_toml());
+        return;
+    }
+    let telemetry = chessclouds_telemetry::init(&config.log, "ws_server").unwrap_or_else(|e| {
+        eprintln!("{e}");
+        process::exit(1);
+    });
 
-    let state = AppState {
-        active_games: Arc::new(HashMap::default()),
-        pool,
+    let in_memory = config.database.store == StoreKind::Memory && !args.migrate;
+    let pool_options = PgPoolOptions::new().max_connections(config.database.max_connections);
+    let pool = if in_memory {
+        // games do not need the database, chat and history connect on first use
+        match env::var("DATABASE_URL") {
+            Ok(url) => pool_options.connect_lazy(&url).unwrap_or_else(|e| {
+                eprintln!("invalid configuration: DATABASE_URL: {e}");
+                process::exit(1);
+            }),
+            Err(_) => pool_options.connect_lazy_with(PgConnectOptions::new()),
+        }
+    } else {
+        pool_options
+            .connect(&env::var("DATABASE_URL").expect("expecting DATABASE_URL in .env"))
+            .await
+            .unwrap()
     };
 
-    let app = Router::new()
-        .route("/ws", any(ws_handler))
-        .route("/init", post(post_init))
-        .route("/games", get(get_games))
-        .with_state(state)
-        .layer(cors);
+    if args.migrate || (config.database.run_migrations && !in_memory) {
+        if let Err(e) = db::MIGRATOR.run(&pool).await {
+            tracing::error!("database migration failed: {e}");
+            telemetry.shutdown();
+            process::exit(1);
+        }
+        tracing::info!("database schema is up to date");
+    }
+    if args.migrate {
+        telemetry.shutdown();
+        return;
+    }
 
-    let listener = tokio::net::TcpListener::bind(HOST).await.unwrap();
-    tracing::info!("Running at {HOST}");
-    axum::serve(listener, app).await.unwrap();
-}
+    let state = AppState::new(config, pool);
+
+    if cluster::is_clustered(&state) {
+        tokio::spawn(cluster::heartbeat(state.clone()));
+    }
 
+    let app = route::router(state.clone());
+
+    let listener = tokio::net::TcpListener::bind(&state.config.host)
+        .await
+        .unwrap();
+    tracing::info!("Running at {} as node {}", state.config.host, state.node.id);
+    // keep serving while draining so clients still get `ServerRestarting`
+    // and reconnects are answered until everything is handed off
+    axum::serve(
+        listener,
+        // the GIF rate limit is per client IP
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        cluster::drain(&state).await;
+    })
+    .await
+    .unwrap();
+
+    telemetry.shutdown();
+}
@@ -0,0 +1,105 @@
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+
+/// Prometheus metrics of this node, exported at `/metrics`.
+pub struct Metrics {
+    registry: Registry,
+    /// Refreshed from the game map on every scrape.
+    pub active_games: IntGauge,
+    /// Authenticated sockets by role (`white`, `black`, `spectator`).
+    pub connected_sockets: IntGaugeVec,
+    /// Moves played, including premoves played by the server.
+    pub moves: IntCounter,
+    /// Rejected authentications by `Error` variant.
+    pub auth_failures: IntCounterVec,
+    /// Rejected in-game messages by `Error` variant.
+    pub rejected_messages: IntCounterVec,
+    pub message_handling_seconds: Histogram,
+    /// Connections closed for not answering pings.
+    pub timed_out_connections: IntCounter,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let active_games = IntGauge::new("ws_active_games", "Games held in memory").unwrap();
+        let connected_sockets = IntGaugeVec::new(
+            Opts::new(
+                "ws_connected_sockets",
+                "Authenticated WebSocket connections",
+            ),
+            &["role"],
+        )
+        .unwrap();
+        let moves = IntCounter::new("ws_moves_total", "Moves played").unwrap();
+        let auth_failures = IntCounterVec::new(
+            Opts::new("ws_auth_failures_total", "Rejected authentications"),
+            &["error"],
+        )
+        .unwrap();
+        let rejected_messages = IntCounterVec::new(
+            Opts::new("ws_rejected_messages_total", "Rejected client messages"),
+            &["error"],
+        )
+        .unwrap();
+        let message_handling_seconds = Histogram::with_opts(
+            HistogramOpts::new(
+                "ws_message_handling_seconds",
+                "Time spent handling a client message",
+            )
+            .buckets(vec![0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5]),
+        )
+        .unwrap();
+
+        let timed_out_connections = IntCounter::new(
+            "ws_timed_out_connections_total",
+            "Connections closed after the idle timeout",
+        )
+        .unwrap();
+
+        registry.register(Box::new(active_games.clone())).unwrap();
+        registry
+            .register(Box::new(connected_sockets.clone()))
+            .unwrap();
+        registry.register(Box::new(moves.clone())).unwrap();
+        registry.register(Box::new(auth_failures.clone())).unwrap();
+        registry
+            .register(Box::new(rejected_messages.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(message_handling_seconds.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(timed_out_connections.clone()))
+            .unwrap();
+
+        Metrics {
+            registry,
+            active_games,
+            connected_sockets,
+            moves,
+            auth_failures,
+            rejected_messages,
+            message_handling_seconds,
+            timed_out_connections,
+        }
+    }
+
+    /// All metrics in the Prometheus text format.
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("metrics should encode");
+        String::from_utf8(buffer).expect("metrics should be UTF-8")
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,143 @@
+use std::fmt::Write;
+
+use crate::{db::GameStatus, history::GameRecord};
+
+pub mod import;
+
+/// Movetext lines are wrapped to stay within the 79 characters the PGN
+/// export format recommends.
+const MAX_LINE_LENGTH: usize = 79;
+
+/// Media type of PGN files.
+pub const CONTENT_TYPE: &str = "application/x-chess-pgn";
+
+/// The `Result` tag value and game termination marker for a `GameStatus`.
+pub fn result(status: GameStatus) -> &'static str {
+    match status {
+        GameStatus::WhiteWins => "1-0",
+        GameStatus::BlackWins => "0-1",
+        GameStatus::Draw => "1/2-1/2",
+        GameStatus::OnGoing | GameStatus::Aborted => "*",
+    }
+}
+
+/// A game in PGN export format: the Seven Tag Roster and `TimeControl`,
+/// then the wrapped movetext. Ends with a blank line, so exported games can
+/// be concatenated into a multi-game file.
+pub fn export(game: &GameRecord) -> String {
+    let result = result(game.status);
+    let date = game.created_at.map_or_else(
+        || "????.??.??".to_string(),
+        |created_at| created_at.format("%Y.%m.%d").to_string(),
+    );
+    let tags = [
+        ("Event", "Casual game"),
+        ("Site", "ChessClouds"),
+        ("Date", &date),
+        ("Round", "-"),
+        ("White", &game.white),
+        ("Black", &game.black),
+        ("Result", result),
+        ("TimeControl", game.time_control.as_deref().unwrap_or("-")),
+    ];
+
+    let mut pgn = String::new();
+    for (name, value) in tags {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        writeln!(pgn, "[{name} \"{value}\"]").unwrap();
+    }
+    pgn.push('\n');
+
+    let numbers: Vec<String> = (1..=game.moves.len().div_ceil(2))
+        .map(|n| format!("{n}."))
+        .collect();
+    let tokens = game
+        .moves
+        .iter()
+        .enumerate()
+        .flat_map(|(i, san)| {
+            let number = (i % 2 == 0).then(|| numbers[i / 2].as_str());
+            number.into_iter().chain([san.as_str()])
+        })
+        .chain([result]);
+
+    let mut line_length = 0;
+    for token in tokens {
+        if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+            pgn.push('\n');
+            line_length = 0;
+        }
+        if line_length > 0 {
+            pgn.push(' ');
+            line_length += 1;
+        }
+        pgn.push_str(token);
+        line_length += token.len();
+    }
+    pgn.push_str("\n\n");
+    pgn
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn game(moves: &[&str], status: GameStatus) -> GameRecord {
+        GameRecord {
+            game_id: "00000000-0000-0000-0000-000000000000".to_string(),
+            white: "alice".to_string(),
+            black: "bob \"the blunderer\"".to_string(),
+            status,
+            created_at: NaiveDate::from_ymd_opt(2026, 10, 19)
+                .unwrap()
+                .and_hms_opt(12, 0, 0),
+            time_control: Some("300+2".to_string()),
+            moves: moves.iter().map(|san| san.to_string()).collect(),
+            imported: false,
+        }
+    }
+
+    #[test]
+    fn results() {
+        assert_eq!(result(GameStatus::WhiteWins), "1-0");
+        assert_eq!(result(GameStatus::BlackWins), "0-1");
+        assert_eq!(result(GameStatus::Draw), "1/2-1/2");
+        assert_eq!(result(GameStatus::OnGoing), "*");
+        assert_eq!(result(GameStatus::Aborted), "*");
+    }
+
+    #[test]
+    fn exports_tags_and_movetext() {
+        let pgn = export(&game(&["f3", "e5", "g4", "Qh4#"], GameStatus::BlackWins));
+        assert_eq!(
+            pgn,
+            "[Event \"Casual game\"]\n\
+             [Site \"ChessClouds\"]\n\
+             [Date \"2026.10.19\"]\n\
+             [Round \"-\"]\n\
+             [White \"alice\"]\n\
+             [Black \"bob \\\"the blunderer\\\"\"]\n\
+             [Result \"0-1\"]\n\
+             [TimeControl \"300+2\"]\n\
+             \n\
+             1. f3 e5 2. g4 Qh4# 0-1\n\n"
+        );
+    }
+
+    #[test]
+    fn wraps_long_games_and_parses_back() {
+        let moves = ["Nf3", "Nf6", "Ng1", "Ng8"].repeat(20);
+        let pgn = export(&game(&moves, GameStatus::Draw));
+        let movetext = pgn.split("\n\n").nth(1).unwrap();
+        assert!(movetext.lines().count() > 1);
+        assert!(movetext.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+
+        let report = import::parse(&pgn, 1);
+        assert!(report.errors.is_empty());
+        assert_eq!(report.games[0].moves, moves);
+        assert_eq!(report.games[0].result, "1/2-1/2");
+        assert_eq!(report.games[0].tag("Black"), Some("bob \"the blunderer\""));
+    }
+}
@@ -0,0 +1,667 @@
+use serde::Serialize;
+use shakmaty::{san::SanPlus, Chess, Position};
+use ts_rs::TS;
+
+/// Characters that end a SAN symbol besides whitespace.
+const DELIMITERS: &str = "{}()[];$";
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, TS)]
+#[ts(export)]
+pub struct PgnTag {
+    pub name: String,
+    pub value: String,
+}
+
+/// A game that passed validation. Comments, NAGs and variations are checked
+/// but only the mainline is kept, in normalized SAN.
+#[derive(Serialize, Debug, Clone, TS)]
+#[ts(export)]
+pub struct ParsedGame {
+    pub tags: Vec<PgnTag>,
+    pub moves: Vec<String>,
+    /// The game termination marker, `1-0`, `0-1`, `1/2-1/2` or `*`.
+    pub result: String,
+}
+
+impl ParsedGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|tag| tag.name == name)
+            .map(|tag| tag.value.as_str())
+    }
+}
+
+/// The first problem found in a game; the rest of that game is skipped.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, TS)]
+#[ts(export)]
+pub struct PgnError {
+    /// Position of the game in the file, starting at 1.
+    pub game: u32,
+    pub line: u32,
+    pub column: u32,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub input: Option<String>,
+}
+
+#[derive(Serialize, Debug, Default, TS)]
+#[ts(export)]
+pub struct PgnReport {
+    pub games: Vec<ParsedGame>,
+    pub errors: Vec<PgnError>,
+}
+
+#[derive(Debug)]
+enum Token<'a> {
+    Tag { name: &'a str, value: String },
+    Comment,
+    Nag,
+    MoveNumber,
+    StartVariation,
+    EndVariation,
+    Result(&'a str),
+    Symbol(&'a str),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    line: u32,
+    column: u32,
+}
+
+struct LexError {
+    location: Location,
+    message: String,
+    input: Option<String>,
+}
+
+struct Lexer<'a> {
+    text: &'a str,
+    offset: usize,
+    line: u32,
+    column: u32,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Self {
+        Lexer {
+            text,
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn location(&self) -> Location {
+        Location {
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.offset..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn bump_while(&mut self, mut f: impl FnMut(char) -> bool) -> &'a str {
+        let start = self.offset;
+        while self.peek().is_some_and(&mut f) {
+            self.bump();
+        }
+        &self.text[start..self.offset]
+    }
+
+    fn skip_line(&mut self) {
+        self.bump_while(|c| c != '\n');
+    }
+
+    fn next_token(&mut self) -> Option<Result<(Location, Token<'a>), LexError>> {
+        loop {
+            self.bump_while(char::is_whitespace);
+            // escape mechanism: lines starting with `%` are ignored
+            if self.column == 1 && self.peek() == Some('%') {
+                self.skip_line();
+            } else {
+                break;
+            }
+        }
+
+        let location = self.location();
+        let error = |message: &str, input: Option<&str>| {
+            Some(Err(LexError {
+                location,
+                message: message.to_string(),
+                input: input.map(str::to_string),
+            }))
+        };
+        let token = match self.peek()? {
+            '{' => {
+                self.bump_while(|c| c != '}');
+                if self.bump().is_none() {
+                    return error("unterminated comment", None);
+                }
+                Token::Comment
+            }
+            ';' => {
+                self.skip_line();
+                Token::Comment
+            }
+            '(' => {
+                self.bump();
+                Token::StartVariation
+            }
+            ')' => {
+                self.bump();
+                Token::EndVariation
+            }
+            '$' => {
+                self.bump();
+                if self.bump_while(|c| c.is_ascii_digit()).is_empty() {
+                    return error("expected a number after $", None);
+                }
+                Token::Nag
+            }
+            '[' => {
+                let start = self.offset;
+                match self.tag() {
+                    Some(token) => token,
+                    None => {
+                        self.skip_line();
+                        let line = self.text[start..self.offset].trim_end();
+                        return error("malformed tag pair", Some(line));
+                    }
+                }
+            }
+            '*' => {
+                self.bump();
+                Token::Result("*")
+            }
+            c if c.is_ascii_digit() => {
+                let rest = &self.text[self.offset..];
+                let result = ["1-0", "0-1", "1/2-1/2"].into_iter().find(|result| {
+                    rest.strip_prefix(result).is_some_and(|after| {
+                        after
+                            .chars()
+                            .next()
+                            .is_none_or(|c| c.is_whitespace() || DELIMITERS.contains(c))
+                    })
+                });
+                if let Some(result) = result {
+                    for _ in 0..result.len() {
+                        self.bump();
+                    }
+                    Token::Result(result)
+                } else if rest.starts_with("0-0") {
+                    // castling written with zeros
+                    Token::Symbol(self.symbol())
+                } else {
+                    self.bump_while(|c| c.is_ascii_digit());
+                    self.bump_while(|c| c == '.');
+                    Token::MoveNumber
+                }
+            }
+            _ => Token::Symbol(self.symbol()),
+        };
+        Some(Ok((location, token)))
+    }
+
+    fn symbol(&mut self) -> &'a str {
+        let symbol = self.bump_while(|c| !c.is_whitespace() && !DELIMITERS.contains(c));
+        if symbol.is_empty() {
+            // a stray `]`, `}` or `"`
+            let start = self.offset;
+            self.bump();
+            return &self.text[start..self.offset];
+        }
+        symbol
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let matches = self.peek() == Some(c);
+        if matches {
+            self.bump();
+        }
+        matches
+    }
+
+    /// `[Name "value"]`, `None` if malformed. Never reads past the line.
+    fn tag(&mut self) -> Option<Token<'a>> {
+        self.bump();
+        self.bump_while(|c| c == ' ' || c == '\t');
+        let name = self.bump_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        self.bump_while(|c| c == ' ' || c == '\t');
+        if name.is_empty() || !self.eat('"') {
+            return None;
+        }
+        let mut value = String::new();
+        loop {
+            match self.peek().filter(|&c| c != '\n')? {
+                '"' => break,
+                '\\' => {
+                    self.bump();
+                    value.push(self.peek().filter(|&c| c != '\n')?);
+                }
+                c => value.push(c),
+            }
+            self.bump();
+        }
+        self.bump();
+        self.bump_while(|c| c == ' ' || c == '\t');
+        self.eat(']').then_some(Token::Tag { name, value })
+    }
+}
+
+/// The game being read.
+struct GameBuilder {
+    number: u32,
+    tags: Vec<PgnTag>,
+    moves: Vec<String>,
+    position: Chess,
+    /// Position before the last move, where a variation starting here
+    /// branches off.
+    before: Option<Chess>,
+    /// `position` and `before` of the enclosing lines of open variations.
+    variations: Vec<(Chess, Option<Chess>)>,
+    in_movetext: bool,
+    /// Set after the first error, the rest of the game is skipped.
+    failed: bool,
+}
+
+impl GameBuilder {
+    fn new(number: u32) -> Self {
+        GameBuilder {
+            number,
+            tags: Vec::new(),
+            moves: Vec::new(),
+            position: Chess::default(),
+            before: None,
+            variations: Vec::new(),
+            in_movetext: false,
+            failed: false,
+        }
+    }
+
+    fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|tag| tag.name == name)
+            .map(|tag| tag.value.as_str())
+    }
+
+    fn add_tag(&mut self, name: &str, value: String) -> Result<(), (String, Option<String>)> {
+        match name {
+            "FEN" => {
+                return Err((
+                    "games from a custom starting position are not supported".to_string(),
+                    Some(value),
+                ))
+            }
+            "Variant" if !value.eq_ignore_ascii_case("standard") => {
+                return Err(("only standard chess is supported".to_string(), Some(value)))
+            }
+            _ => {}
+        }
+        self.tags.push(PgnTag {
+            name: name.to_string(),
+            value,
+        });
+        Ok(())
+    }
+
+    fn play(&mut self, symbol: &str) -> Result<(), (String, Option<String>)> {
+        let san = symbol.trim_end_matches(['!', '?']).replace('0', "O");
+        let san: SanPlus = san
+            .parse()
+            .map_err(|_| ("not a move in SAN".to_string(), Some(symbol.to_string())))?;
+        let m = san.san.to_move(&self.position).map_err(|_| {
+            (
+                "illegal move in this position".to_string(),
+                Some(symbol.to_string()),
+            )
+        })?;
+        let before = self.position.clone();
+        let san = SanPlus::from_move_and_play_unchecked(&mut self.position, &m);
+        if self.variations.is_empty() {
+            self.moves.push(san.to_string());
+        }
+        self.before = Some(before);
+        Ok(())
+    }
+
+    fn start_variation(&mut self) -> Result<(), (String, Option<String>)> {
+        let Some(before) = self.before.take() else {
+            return Err(("variation without a preceding move".to_string(), None));
+        };
+        let position = std::mem::replace(&mut self.position, before.clone());
+        self.variations.push((position, Some(before)));
+        Ok(())
+    }
+
+    fn end_variation(&mut self) -> Result<(), (String, Option<String>)> {
+        let (position, before) = self
+            .variations
+            .pop()
+            .ok_or(("unmatched closing parenthesis".to_string(), None))?;
+        self.position = position;
+        self.before = before;
+        Ok(())
+    }
+
+    fn finish(self, result: &str) -> Result<ParsedGame, (String, Option<String>)> {
+        if !self.variations.is_empty() {
+            return Err(("unterminated variation".to_string(), None));
+        }
+        if let Some(tag) = self.tag("Result").filter(|&tag| tag != result) {
+            return Err((
+                format!("result does not match the Result tag {tag}"),
+                Some(result.to_string()),
+            ));
+        }
+        if let Some(outcome) = self.position.outcome() {
+            if result != "*" && result != outcome.as_str() {
+                return Err((
+                    format!("the final position is a {outcome} result"),
+                    Some(result.to_string()),
+                ));
+            }
+        }
+        Ok(ParsedGame {
+            tags: self.tags,
+            moves: self.moves,
+            result: result.to_string(),
+        })
+    }
+}
+
+fn fail(
+    report: &mut PgnReport,
+    game: &mut GameBuilder,
+    location: Location,
+    (message, input): (String, Option<String>),
+) {
+    if !game.failed {
+        game.failed = true;
+        report.errors.push(PgnError {
+            game: game.number,
+            line: location.line,
+            column: location.column,
+            message,
+            input,
+        });
+    }
+}
+
+/// Parses a file of one or more games, validating every move including
+/// those in variations. Parsing stops after `max_games` games.
+pub fn parse(text: &str, max_games: usize) -> PgnReport {
+    let mut report = PgnReport::default();
+    let mut lexer = Lexer::new(text);
+    let mut game: Option<GameBuilder> = None;
+    let mut started = 0;
+
+    while let Some(token) = lexer.next_token() {
+        let (location, token) = match token {
+            Ok(token) => token,
+            Err(e) => {
+                let game = game.get_or_insert_with(|| {
+                    started += 1;
+                    GameBuilder::new(started)
+                });
+                fail(&mut report, game, e.location, (e.message, e.input));
+                continue;
+            }
+        };
+
+        // a tag after movetext starts the next game
+        let starts_game = match (&game, &token) {
+            (None, _) => true,
+            (Some(game), Token::Tag { .. }) => game.in_movetext,
+            _ => false,
+        };
+        if starts_game {
+            if let Some(mut previous) = game.take() {
+                let e = ("missing game termination marker".to_string(), None);
+                fail(&mut report, &mut previous, location, e);
+            }
+            if started as usize == max_games {
+                report.errors.push(PgnError {
+                    game: started + 1,
+                    line: location.line,
+                    column: location.column,
+                    message: format!("more than {max_games} games"),
+                    input: None,
+                });
+                return report;
+            }
+            started += 1;
+            game = Some(GameBuilder::new(started));
+        }
+        let current = game.as_mut().expect("game was started above");
+
+        if current.failed {
+            if let Token::Result(_) = token {
+                game = None;
+            } else if !matches!(token, Token::Tag { .. }) {
+                current.in_movetext = true;
+            }
+            continue;
+        }
+
+        let outcome = match token {
+            Token::Tag { name, value } => current.add_tag(name, value),
+            Token::Comment | Token::Nag | Token::MoveNumber => {
+                current.in_movetext = true;
+                Ok(())
+            }
+            Token::StartVariation => {
+                current.in_movetext = true;
+                current.start_variation()
+            }
+            Token::EndVariation => current.end_variation(),
+            Token::Symbol(symbol) => {
+                current.in_movetext = true;
+                current.play(symbol)
+            }
+            Token::Result(result) => {
+                let mut finished = game.take().expect("game was started above");
+                let number = finished.number;
+                match finished.finish(result) {
+                    Ok(parsed) => report.games.push(parsed),
+                    Err(e) => {
+                        // `finish` consumed the game, report on a stand-in
+                        finished = GameBuilder::new(number);
+                        fail(&mut report, &mut finished, location, e);
+                    }
+                }
+                continue;
+            }
+        };
+        if let Err(e) = outcome {
+            fail(&mut report, current, location, e);
+        }
+    }
+
+    if let Some(mut game) = game {
+        let e = ("missing game termination marker".to_string(), None);
+        fail(&mut report, &mut game, lexer.location(), e);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_one(text: &str) -> ParsedGame {
+        let report = parse(text, 10);
+        assert_eq!(report.errors, [], "{text}");
+        assert_eq!(report.games.len(), 1);
+        report.games.into_iter().next().unwrap()
+    }
+
+    fn parse_error(text: &str) -> PgnError {
+        let report = parse(text, 10);
+        assert!(report.games.is_empty(), "{text}");
+        assert_eq!(report.errors.len(), 1, "{:?}", report.errors);
+        report.errors.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn tags_and_mainline() {
+        let game = parse_one(
+            "[Event \"Casual\"]\n[White \"alice\"]\n[Result \"1-0\"]\n\n1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0\n",
+        );
+        assert_eq!(game.tag("White"), Some("alice"));
+        assert_eq!(game.tag("Black"), None);
+        assert_eq!(
+            game.moves,
+            ["e4", "e5", "Qh5", "Nc6", "Bc4", "Nf6", "Qxf7#"]
+        );
+        assert_eq!(game.result, "1-0");
+    }
+
+    #[test]
+    fn escaped_tag_values() {
+        let game = parse_one(r#"[Event "The \"Big\" \\ game"] *"#);
+        assert_eq!(game.tag("Event"), Some(r#"The "Big" \ game"#));
+    }
+
+    #[test]
+    fn comments_nags_and_annotations_are_skipped() {
+        let game = parse_one(
+            "1. e4 {best by test} e5 $1 ; rest of line\n2. Nf3!? Nc6??\n% escaped line 3. a3\n*",
+        );
+        assert_eq!(game.moves, ["e4", "e5", "Nf3", "Nc6"]);
+    }
+
+    #[test]
+    fn variations_are_validated_but_not_kept() {
+        let game = parse_one("1. e4 (1. d4 d5 (1... Nf6 2. c4)) e5 2. Nf3 (2. f4 exf4) *");
+        assert_eq!(game.moves, ["e4", "e5", "Nf3"]);
+
+        let error = parse_error("1. e4 (1. d4 d4) e5 *");
+        assert_eq!(error.message, "illegal move in this position");
+        assert_eq!(error.input.as_deref(), Some("d4"));
+        assert_eq!((error.line, error.column), (1, 14));
+    }
+
+    #[test]
+    fn moves_are_normalized() {
+        let game = parse_one("1. e4 e5 2. Nf3 Nf6 3. Bc4 Bc5 4. 0-0 O-O 5. Nxe5+ *");
+        assert_eq!(
+            game.moves,
+            ["e4", "e5", "Nf3", "Nf6", "Bc4", "Bc5", "O-O", "O-O", "Nxe5"]
+        );
+    }
+
+    #[test]
+    fn result_tokens() {
+        for result in ["1-0", "0-1", "1/2-1/2", "*"] {
+            let game = parse_one(&format!("[Result \"{result}\"] 1. e4 {result}"));
+            assert_eq!(game.result, result);
+        }
+
+        let error = parse_error("[Result \"1-0\"] 1. e4 0-1");
+        assert_eq!(error.message, "result does not match the Result tag 1-0");
+        assert_eq!(error.input.as_deref(), Some("0-1"));
+
+        let error = parse_error("1. f3 e5 2. g4 Qh4# 1-0");
+        assert_eq!(error.message, "the final position is a 0-1 result");
+    }
+
+    #[test]
+    fn errors_have_line_and_column() {
+        let error = parse_error("[Event \"x\"]\n\n1. e4 e5\n2. Ke3 Nc6 *");
+        assert_eq!(error.game, 1);
+        assert_eq!((error.line, error.column), (4, 4));
+        assert_eq!(error.message, "illegal move in this position");
+        assert_eq!(error.input.as_deref(), Some("Ke3"));
+
+        let error = parse_error("1. e4 Xz9 *");
+        assert_eq!((error.line, error.column), (1, 7));
+        assert_eq!(error.message, "not a move in SAN");
+
+        let error = parse_error("[Event \"x]\n1. e4 *");
+        assert_eq!((error.line, error.column), (1, 1));
+        assert_eq!(error.message, "malformed tag pair");
+
+        let error = parse_error("1. e4 {never closed *");
+        assert_eq!((error.line, error.column), (1, 7));
+        assert_eq!(error.message, "unterminated comment");
+
+        assert_eq!(
+            parse_error("1. e4 e5").message,
+            "missing game termination marker"
+        );
+        assert_eq!(parse_error("1. e4 (e3 *").message, "unterminated variation");
+        assert_eq!(
+            parse_error("1. e4 ) *").message,
+            "unmatched closing parenthesis"
+        );
+        assert_eq!(
+            parse_error("(1. e4) *").message,
+            "variation without a preceding move"
+        );
+        assert_eq!(
+            parse_error("1. e4 $ *").message,
+            "expected a number after $"
+        );
+    }
+
+    #[test]
+    fn unsupported_games_are_rejected() {
+        let error = parse_error("[FEN \"8/8/8/8/8/8/8/K1k5 w - - 0 1\"] *");
+        assert_eq!(
+            error.message,
+            "games from a custom starting position are not supported"
+        );
+        assert!(parse_one("[Variant \"Standard\"] *").moves.is_empty());
+        assert_eq!(
+            parse_error("[Variant \"Atomic\"] *").message,
+            "only standard chess is supported"
+        );
+    }
+
+    #[test]
+    fn multiple_games_are_reported_separately() {
+        let report = parse(
+            "[Event \"a\"]\n1. e4 *\n\n[Event \"b\"]\n1. e5 *\n\n[Event \"c\"]\n1. d4 d5 1/2-1/2\n",
+            10,
+        );
+        assert_eq!(report.games.len(), 2);
+        assert_eq!(report.games[0].tag("Event"), Some("a"));
+        assert_eq!(report.games[1].tag("Event"), Some("c"));
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].game, 2);
+        assert_eq!(report.errors[0].line, 5);
+
+        // a game without a termination marker ends at the next tag
+        let report = parse("[Event \"a\"]\n1. e4\n[Event \"b\"]\n1. d4 *", 10);
+        assert_eq!(report.games.len(), 1);
+        assert_eq!(report.errors[0].game, 1);
+        assert_eq!(report.errors[0].message, "missing game termination marker");
+    }
+
+    #[test]
+    fn parsing_stops_after_max_games() {
+        let report = parse("1. e4 * 1. d4 * 1. c4 *", 2);
+        assert_eq!(report.games.len(), 2);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].game, 3);
+        assert_eq!(report.errors[0].message, "more than 2 games");
+    }
+}
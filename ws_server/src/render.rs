@@ -0,0 +1,407 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    sync::Mutex,
+};
+
+use axum::body::Bytes;
+use resvg::{tiny_skia, usvg};
+use shakmaty::{san::San, Board, CastlingSide, Chess, Color, Move, Piece, Position, Role, Square};
+
+/// Side of a square in SVG user units; the board is 8 squares wide.
+const SQUARE: f32 = 45.0;
+const LIGHT: &str = "#f0d9b5";
+const DARK: &str = "#b58863";
+const HIGHLIGHT: &str = "#9bc700";
+const ARROW: &str = "#15781b";
+/// How long the final position stays on screen before an animation loops.
+const FINAL_FRAME_DELAY_MS: u32 = 3000;
+
+/// A position as it should be drawn.
+#[derive(Debug, Clone)]
+pub struct BoardImage {
+    pub board: Board,
+    /// The side shown at the bottom.
+    pub orientation: Color,
+    pub last_move: Option<(Square, Square)>,
+    /// Square of a king in check.
+    pub check: Option<Square>,
+    pub arrows: Vec<(Square, Square)>,
+}
+
+impl BoardImage {
+    /// `position` seen from `orientation`, with its king highlighted if in check.
+    pub fn new(position: &Chess, orientation: Color) -> Self {
+        BoardImage {
+            board: position.board().clone(),
+            orientation,
+            last_move: None,
+            check: position
+                .is_check()
+                .then(|| position.board().king_of(position.turn()))
+                .flatten(),
+            arrows: Vec::new(),
+        }
+    }
+
+    /// Top left corner of `square` in SVG user units.
+    fn origin(&self, square: Square) -> (f32, f32) {
+        let file = u32::from(square.file()) as f32;
+        let rank = u32::from(square.rank()) as f32;
+        match self.orientation {
+            Color::White => (file * SQUARE, (7.0 - rank) * SQUARE),
+            Color::Black => ((7.0 - file) * SQUARE, rank * SQUARE),
+        }
+    }
+
+    fn center(&self, square: Square) -> (f32, f32) {
+        let (x, y) = self.origin(square);
+        (x + SQUARE / 2.0, y + SQUARE / 2.0)
+    }
+}
+
+/// The position after each move of a game, starting with the initial
+/// position. `None` if a move is not legal.
+pub fn game_frames(moves: &[String], orientation: Color) -> Option<Vec<BoardImage>> {
+    let mut position = Chess::default();
+    let mut frames = vec![BoardImage::new(&position, orientation)];
+    for san in moves {
+        let m = san.parse::<San>().ok()?.to_move(&position).ok()?;
+        let mover = position.turn();
+        position.play_unchecked(&m);
+        let mut frame = BoardImage::new(&position, orientation);
+        frame.last_move = highlighted_squares(&m, mover);
+        frames.push(frame);
+    }
+    Some(frames)
+}
+
+/// Where `m` moved a piece from and to. Castling is shown as the king's
+/// move, not as the king capturing its own rook like shakmaty encodes it.
+fn highlighted_squares(m: &Move, color: Color) -> Option<(Square, Square)> {
+    match *m {
+        Move::Castle { king, rook } => {
+            let side = CastlingSide::from_queen_side(rook.file() < king.file());
+            Some((king, side.king_to(color)))
+        }
+        _ => m.from().map(|from| (from, m.to())),
+    }
+}
+
+/// At most `max` of `frames`, spread evenly over the game and always
+/// including the first and the last. `max` must be at least 2.
+pub fn sample_frames(mut frames: Vec<BoardImage>, max: usize) -> Vec<BoardImage> {
+    let len = frames.len();
+    if len <= max {
+        return frames;
+    }
+    let keep: Vec<usize> = (0..max).map(|i| i * (len - 1) / (max - 1)).collect();
+    let mut index = 0;
+    frames.retain(|_| {
+        let kept = keep.binary_search(&index).is_ok();
+        index += 1;
+        kept
+    });
+    frames
+}
+
+/// The board as an SVG document `size` pixels wide.
+pub fn svg(image: &BoardImage, size: u32) -> String {
+    let board_size = SQUARE * 8.0;
+    let mut svg = String::new();
+    write!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{size}" height="{size}" viewBox="0 0 {board_size} {board_size}">"##
+    )
+    .unwrap();
+    svg.push_str(
+        r##"<defs><radialGradient id="check"><stop offset="0%" stop-color="#ff0000"/><stop offset="25%" stop-color="#e70000"/><stop offset="90%" stop-color="#a90000" stop-opacity="0"/></radialGradient></defs>"##,
+    );
+
+    for square in Square::ALL {
+        let (x, y) = image.origin(square);
+        let color = if square.is_light() { LIGHT } else { DARK };
+        write!(
+            svg,
+            r##"<rect x="{x}" y="{y}" width="{SQUARE}" height="{SQUARE}" fill="{color}"/>"##
+        )
+        .unwrap();
+    }
+    if let Some((from, to)) = image.last_move {
+        for square in [from, to] {
+            let (x, y) = image.origin(square);
+            write!(
+                svg,
+                r##"<rect x="{x}" y="{y}" width="{SQUARE}" height="{SQUARE}" fill="{HIGHLIGHT}" fill-opacity="0.41"/>"##
+            )
+            .unwrap();
+        }
+    }
+    if let Some(square) = image.check {
+        let (x, y) = image.center(square);
+        let r = SQUARE / 2.0;
+        write!(
+            svg,
+            r##"<circle cx="{x}" cy="{y}" r="{r}" fill="url(#check)"/>"##
+        )
+        .unwrap();
+    }
+
+    for square in Square::ALL {
+        if let Some(piece) = image.board.piece_at(square) {
+            let (x, y) = image.origin(square);
+            write!(svg, r##"<g transform="translate({x} {y})">"##).unwrap();
+            piece_svg(&mut svg, piece);
+            svg.push_str("</g>");
+        }
+    }
+
+    for &(from, to) in &image.arrows {
+        arrow_svg(&mut svg, image.center(from), image.center(to));
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Simple piece silhouettes drawn in a 45 unit square.
+fn piece_svg(svg: &mut String, piece: Piece) {
+    let (fill, detail) = match piece.color {
+        Color::White => ("#ffffff", "#000000"),
+        Color::Black => ("#000000", "#ffffff"),
+    };
+    write!(
+        svg,
+        r##"<g fill="{fill}" stroke="#000000" stroke-width="1.5" stroke-linejoin="round" stroke-linecap="round">"##
+    )
+    .unwrap();
+    let (body, details) = match piece.role {
+        Role::Pawn => (
+            r##"<circle cx="22.5" cy="14" r="5"/><path d="M18 20h9l-1 3c3 3 5 7 5 11H14c0-4 2-8 5-11z"/><path d="M12 34h21v4H12z"/>"##,
+            "",
+        ),
+        Role::Knight => (
+            r##"<path d="M14 38h20c0-9-1-16-5-22-1-2-2-4-3-5l-1 3c-1-1-3-2-5-2l1 3c-4 2-7 7-9 11-1 2 1 4 3 3l5-2c1 0 1 1 0 2-4 3-6 6-6 9z"/>"##,
+            r##"<circle cx="20" cy="18" r="1.3" stroke="none"/>"##,
+        ),
+        Role::Bishop => (
+            r##"<path d="M12 34h21v4H12z"/><path d="M16 30h13v4H16z"/><path d="M22.5 10c-6 4-9 10-7 16l1 4h12l1-4c2-6-1-12-7-16z"/><circle cx="22.5" cy="8" r="2.5"/>"##,
+            r##"<path d="M22.5 15v8M19 19h7" fill="none"/>"##,
+        ),
+        Role::Rook => (
+            r##"<path d="M11 34h23v4H11z"/><path d="M14 34l1-16h15l1 16z"/><path d="M12 10h4v3h4v-3h5v3h4v-3h4v5l-3 3H15l-3-3z"/>"##,
+            r##"<path d="M15 18h15" fill="none"/>"##,
+        ),
+        Role::Queen => (
+            r##"<path d="M11 34L9 14l4.5 9 2-12 4 11.5 3-13 3 13 4-11.5 2 12 4.5-9-2 20z"/><path d="M10 34h25v4H10z"/><circle cx="9" cy="13" r="2"/><circle cx="15.5" cy="10" r="2"/><circle cx="22.5" cy="8.5" r="2"/><circle cx="29.5" cy="10" r="2"/><circle cx="36" cy="13" r="2"/>"##,
+            "",
+        ),
+        Role::King => (
+            r##"<path d="M22.5 5v8M19 8.5h7" fill="none"/><path d="M22.5 13c-2 2-3 4-2.5 7l2.5 3 2.5-3c.5-3-.5-5-2.5-7z"/><path d="M12 34c-4-6-3-12 2-14 4-1.5 7 1 8.5 4 1.5-3 4.5-5.5 8.5-4 5 2 6 8 2 14z"/><path d="M10 34h25v4H10z"/>"##,
+            "",
+        ),
+    };
+    svg.push_str(body);
+    if !details.is_empty() {
+        write!(
+            svg,
+            r##"<g stroke="{detail}" fill="{detail}">{details}</g>"##
+        )
+        .unwrap();
+    }
+    svg.push_str("</g>");
+}
+
+fn arrow_svg(svg: &mut String, (x1, y1): (f32, f32), (x2, y2): (f32, f32)) {
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length == 0.0 {
+        return;
+    }
+    let (ux, uy) = (dx / length, dy / length);
+    let head_length = SQUARE * 0.4;
+    let head_width = SQUARE * 0.3;
+    // the head ends a little before the center of the target square
+    let (tip_x, tip_y) = (x2 - ux * SQUARE * 0.15, y2 - uy * SQUARE * 0.15);
+    let (base_x, base_y) = (tip_x - ux * head_length, tip_y - uy * head_length);
+    let (nx, ny) = (-uy * head_width, ux * head_width);
+    write!(
+        svg,
+        r##"<g fill="{ARROW}" stroke="{ARROW}" opacity="0.8"><line x1="{x1}" y1="{y1}" x2="{base_x}" y2="{base_y}" stroke-width="{}" stroke-linecap="round"/><polygon points="{tip_x},{tip_y} {},{} {},{}" stroke="none"/></g>"##,
+        SQUARE * 0.2,
+        base_x + nx,
+        base_y + ny,
+        base_x - nx,
+        base_y - ny,
+    )
+    .unwrap();
+}
+
+fn rasterize(image: &BoardImage, size: u32) -> Result<tiny_skia::Pixmap, String> {
+    let tree = usvg::Tree::from_str(&svg(image, size), &usvg::Options::default())
+        .map_err(|e| e.to_string())?;
+    let mut pixmap = tiny_skia::Pixmap::new(size, size).ok_or("invalid image size")?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    Ok(pixmap)
+}
+
+pub fn png(image: &BoardImage, size: u32) -> Result<Vec<u8>, String> {
+    rasterize(image, size)?
+        .encode_png()
+        .map_err(|e| e.to_string())
+}
+
+/// An animated GIF showing each frame for `delay_ms`, looping forever.
+pub fn gif(frames: &[BoardImage], size: u32, delay_ms: u32) -> Result<Vec<u8>, String> {
+    let side = u16::try_from(size).map_err(|_| "invalid image size")?;
+    let mut gif = Vec::new();
+    {
+        let mut encoder =
+            gif::Encoder::new(&mut gif, side, side, &[]).map_err(|e| e.to_string())?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(|e| e.to_string())?;
+        for (i, image) in frames.iter().enumerate() {
+            // boards are opaque, so the premultiplied pixels are plain RGBA
+            let mut pixels = rasterize(image, size)?.take();
+            let mut frame = gif::Frame::from_rgba_speed(side, side, &mut pixels, 10);
+            let delay = if i + 1 == frames.len() {
+                delay_ms.max(FINAL_FRAME_DELAY_MS)
+            } else {
+                delay_ms
+            };
+            frame.delay = u16::try_from(delay / 10).unwrap_or(u16::MAX);
+            encoder.write_frame(&frame).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(gif)
+}
+
+/// Identifies a rendered GIF.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GifKey {
+    pub game_id: String,
+    pub orientation: Color,
+    pub size: u32,
+    pub delay_ms: u32,
+}
+
+#[derive(Default)]
+struct GifEntries {
+    by_key: HashMap<GifKey, Bytes>,
+    /// Insertion order, the oldest are evicted first.
+    order: VecDeque<GifKey>,
+    bytes: usize,
+}
+
+/// GIFs of finished games, which never change, up to `max_bytes` in total.
+pub struct GifCache {
+    max_bytes: usize,
+    entries: Mutex<GifEntries>,
+}
+
+impl GifCache {
+    pub fn new(max_bytes: usize) -> Self {
+        GifCache {
+            max_bytes,
+            entries: Mutex::new(GifEntries::default()),
+        }
+    }
+
+    pub fn get(&self, key: &GifKey) -> Option<Bytes> {
+        self.entries.lock().unwrap().by_key.get(key).cloned()
+    }
+
+    pub fn insert(&self, key: GifKey, gif: Bytes) {
+        if gif.len() > self.max_bytes {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let size = gif.len();
+        if let Some(replaced) = entries.by_key.insert(key.clone(), gif) {
+            entries.bytes -= replaced.len();
+        } else {
+            entries.order.push_back(key);
+        }
+        entries.bytes += size;
+        while entries.bytes > self.max_bytes {
+            let Some(oldest) = entries.order.pop_front() else {
+                break;
+            };
+            if let Some(evicted) = entries.by_key.remove(&oldest) {
+                entries.bytes -= evicted.len();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves(sans: &str) -> Vec<String> {
+        sans.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn key(game_id: &str) -> GifKey {
+        GifKey {
+            game_id: game_id.to_string(),
+            orientation: Color::White,
+            size: 320,
+            delay_ms: 800,
+        }
+    }
+
+    #[test]
+    fn one_frame_per_position() {
+        let frames = game_frames(&moves("f3 e5 g4 Qh4#"), Color::White).unwrap();
+        assert_eq!(frames.len(), 5);
+        assert_eq!(frames[0].last_move, None);
+        assert_eq!(frames[4].last_move, Some((Square::D8, Square::H4)));
+        assert_eq!(frames[4].check, Some(Square::E1));
+
+        let frames = game_frames(&moves("e4 e5 Nf3 Nc6 Bc4 Bc5 O-O"), Color::White).unwrap();
+        assert_eq!(frames[7].last_move, Some((Square::E1, Square::G1)));
+
+        assert!(game_frames(&moves("e4 e4"), Color::White).is_none());
+    }
+
+    #[test]
+    fn samples_keep_the_first_and_last_positions() {
+        let sans = ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O"];
+        let frames = game_frames(&moves(&sans.join(" ")), Color::White).unwrap();
+        let sampled = sample_frames(frames.clone(), 4);
+        assert_eq!(sampled.len(), 4);
+        assert_eq!(sampled[0].last_move, None);
+        assert_eq!(sampled[3].last_move, frames[9].last_move);
+
+        assert_eq!(sample_frames(frames, 20).len(), 10);
+    }
+
+    #[test]
+    fn svg_and_raster_images() {
+        let image = BoardImage::new(&Chess::default(), Color::Black);
+        assert!(svg(&image, 100).starts_with("<svg"));
+        assert!(png(&image, 64).unwrap().starts_with(b"\x89PNG"));
+
+        let frames = game_frames(&moves("e4"), Color::White).unwrap();
+        assert!(gif(&frames, 32, 100).unwrap().starts_with(b"GIF89a"));
+    }
+
+    #[test]
+    fn gif_cache_evicts_the_oldest_first() {
+        let cache = GifCache::new(10);
+        cache.insert(key("a"), Bytes::from_static(b"aaaa"));
+        cache.insert(key("b"), Bytes::from_static(b"bbbb"));
+        assert!(cache.get(&key("a")).is_some());
+
+        cache.insert(key("c"), Bytes::from_static(b"cccc"));
+        assert!(cache.get(&key("a")).is_none());
+        assert!(cache.get(&key("b")).is_some());
+        assert!(cache.get(&key("c")).is_some());
+
+        // larger than the whole cache
+        cache.insert(key("d"), Bytes::from_static(b"ddddddddddd"));
+        assert!(cache.get(&key("d")).is_none());
+        assert!(cache.get(&key("c")).is_some());
+    }
+}
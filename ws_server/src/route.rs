@@ -1,3 +1,62 @@
 pub mod games;
+pub mod health;
+pub mod history;
+pub mod import;
 pub mod init;
+pub mod metrics;
+pub mod render;
 pub mod ws;
+
+use axum::{
+    extract::DefaultBodyLimit,
+    http::{header, Method},
+    routing::{any, get, post},
+    Router,
+};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::state::AppState;
+
+/// All HTTP and WebSocket routes of the service.
+pub fn router(state: AppState) -> Router {
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::any())
+        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::ACCEPT,
+            header::ORIGIN,
+            header::AUTHORIZATION,
+        ])
+        .allow_credentials(false);
+    let import_limit = DefaultBodyLimit::max(state.config.import.max_bytes);
+
+    Router::new()
+        .route("/ws", any(ws::ws_handler))
+        .route("/init", post(init::post_init))
+        .route("/games", get(games::get_games))
+        .route(
+            "/games/import",
+            post(import::post_games_import).layer(import_limit),
+        )
+        .route(
+            "/pgn/validate",
+            post(import::post_pgn_validate).layer(import_limit),
+        )
+        .route("/games/featured", get(games::get_featured_game))
+        .route("/games/{game_id}", get(history::get_game))
+        .route("/games/{game_id}/pgn", get(history::get_game_pgn))
+        .route("/games/{game_id}/image", get(render::get_game_image))
+        .route("/games/{game_id}/gif", get(render::get_game_gif))
+        .route("/render", get(render::get_render))
+        .route("/users/{user_id}/games", get(history::get_user_games))
+        .route(
+            "/users/{user_id}/games/pgn",
+            get(history::get_user_games_pgn),
+        )
+        .route("/metrics", get(metrics::get_metrics))
+        .route("/healthz", get(health::get_healthz))
+        .route("/readyz", get(health::get_readyz))
+        .with_state(state)
+        .layer(cors)
+}
@@ -1,16 +1,266 @@
-use axum::extract::State;
+use std::cmp::Reverse;
 
-use crate::state::AppState;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use chessclouds_protocol::Color;
+use chessclouds_store::pgn::moves_from_pgn;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use shakmaty::{fen::Fen, EnPassantMode, Position};
+use ts_rs::TS;
 
-// for debugging, might remove this later
-pub async fn get_games(State(state): State<AppState>) -> String {
+use crate::{
+    cluster::{self, OwnedGame},
+    route::history::Page,
+    state::{self, ActiveGame, AppState},
+};
+
+/// A game being played on any node of the cluster.
+#[derive(Serialize, Debug, Clone, TS)]
+#[ts(export)]
+pub struct LiveGame {
+    pub game_id: String,
+    pub white_user_id: String,
+    pub black_user_id: String,
+    pub white_rating: Option<u32>,
+    pub black_rating: Option<u32>,
+    pub time_control: Option<String>,
+    pub move_count: u32,
+    pub fen: String,
+    pub turn: Color,
+    pub spectators: u32,
+    pub created_at: DateTime<Utc>,
+}
+
+impl LiveGame {
+    fn new(game_id: &str, game: &ActiveGame) -> Self {
+        LiveGame {
+            game_id: game_id.to_string(),
+            white_user_id: game.white_user_id.clone(),
+            black_user_id: game.black_user_id.clone(),
+            white_rating: game.white_rating,
+            black_rating: game.black_rating,
+            time_control: game.settings.time_control.clone(),
+            move_count: game.moves.len() as u32,
+            fen: Fen::from_position(game.board.clone(), EnPassantMode::Legal).to_string(),
+            turn: game.board.turn().into(),
+            spectators: game.spectators.len() as u32,
+            created_at: game.created_at,
+        }
+    }
+
+    /// A game owned by another node. Ratings are only known to the owner.
+    fn from_owned(game: OwnedGame) -> Option<Self> {
+        let moves = moves_from_pgn(&game.pgn);
+        let board = state::replay(&moves)?;
+        Some(LiveGame {
+            game_id: game.game_id.to_string(),
+            white_user_id: game.white,
+            black_user_id: game.black,
+            white_rating: None,
+            black_rating: None,
+            time_control: game.time_control,
+            move_count: moves.len() as u32,
+            fen: Fen::from_position(board.clone(), EnPassantMode::Legal).to_string(),
+            turn: board.turn().into(),
+            spectators: game.spectators.max(0) as u32,
+            created_at: game.created_at.map_or_else(Utc::now, |t| t.and_utc()),
+        })
+    }
+
+    /// Mean of the known ratings.
+    fn rating(&self) -> Option<u32> {
+        match (self.white_rating, self.black_rating) {
+            (Some(white), Some(black)) => Some(((white as u64 + black as u64) / 2) as u32),
+            (rating, None) | (None, rating) => rating,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, TS)]
+#[serde(rename_all = "lowercase")]
+#[ts(export, rename_all = "lowercase")]
+pub enum LiveGameSort {
+    /// Most recently started first.
+    #[default]
+    Recent,
+    /// Most watched first.
+    Spectators,
+    /// Highest average rating first, unrated games last.
+    Rating,
+    /// Longest first.
+    Moves,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Sort {
+    #[serde(default)]
+    sort: LiveGameSort,
+}
+
+#[derive(Serialize, Debug, TS)]
+#[ts(export)]
+pub struct LiveGamePage {
+    pub games: Vec<LiveGame>,
+    /// Ongoing games in the cluster.
+    pub total: u32,
+    /// Pass as `offset` to fetch the next page, `None` on the last page.
+    pub next_offset: Option<u32>,
+}
+
+/// Games that have not ended yet: those in memory, including `/init` games
+/// that are not stored, and those other nodes own.
+async fn live_games(state: &AppState) -> Result<Vec<LiveGame>, (StatusCode, &'static str)> {
+    let mut games = Vec::new();
+    state.active_games.scan(|game_id, game| {
+        if game.board.outcome().is_none() {
+            games.push(LiveGame::new(game_id, game));
+        }
+    });
+    if !cluster::is_clustered(state) {
+        return Ok(games);
+    }
+
+    let owned = cluster::owned_games(state).await.map_err(|e| {
+        tracing::error!("failed to load live games: {e}");
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to load live games",
+        )
+    })?;
+    for game in owned {
+        // the in-memory copy is more recent
+        if !state.active_games.contains(&game.game_id.to_string()) {
+            games.extend(LiveGame::from_owned(game));
+        }
+    }
+    Ok(games)
+}
+
+fn sort_games(games: &mut [LiveGame], sort: LiveGameSort) {
+    match sort {
+        LiveGameSort::Recent => games.sort_by_key(|game| Reverse(game.created_at)),
+        LiveGameSort::Spectators => games.sort_by_key(|game| Reverse(game.spectators)),
+        LiveGameSort::Rating => games.sort_by_key(|game| Reverse(game.rating())),
+        LiveGameSort::Moves => games.sort_by_key(|game| Reverse(game.move_count)),
+    }
+}
+
+/// The most watched, then the highest rated, then the longest.
+fn featured(games: Vec<LiveGame>) -> Option<LiveGame> {
+    games
+        .into_iter()
+        .max_by_key(|game| (game.spectators, game.rating(), game.move_count))
+}
+
+pub async fn get_games(
+    State(state): State<AppState>,
+    Query(Sort { sort }): Query<Sort>,
+    Query(page): Query<Page>,
+) -> Result<Json<LiveGamePage>, (StatusCode, &'static str)> {
     tracing::info!("/GET games");
-    let mut s = String::new();
-    state.active_games.scan(|k, v| {
-        s = format!(
-            "{s}\n{{Game: {k}, Black: {}, White: {}}}",
-            v.black_user_id, v.white_user_id
+    let mut games = live_games(&state).await?;
+    sort_games(&mut games, sort);
+
+    let total = games.len() as u32;
+    let limit = page.limit();
+    let games: Vec<LiveGame> = games
+        .into_iter()
+        .skip(page.offset as usize)
+        .take(limit as usize)
+        .collect();
+    let next_offset = (page.offset.saturating_add(limit) < total).then(|| page.offset + limit);
+    Ok(Json(LiveGamePage {
+        games,
+        total,
+        next_offset,
+    }))
+}
+
+/// The game to show on the homepage, see `featured`.
+pub async fn get_featured_game(
+    State(state): State<AppState>,
+) -> Result<Json<LiveGame>, (StatusCode, &'static str)> {
+    tracing::info!("/GET games/featured");
+    featured(live_games(&state).await?)
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "No live games"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn live_game(game_id: &str, ratings: (Option<u32>, Option<u32>), spectators: u32) -> LiveGame {
+        LiveGame {
+            game_id: game_id.to_string(),
+            white_user_id: "white".to_string(),
+            black_user_id: "black".to_string(),
+            white_rating: ratings.0,
+            black_rating: ratings.1,
+            time_control: None,
+            move_count: 0,
+            fen: Fen::from_position(shakmaty::Chess::default(), EnPassantMode::Legal).to_string(),
+            turn: Color::White,
+            spectators,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn rating_is_the_mean_of_the_known_ratings() {
+        assert_eq!(
+            live_game("a", (Some(1500), Some(1700)), 0).rating(),
+            Some(1600)
         );
-    });
-    s
+        assert_eq!(live_game("a", (None, Some(1700)), 0).rating(), Some(1700));
+        assert_eq!(live_game("a", (None, None), 0).rating(), None);
+        assert_eq!(
+            live_game("a", (Some(u32::MAX), Some(u32::MAX)), 0).rating(),
+            Some(u32::MAX)
+        );
+    }
+
+    #[test]
+    fn unrated_games_sort_last() {
+        let mut games = vec![
+            live_game("unrated", (None, None), 0),
+            live_game("low", (Some(1200), Some(1300)), 0),
+            live_game("high", (Some(2000), None), 0),
+        ];
+        sort_games(&mut games, LiveGameSort::Rating);
+        let order: Vec<&str> = games.iter().map(|game| game.game_id.as_str()).collect();
+        assert_eq!(order, ["high", "low", "unrated"]);
+    }
+
+    #[test]
+    fn features_the_most_watched_game() {
+        let games = vec![
+            live_game("rated", (Some(2800), Some(2800)), 1),
+            live_game("watched", (None, None), 5),
+        ];
+        assert_eq!(featured(games).unwrap().game_id, "watched");
+        assert!(featured(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn other_nodes_games_are_replayed() {
+        let game = LiveGame::from_owned(OwnedGame {
+            game_id: uuid::Uuid::new_v4(),
+            white: "alice".to_string(),
+            black: "bob".to_string(),
+            pgn: "1. e4 e5 2. Nf3".to_string(),
+            time_control: Some("300+2".to_string()),
+            created_at: None,
+            spectators: 3,
+        })
+        .unwrap();
+        assert_eq!(game.move_count, 3);
+        assert_eq!(game.turn, Color::Black);
+        assert_eq!(game.spectators, 3);
+        assert_eq!(game.rating(), None);
+    }
 }
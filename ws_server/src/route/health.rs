@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode};
+
+use crate::{config::StoreKind, state::AppState};
+
+/// Liveness: the process is up and serving requests.
+pub async fn get_healthz() -> (StatusCode, &'static str) {
+    (StatusCode::OK, "OK")
+}
+
+/// Readiness: the database is reachable and the node is not draining.
+/// Nodes keeping games in memory do not need the database to serve them.
+pub async fn get_readyz(State(state): State<AppState>) -> (StatusCode, &'static str) {
+    if state.drain.is_draining() {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Draining");
+    }
+    if state.config.database.store == StoreKind::Memory {
+        return (StatusCode::OK, "OK");
+    }
+
+    let timeout = Duration::from_secs(state.config.database.health_check_timeout_secs);
+    match tokio::time::timeout(timeout, sqlx::query("SELECT 1").execute(&state.pool)).await {
+        Ok(Ok(_)) => (StatusCode::OK, "OK"),
+        Ok(Err(e)) => {
+            tracing::error!("readiness check failed: {e}");
+            (StatusCode::SERVICE_UNAVAILABLE, "Database unreachable")
+        }
+        Err(_) => {
+            tracing::error!("readiness check timed out");
+            (StatusCode::SERVICE_UNAVAILABLE, "Database unreachable")
+        }
+    }
+}
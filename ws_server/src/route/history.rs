@@ -0,0 +1,182 @@
+use std::convert::Infallible;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures_util::{stream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::{
+    history::{self, Cursor, GameFilter, GameRecord},
+    pgn,
+    state::AppState,
+};
+
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+/// Games exported ahead of a slow client before the export waits.
+const EXPORT_BUFFER: usize = 16;
+/// Games read from the database at once while exporting. The connection is
+/// returned to the pool between pages, so slow downloads do not hold it.
+const EXPORT_PAGE_SIZE: i64 = 100;
+
+/// `limit` and `offset` query parameters of paginated listings.
+#[derive(Deserialize, Debug)]
+pub struct Page {
+    pub limit: Option<u32>,
+    #[serde(default)]
+    pub offset: u32,
+}
+
+impl Page {
+    pub fn limit(&self) -> u32 {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+}
+
+#[derive(Serialize, Debug, TS)]
+#[ts(export)]
+pub struct GamePage {
+    pub games: Vec<GameRecord>,
+    /// Pass as `offset` to fetch the next page, `None` on the last page.
+    pub next_offset: Option<u32>,
+}
+
+pub async fn get_user_games(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    Query(filter): Query<GameFilter>,
+    Query(page): Query<Page>,
+) -> Result<Json<GamePage>, (StatusCode, &'static str)> {
+    tracing::info!("/GET users/{user_id}/games");
+    let limit = page.limit();
+    // one extra row tells whether there is a next page
+    let mut games: Vec<GameRecord> = history::find_user_games(
+        &state.pool,
+        &user_id,
+        &filter,
+        None,
+        Some(i64::from(limit) + 1),
+        i64::from(page.offset),
+    )
+    .try_collect()
+    .await
+    .map_err(database_error)?;
+
+    let next_offset = (games.len() > limit as usize).then(|| page.offset + limit);
+    games.truncate(limit as usize);
+    Ok(Json(GamePage { games, next_offset }))
+}
+
+pub async fn get_game(
+    State(state): State<AppState>,
+    Path(game_id): Path<String>,
+) -> Result<Json<GameRecord>, (StatusCode, &'static str)> {
+    tracing::info!("/GET games/{game_id}");
+    find_game(&state, &game_id).await.map(Json)
+}
+
+pub async fn get_game_pgn(
+    State(state): State<AppState>,
+    Path(game_id): Path<String>,
+) -> Result<Response, (StatusCode, &'static str)> {
+    tracing::info!("/GET games/{game_id}/pgn");
+    let game = find_game(&state, &game_id).await?;
+    Ok(pgn_response(&game.game_id, pgn::export(&game)))
+}
+
+/// Every game of the user matching the filter as one PGN file, streamed a
+/// page of games at a time.
+pub async fn get_user_games_pgn(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    Query(filter): Query<GameFilter>,
+) -> Response {
+    tracing::info!("/GET users/{user_id}/games/pgn");
+    let (tx, rx) = mpsc::channel::<Bytes>(EXPORT_BUFFER);
+    let file_name = user_id.clone();
+    tokio::spawn(async move {
+        let mut after = None;
+        loop {
+            let games: Vec<GameRecord> = match history::find_user_games(
+                &state.pool,
+                &user_id,
+                &filter,
+                after,
+                Some(EXPORT_PAGE_SIZE),
+                0,
+            )
+            .try_collect()
+            .await
+            {
+                Ok(games) => games,
+                Err(e) => {
+                    // the status line is already sent, the client sees a truncated file
+                    tracing::error!("failed to export games of {user_id}: {e}");
+                    return;
+                }
+            };
+            for game in &games {
+                if tx.send(Bytes::from(pgn::export(game))).await.is_err() {
+                    // client went away
+                    return;
+                }
+            }
+            if games.len() < EXPORT_PAGE_SIZE as usize {
+                return;
+            }
+            after = games.last().map(Cursor::from);
+        }
+    });
+
+    let body = stream::unfold(rx, |mut rx| async move {
+        rx.recv()
+            .await
+            .map(|chunk| (Ok::<_, Infallible>(chunk), rx))
+    });
+    pgn_response(&file_name, Body::from_stream(body))
+}
+
+pub(super) async fn find_game(
+    state: &AppState,
+    game_id: &str,
+) -> Result<GameRecord, (StatusCode, &'static str)> {
+    let game_id =
+        Uuid::parse_str(game_id).map_err(|_| (StatusCode::NOT_FOUND, "Game not found"))?;
+    history::find_game(&state.pool, game_id)
+        .await
+        .map_err(database_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Game not found"))
+}
+
+fn pgn_response(file_name: &str, body: impl Into<Body>) -> Response {
+    let file_name: String = file_name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+        .collect();
+    (
+        [
+            (header::CONTENT_TYPE, pgn::CONTENT_TYPE.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}.pgn\""),
+            ),
+        ],
+        body.into(),
+    )
+        .into_response()
+}
+
+fn database_error(e: sqlx::Error) -> (StatusCode, &'static str) {
+    tracing::error!("failed to load games: {e}");
+    (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load games")
+}
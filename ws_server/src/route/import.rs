@@ -0,0 +1,50 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::{
+    history,
+    pgn::import::{self, PgnReport},
+    state::AppState,
+};
+
+#[derive(Serialize, Debug, TS)]
+#[ts(export)]
+pub struct ImportedGames {
+    pub game_ids: Vec<String>,
+}
+
+/// Parses and validates a PGN file without storing it.
+pub async fn post_pgn_validate(State(state): State<AppState>, body: String) -> Json<PgnReport> {
+    tracing::info!("/POST pgn/validate");
+    Json(import::parse(&body, state.config.import.max_games))
+}
+
+/// Stores every game of a PGN file, or none if any game is invalid, in which
+/// case the report is returned with `422 Unprocessable Entity`.
+pub async fn post_games_import(State(state): State<AppState>, body: String) -> Response {
+    tracing::info!("/POST games/import");
+    let report = import::parse(&body, state.config.import.max_games);
+    if !report.errors.is_empty() {
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(report)).into_response();
+    }
+    if report.games.is_empty() {
+        return (StatusCode::BAD_REQUEST, "No games found").into_response();
+    }
+
+    match history::insert_games(&state.pool, &report.games).await {
+        Ok(game_ids) => Json(ImportedGames {
+            game_ids: game_ids.iter().map(ToString::to_string).collect(),
+        })
+        .into_response(),
+        Err(e) => {
+            tracing::error!("failed to import games: {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to store games").into_response()
+        }
+    }
+}
@@ -1,30 +1,25 @@
 use axum::{extract::State, http::StatusCode, Json};
-use serde::{Deserialize, Serialize};
-use ts_rs::TS;
+use chessclouds_protocol::game::InitBody;
 
 use crate::state::{ActiveGame, AppState};
 
-#[derive(Deserialize, Serialize, Debug, TS)]
-#[ts(export)]
-pub struct InitBody {
-    pub game_id: String,
-    pub white_user_id: String,
-    pub black_user_id: String,
-}
-
 pub async fn post_init(
     State(state): State<AppState>,
     Json(body): Json<InitBody>,
 ) -> (StatusCode, &'static str) {
     tracing::info!("/POST init");
-    if state
-        .active_games
-        .insert(
-            body.game_id,
-            ActiveGame::new(body.white_user_id, body.black_user_id),
-        )
-        .is_err()
-    {
+    if state.drain.is_draining() {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Server is restarting");
+    }
+    let mut game = ActiveGame::new(
+        body.white_user_id,
+        body.black_user_id,
+        body.settings.unwrap_or_default(),
+        &state.config.game,
+    );
+    game.white_rating = body.white_rating;
+    game.black_rating = body.black_rating;
+    if state.active_games.insert(body.game_id, game).is_err() {
         tracing::error!("Game already exists");
         return (StatusCode::INTERNAL_SERVER_ERROR, "Game already exists");
     }
@@ -0,0 +1,14 @@
+use axum::{extract::State, http::header, response::IntoResponse};
+
+use crate::state::AppState;
+
+pub async fn get_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    state
+        .metrics
+        .active_games
+        .set(state.active_games.len() as i64);
+    (
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        state.metrics.encode(),
+    )
+}
@@ -0,0 +1,264 @@
+use std::net::SocketAddr;
+
+use axum::{
+    body::Bytes,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chessclouds_rate_limit::client_ip;
+use serde::Deserialize;
+use shakmaty::{fen::Fen, CastlingMode, Chess, Color, Square};
+
+use crate::{
+    db::GameStatus,
+    render::{self, BoardImage, GifKey},
+    route::history::find_game,
+    state::AppState,
+};
+
+const DEFAULT_SIZE: u32 = 400;
+const MAX_SIZE: u32 = 1024;
+const DEFAULT_GIF_SIZE: u32 = 320;
+const DEFAULT_DELAY_MS: u32 = 800;
+const FINISHED_CACHE_CONTROL: &str = "public, max-age=86400";
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Svg,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    #[default]
+    White,
+    Black,
+}
+
+impl From<Orientation> for Color {
+    fn from(orientation: Orientation) -> Self {
+        match orientation {
+            Orientation::White => Color::White,
+            Orientation::Black => Color::Black,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct PositionQuery {
+    /// Defaults to the starting position.
+    fen: Option<String>,
+    /// UCI squares, e.g. `e2e4`.
+    last_move: Option<String>,
+    /// Comma separated UCI squares, e.g. `e2e4,g1f3`.
+    arrows: Option<String>,
+    #[serde(default)]
+    orientation: Orientation,
+    #[serde(default)]
+    format: ImageFormat,
+    size: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct GameImageQuery {
+    /// Number of moves played before the position shown, the final position
+    /// by default.
+    ply: Option<usize>,
+    #[serde(default)]
+    orientation: Orientation,
+    #[serde(default)]
+    format: ImageFormat,
+    size: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct GameGifQuery {
+    #[serde(default)]
+    orientation: Orientation,
+    size: Option<u32>,
+    delay_ms: Option<u32>,
+}
+
+fn squares(uci: &str) -> Option<(Square, Square)> {
+    if uci.len() < 4 || !uci.is_char_boundary(2) || !uci.is_char_boundary(4) {
+        return None;
+    }
+    // the promotion piece, if any, is not drawn
+    let from = uci[..2].parse().ok()?;
+    let to = uci[2..4].parse().ok()?;
+    Some((from, to))
+}
+
+/// Renders an arbitrary position, e.g. for an analysis link preview.
+pub async fn get_render(
+    Query(query): Query<PositionQuery>,
+) -> Result<Response, (StatusCode, &'static str)> {
+    tracing::info!("/GET render");
+    let orientation = query.orientation.into();
+    let mut image = match &query.fen {
+        None => BoardImage::new(&Chess::default(), orientation),
+        Some(fen) => {
+            let fen: Fen = fen
+                .parse()
+                .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid FEN"))?;
+            match fen.clone().into_position::<Chess>(CastlingMode::Standard) {
+                Ok(position) => BoardImage::new(&position, orientation),
+                // still draw positions that cannot occur in a game
+                Err(_) => BoardImage {
+                    board: fen.0.board,
+                    orientation,
+                    last_move: None,
+                    check: None,
+                    arrows: Vec::new(),
+                },
+            }
+        }
+    };
+    if let Some(last_move) = &query.last_move {
+        image.last_move =
+            Some(squares(last_move).ok_or((StatusCode::BAD_REQUEST, "Invalid last_move"))?);
+    }
+    for arrow in query.arrows.iter().flat_map(|arrows| arrows.split(',')) {
+        image
+            .arrows
+            .push(squares(arrow).ok_or((StatusCode::BAD_REQUEST, "Invalid arrow"))?);
+    }
+
+    let size = query.size.unwrap_or(DEFAULT_SIZE).clamp(1, MAX_SIZE);
+    // the image only depends on the query
+    image_response(image, query.format, size, "public, max-age=86400").await
+}
+
+/// A position of a stored game, the final one by default, for social cards.
+pub async fn get_game_image(
+    State(state): State<AppState>,
+    Path(game_id): Path<String>,
+    Query(query): Query<GameImageQuery>,
+) -> Result<Response, (StatusCode, &'static str)> {
+    tracing::info!("/GET games/{game_id}/image");
+    let game = find_game(&state, &game_id).await?;
+    let mut frames = render::game_frames(&game.moves, query.orientation.into()).ok_or((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Stored moves are not legal",
+    ))?;
+    let ply = query.ply.unwrap_or(game.moves.len());
+    if ply >= frames.len() {
+        return Err((StatusCode::BAD_REQUEST, "ply is past the end of the game"));
+    }
+    let image = frames.swap_remove(ply);
+
+    let size = query.size.unwrap_or(DEFAULT_SIZE).clamp(1, MAX_SIZE);
+    image_response(image, query.format, size, cache_control(game.status)).await
+}
+
+/// The whole game as an animated GIF, see `RenderConfig` for its limits.
+pub async fn get_game_gif(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(game_id): Path<String>,
+    Query(query): Query<GameGifQuery>,
+) -> Result<Response, (StatusCode, &'static str)> {
+    tracing::info!("/GET games/{game_id}/gif");
+    let limits = &state.config.render;
+    let key = GifKey {
+        game_id: game_id.clone(),
+        orientation: query.orientation.into(),
+        size: query
+            .size
+            .unwrap_or(DEFAULT_GIF_SIZE)
+            .clamp(1, limits.gif_max_size),
+        delay_ms: query.delay_ms.unwrap_or(DEFAULT_DELAY_MS),
+    };
+    if let Some(gif) = state.gif_cache.get(&key) {
+        // only finished games are cached
+        return Ok(gif_response(gif, FINISHED_CACHE_CONTROL));
+    }
+
+    let forwarded_for = headers
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok());
+    let ip = client_ip(peer, forwarded_for, limits.trust_forwarded_for);
+    if let Err(retry_after) = state.gif_limiter.try_acquire(ip) {
+        tracing::warn!("{ip} exceeded the GIF rate limit");
+        let retry_after = retry_after.as_secs_f64().ceil() as u64;
+        return Ok((
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, retry_after.to_string())],
+            "Too many GIFs requested",
+        )
+            .into_response());
+    }
+
+    let game = find_game(&state, &game_id).await?;
+    let frames = render::game_frames(&game.moves, key.orientation).ok_or((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Stored moves are not legal",
+    ))?;
+    let frames = render::sample_frames(frames, limits.gif_max_frames);
+    let (size, delay_ms) = (key.size, key.delay_ms);
+    let gif = tokio::task::spawn_blocking(move || render::gif(&frames, size, delay_ms))
+        .await
+        .expect("rendering should not panic")
+        .map(Bytes::from)
+        .map_err(render_error)?;
+    if game.status != GameStatus::OnGoing {
+        state.gif_cache.insert(key, gif.clone());
+    }
+    Ok(gif_response(gif, cache_control(game.status)))
+}
+
+fn gif_response(gif: Bytes, cache_control: &'static str) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "image/gif"),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        gif,
+    )
+        .into_response()
+}
+
+/// Finished games never change, ongoing ones do with every move.
+fn cache_control(status: GameStatus) -> &'static str {
+    if status == GameStatus::OnGoing {
+        "no-cache"
+    } else {
+        FINISHED_CACHE_CONTROL
+    }
+}
+
+async fn image_response(
+    image: BoardImage,
+    format: ImageFormat,
+    size: u32,
+    cache_control: &'static str,
+) -> Result<Response, (StatusCode, &'static str)> {
+    let (content_type, body) = match format {
+        ImageFormat::Svg => ("image/svg+xml", render::svg(&image, size).into_bytes()),
+        ImageFormat::Png => {
+            let png = tokio::task::spawn_blocking(move || render::png(&image, size))
+                .await
+                .expect("rendering should not panic")
+                .map_err(render_error)?;
+            ("image/png", png)
+        }
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+fn render_error(e: String) -> (StatusCode, &'static str) {
+    tracing::error!("rendering failed: {e}");
+    (StatusCode::INTERNAL_SERVER_ERROR, "Rendering failed")
+}
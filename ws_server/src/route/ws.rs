@@ -1,23 +1,41 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use axum::{
+    body::Bytes,
     extract::{
         ws::{Message, Utf8Bytes, WebSocket},
         State, WebSocketUpgrade,
     },
     response::Response,
 };
-use shakmaty::{san::San, Color, Position};
-use tokio::sync::{
-    broadcast,
-    mpsc::{self, Receiver, Sender},
+use chessclouds_protocol::{
+    game::{
+        ChatChannel, ClientMessage, Error, ErrorCode, GameSettings, GameSnapshot, Negotiated,
+        SequencedMessage, ServerMessage,
+    },
+    MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+use serde::Serialize;
+use shakmaty::{Color, Position};
+use tokio::{
+    sync::{
+        broadcast,
+        mpsc::{self, Receiver, Sender},
+        oneshot,
+    },
+    time::{Instant, MissedTickBehavior},
 };
+use tracing::{Instrument, Span};
 use uuid::Uuid;
 
 use crate::{
-    message::{ClientMessage, Error, ServerMessage},
-    state::{ActiveGame, ActiveGameMap, AppState},
-    DEFERRED_CLEAN_UP_DURATION, MAX_CHANNEL_CAPACITY,
+    chat::{store_chat, FilterVerdict},
+    cluster::{claim_game, release_game, Ownership},
+    rate_limit::RateLimiter,
+    state::{ActiveGame, ActiveGameMap, AppState, Session, StoreWriter},
 };
 use futures_util::{
     sink::SinkExt,
@@ -28,17 +46,85 @@ type Result<T> = std::result::Result<T, Error>;
 
 struct Connection {
     pub game_id: String,
-    pub color: Color,
-    pub tx_broadcast: broadcast::Sender<ServerMessage>,
+    pub user_id: String,
+    /// `None` for spectators.
+    pub color: Option<Color>,
+}
+
+impl Connection {
+    fn role(&self) -> &'static str {
+        match self.color {
+            Some(Color::White) => "white",
+            Some(Color::Black) => "black",
+            None => "spectator",
+        }
+    }
+
+    /// The seat this connection plays for, spectators cannot act on the game.
+    fn seat(&self) -> Result<Color> {
+        self.color
+            .ok_or_else(|| Error::new(ErrorCode::SpectatorNotAllowed, "spectators can only chat"))
+    }
+
+    fn chat_channel(&self) -> ChatChannel {
+        match self.color {
+            Some(_) => ChatChannel::Players,
+            None => ChatChannel::Spectators,
+        }
+    }
+
+    /// Whether a game message should reach this connection. Each chat channel
+    /// is only visible to its own audience, minus muted users.
+    fn should_receive(&self, state: &ActiveGameMap, msg: &ServerMessage) -> bool {
+        match msg {
+            ServerMessage::Chat { from, channel, .. } => {
+                *channel == self.chat_channel()
+                    && !state
+                        .read(&self.game_id, |_, v| v.is_muted(&self.user_id, from))
+                        .unwrap_or(false)
+            }
+            _ => true,
+        }
+    }
+}
+
+enum AuthError {
+    Rejected(Error),
+    /// The game is owned by another node.
+    Redirect(String),
+}
+
+impl From<Error> for AuthError {
+    fn from(err: Error) -> Self {
+        AuthError::Rejected(err)
+    }
+}
+
+/// Why the write task should close the socket on the server's initiative.
+struct CloseSignals {
+    /// The seat was taken over by a newer connection.
+    replaced: oneshot::Receiver<()>,
+    /// The read task ended the connection with this error.
+    error: oneshot::Receiver<Error>,
+}
+
+/// What a freshly authenticated socket is sent before the live stream.
+enum CatchUp {
+    Replay(Vec<SequencedMessage>),
+    Snapshot(GameSnapshot),
 }
 
+/// How long a socket closed by the server gets to deliver the reason.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
 pub async fn ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+    ws.max_message_size(state.config.connection.max_message_bytes)
+        .on_upgrade(|socket| handle_socket(socket, state))
 }
 
 async fn send_msg(
     writer: &mut SplitSink<WebSocket, Message>,
-    msg: &ServerMessage,
+    msg: &impl Serialize,
 ) -> std::result::Result<(), axum::Error> {
     writer
         .send(Message::Text(Utf8Bytes::from(
@@ -47,187 +133,372 @@ async fn send_msg(
         .await
 }
 
+/// Everything logged for a connection, including by its tasks, carries the
+/// game, user, color and protocol version once they are known.
+#[tracing::instrument(
+    name = "connection",
+    skip_all,
+    fields(game_id, user_id, color, protocol_version)
+)]
 async fn handle_socket(socket: WebSocket, state: AppState) {
     tracing::info!("socket connected");
 
     let (mut writer, mut reader) = socket.split();
-    let (tx_local, rx_local) = mpsc::channel(MAX_CHANNEL_CAPACITY);
-
-    let connection = match auth_socket(&mut reader, &state).await {
-        Ok(info) => {
-            let msg = ServerMessage::AuthSuccess;
+    let (tx_local, rx_local) = mpsc::channel(state.config.game.channel_capacity);
+
+    let auth_timeout = Duration::from_secs(state.config.connection.auth_timeout_secs);
+    let auth = match tokio::time::timeout(auth_timeout, auth_socket(&mut reader, &state)).await {
+        Ok(auth) => auth,
+        Err(_) => Err(Error::new(
+            ErrorCode::AuthTimeout,
+            "no Auth or Spectate message was received in time",
+        )
+        .into()),
+    };
+    let (connection, last_seq) = match auth {
+        Ok((info, last_seq, protocol_version, negotiated)) => {
+            let msg =
+                ServerMessage::AuthSuccess(negotiated.then_some(Negotiated { protocol_version }));
             let _ = send_msg(&mut writer, &msg).await;
-            tracing::info!("auth success: {} {}", info.game_id, info.color);
-            Arc::new(info)
+            Span::current()
+                .record("color", info.role())
+                .record("protocol_version", protocol_version);
+            tracing::info!("auth success");
+            state
+                .metrics
+                .connected_sockets
+                .with_label_values(&[info.role()])
+                .inc();
+            (Arc::new(info), last_seq)
         }
-        Err(err) => {
+        Err(AuthError::Rejected(err)) => {
+            state
+                .metrics
+                .auth_failures
+                .with_label_values(&[&format!("{:?}", err.code)])
+                .inc();
+            tracing::error!("auth failed: {err}");
             let msg = ServerMessage::Error(err);
             let _ = send_msg(&mut writer, &msg).await;
-            tracing::error!("auth failed");
+            let _ = writer.close().await;
+            return;
+        }
+        Err(AuthError::Redirect(url)) => {
+            let _ = send_msg(&mut writer, &ServerMessage::Redirect(url)).await;
+            let _ = writer.close().await;
             return;
         }
     };
 
+    let session_id = Uuid::new_v4();
+    let (tx_replaced, rx_replaced) = oneshot::channel();
+    let (tx_close, rx_close) = oneshot::channel();
+
+    // subscribe while holding the entry so no broadcast falls between the
+    // catch-up messages and the live stream
+    let joined = match state.active_games.get(&connection.game_id) {
+        // cleaned up since the socket authenticated
+        None => Err(ServerMessage::Error(game_not_found(&connection.game_id))),
+        Some(game) if game.board.outcome().is_some() => {
+            // seating anyone would only keep the game from being cleaned up
+            tracing::info!("game is over, closing socket after the snapshot");
+            Err(ServerMessage::GameSnapshot(game.snapshot(connection.color)))
+        }
+        Some(mut game) => {
+            let session = Session {
+                id: session_id,
+                replaced: tx_replaced,
+                tx_local: tx_local.clone(),
+            };
+            match connection.color {
+                Some(color) => game.connect(color, session),
+                None => game.connect_spectator(session),
+            }
+            let catch_up = match last_seq.and_then(|seq| game.replay_since(seq)) {
+                Some(missed) => CatchUp::Replay(missed),
+                None => CatchUp::Snapshot(game.snapshot(connection.color)),
+            };
+            Ok((game.tx_broadcast.subscribe(), catch_up))
+        }
+    };
+    let (rx_broadcast, catch_up) = match joined {
+        Ok(joined) => joined,
+        Err(msg) => {
+            let _ = send_msg(&mut writer, &msg).await;
+            let _ = writer.close().await;
+            state
+                .metrics
+                .connected_sockets
+                .with_label_values(&[connection.role()])
+                .dec();
+            return;
+        }
+    };
+    // a spectator may have loaded a game neither player is connected to
+    schedule_clean_up(&state, &connection.game_id);
+
+    let mut read_task = tokio::spawn(
+        handle_socket_read(reader, state.clone(), connection.clone(), tx_local).in_current_span(),
+    );
+    let mut write_task = tokio::spawn(
+        handle_socket_write(
+            writer,
+            state.clone(),
+            connection.clone(),
+            catch_up,
+            rx_broadcast,
+            rx_local,
+            CloseSignals {
+                replaced: rx_replaced,
+                error: rx_close,
+            },
+        )
+        .in_current_span(),
+    );
+
+    tokio::select! {
+        closed = &mut read_task => match closed {
+            // let the writer deliver why the server closes the socket
+            Ok(Some(err)) => {
+                let _ = tx_close.send(err);
+                let _ = tokio::time::timeout(CLOSE_TIMEOUT, &mut write_task).await;
+                write_task.abort();
+            }
+            _ => write_task.abort(),
+        },
+        _ = &mut write_task => read_task.abort()
+    }
+
+    tracing::info!("socket closing");
     state
-        .active_games
-        .get(&connection.game_id)
-        .expect("game should exist")
-        .connect(connection.color);
+        .metrics
+        .connected_sockets
+        .with_label_values(&[connection.role()])
+        .dec();
+
+    // the game may already have been cleaned up underneath a spectator
+    if let Some(mut game) = state.active_games.get(&connection.game_id) {
+        match connection.color {
+            Some(color) => game.disconnect(color, session_id),
+            None => game.disconnect_spectator(session_id),
+        }
+    }
+    schedule_clean_up(&state, &connection.game_id);
+}
 
-    let move_history = state
-        .active_games
-        .read(&connection.game_id, |_, v| v.moves.clone())
-        .expect("game should exist");
-    if send_msg(&mut writer, &ServerMessage::MoveHistory(move_history))
-        .await
-        .is_err()
+/// Removes the game once nobody played it for `game.clean_up_after_secs`,
+/// unless a player connects in the meantime. Spectators do not keep a game
+/// alive. Does nothing while a player is connected or a clean up is pending.
+fn schedule_clean_up(state: &AppState, game_id: &str) {
+    let Some(mut game) = state.active_games.get(game_id) else {
+        return;
+    };
+    if game.is_connected(Color::Black)
+        || game.is_connected(Color::White)
+        || game.clean_up_task.is_some()
     {
         return;
     }
 
-    let rx_broadcast = connection.tx_broadcast.subscribe();
+    let cloned_state = state.clone();
+    let cloned_game_id = game_id.to_owned();
 
-    let mut read_task = tokio::spawn(handle_socket_read(
-        reader,
-        state.active_games.clone(),
-        connection.clone(),
-        tx_local,
-    ));
-    let mut write_task = tokio::spawn(handle_socket_write(writer, rx_broadcast, rx_local));
+    let clean_up = async move {
+        tracing::info!("initiating deferred clean up for {}", cloned_game_id);
 
-    tokio::select! {
-        _ = &mut read_task => write_task.abort(),
-        _ = &mut write_task => read_task.abort()
-    }
-
-    tracing::info!("socket closing {} {}", connection.game_id, connection.color);
+        tokio::time::sleep(Duration::from_secs(
+            cloned_state.config.game.clean_up_after_secs,
+        ))
+        .await;
 
-    state
-        .active_games
-        .get(&connection.game_id)
-        .expect("game should exist")
-        .disconnect(connection.color);
+        tracing::info!("cleaning up state for {}", cloned_game_id);
 
-    if state
-        .active_games
-        .read(&connection.game_id, |_, v| {
-            !v.black_connected && !v.white_connected
-        })
-        .expect("game should exist")
-    {
-        // both players disconnect, initiate deferred clean up
-        if state
-            .active_games
-            .read(&connection.game_id, |_, v| v.clean_up_task.is_none())
-            .expect("game should exist")
-        {
-            let cloned_state = state.clone();
-            let cloned_game_id = connection.game_id.clone();
-
-            let join_handle = tokio::spawn(async move {
-                tracing::info!("initiating deferred clean up for {}", cloned_game_id);
-
-                tokio::time::sleep(Duration::from_secs(DEFERRED_CLEAN_UP_DURATION)).await;
-
-                tracing::info!("cleaning up state for {}", cloned_game_id);
-
-                // Only updating the database status to 'Abort' if current status is 'On Going'
-                if let Err(e) = sqlx::query!(
-                    "UPDATE GameState SET Status = 'Abort' WHERE GameID = $1 AND Status = 'On Going'",
-                    Uuid::parse_str(&cloned_game_id).expect("game_id should be a valid UUID")
-                )
-                .execute(&cloned_state.pool)
-                .await
-                {
-                    tracing::error!("updating game status to Abort failed: {e}");
-                    return;
-                }
+        // games created through `/init` are not in the store
+        if let Ok(game_uuid) = Uuid::parse_str(&cloned_game_id) {
+            // a finished game must be stored as such before aborting it
+            let flushed = cloned_state
+                .active_games
+                .read(&cloned_game_id, |_, game| {
+                    game.store_writer.as_ref().map(StoreWriter::flushed)
+                })
+                .flatten();
+            if let Some(flushed) = flushed {
+                flushed.await;
+            }
 
-                // Remove game from active_games map
-                cloned_state.active_games.remove(&cloned_game_id);
+            // Only aborting games that are still going on
+            if let Err(e) = cloned_state.store.abort_game(game_uuid).await {
+                tracing::error!("updating game status to Abort failed: {e}");
+                return;
+            }
 
-                tracing::info!("Removed game {} from active_games map", cloned_game_id);
-            });
+            // Remove game from active_games map
+            cloned_state.active_games.remove(&cloned_game_id);
 
-            state
-                .active_games
-                .get(&connection.game_id)
-                .expect("game should exist")
-                .clean_up_task = Some(join_handle);
+            if let Err(e) = release_game(&cloned_state, game_uuid).await {
+                tracing::error!("releasing ownership of {cloned_game_id} failed: {e}");
+            }
+        } else {
+            cloned_state.active_games.remove(&cloned_game_id);
         }
-    }
+
+        tracing::info!("Removed game {} from active_games map", cloned_game_id);
+    };
+    // not a child of the connection span, which would stay open until the
+    // clean up is done
+    let span = tracing::info_span!(parent: None, "clean_up", game_id = %game_id);
+    game.clean_up_task = Some(tokio::spawn(clean_up.instrument(span)));
 }
 
 fn get_connection_from_map(
     state: &AppState,
     game_id: &str,
     user_id: &str,
+    spectate: bool,
 ) -> Option<Result<Connection>> {
     state.active_games.read(game_id, |_, v| {
-        if v.black_user_id.as_str() == user_id && !v.black_connected {
-            return Ok(Connection {
-                game_id: game_id.to_owned(),
-                color: Color::Black,
-                tx_broadcast: v.tx_broadcast.clone(),
-            });
-        } else if v.white_user_id.as_str() == user_id && !v.white_connected {
-            return Ok(Connection {
-                game_id: game_id.to_owned(),
-                color: Color::White,
-                tx_broadcast: v.tx_broadcast.clone(),
-            });
-        }
-        tracing::error!("connection not found in appstate");
-        Err(Error::Unauthorized)
+        let color = if spectate {
+            None
+        } else if v.black_user_id.as_str() == user_id {
+            Some(Color::Black)
+        } else if v.white_user_id.as_str() == user_id {
+            Some(Color::White)
+        } else {
+            tracing::error!("connection not found in appstate");
+            return Err(
+                Error::new(ErrorCode::NotAPlayer, "you are not a player in this game")
+                    .with_input(user_id),
+            );
+        };
+        Ok(Connection {
+            game_id: game_id.to_owned(),
+            user_id: user_id.to_owned(),
+            color,
+        })
     })
 }
 
-async fn auth_socket(socket: &mut SplitStream<WebSocket>, state: &AppState) -> Result<Connection> {
+/// Returns the connection, the client's `last_seq`, the protocol version to
+/// speak and whether the client offered one.
+async fn auth_socket(
+    socket: &mut SplitStream<WebSocket>,
+    state: &AppState,
+) -> std::result::Result<(Connection, Option<u64>, u32, bool), AuthError> {
     while let Some(Ok(Message::Text(text))) = socket.next().await {
         let client_msg: ClientMessage = match serde_json::from_str(text.as_str()) {
             Ok(msg) => msg,
-            Err(_) => {
+            Err(e) => {
                 tracing::error!("auth deserialization failed");
-                return Err(Error::Deserialization);
+                return Err(malformed_message(e).into());
             }
         };
 
-        if let ClientMessage::Auth { game_id, user_id } = client_msg {
-            // find active game in server's HashMap first
-            match get_connection_from_map(state, &game_id, &user_id) {
-                Some(conn) => return conn,
-
-                // if not found, find from DB and add to HashMap
-                None => {
-                    let game_uuid = match Uuid::parse_str(&game_id) {
-                        Ok(uuid) => uuid,
-                        Err(_) => {
-                            tracing::error!("Failed to parse GameID UUID");
-                            return Err(Error::Unauthorized);
-                        }
-                    };
+        let (game_id, user_id, last_seq, client_version, spectate) = match client_msg {
+            ClientMessage::Auth {
+                game_id,
+                user_id,
+                last_seq,
+                protocol_version,
+            } => (game_id, user_id, last_seq, protocol_version, false),
+            ClientMessage::Spectate {
+                game_id,
+                user_id,
+                last_seq,
+                protocol_version,
+            } => (game_id, user_id, last_seq, protocol_version, true),
+            _ => continue,
+        };
+        Span::current()
+            .record("game_id", game_id.as_str())
+            .record("user_id", user_id.as_str());
 
-                    let row = sqlx::query!(
-                        r#"SELECT GameID, Black, White FROM GameState WHERE GameId = $1 AND Status = 'On Going'"#,
-                        game_uuid
-                    )
-                    .fetch_one(&state.pool)
-                    .await;
+        let Some(protocol_version) = chessclouds_protocol::negotiate(client_version) else {
+            tracing::error!("unsupported protocol version {client_version:?}");
+            return Err(unsupported_protocol_version(client_version).into());
+        };
+        let negotiated = client_version.is_some();
+
+        // find active game in server's HashMap first
+        match get_connection_from_map(state, &game_id, &user_id, spectate) {
+            Some(conn) => {
+                return conn
+                    .map(|conn| (conn, last_seq, protocol_version, negotiated))
+                    .map_err(AuthError::from)
+            }
 
-                    match row {
-                        Ok(row) => {
-                            let _ = state
-                                .active_games
-                                .insert(game_id.clone(), ActiveGame::new(row.white, row.black));
+            // if not found, find from DB and add to HashMap
+            None => {
+                if state.drain.is_draining() {
+                    tracing::error!("refusing to load game {game_id} while draining");
+                    return Err(server_restarting().into());
+                }
 
-                            tracing::info!("adding to app state");
-                            return get_connection_from_map(state, &game_id, &user_id)
-                                .expect("game should exist");
+                let game_uuid = match Uuid::parse_str(&game_id) {
+                    Ok(uuid) => uuid,
+                    Err(_) => {
+                        tracing::error!("Failed to parse GameID UUID");
+                        return Err(game_not_found(&game_id).into());
+                    }
+                };
+
+                match state.store.load_game(game_uuid).await {
+                    Ok(Some(stored)) => {
+                        match claim_game(state, game_uuid).await {
+                            Ok(Ownership::Owned) => {}
+                            Ok(Ownership::Redirect(url)) => {
+                                tracing::info!("game {game_id} is owned by {url}");
+                                return Err(AuthError::Redirect(url));
+                            }
+                            Err(e) => {
+                                tracing::error!("claiming game {game_id} failed: {e}");
+                                return Err(Error::new(
+                                    ErrorCode::GameUnavailable,
+                                    "the game could not be loaded, try again",
+                                )
+                                .into());
+                            }
+                        }
+
+                        // the game may have been played on another node before
+                        let settings = GameSettings {
+                            allow_takebacks: stored.allow_takebacks,
+                            time_control: stored.time_control,
+                        };
+                        let mut game = ActiveGame::new(
+                            stored.white,
+                            stored.black,
+                            settings,
+                            &state.config.game,
+                        );
+                        if let Some(created_at) = stored.created_at {
+                            game.created_at = created_at;
                         }
-                        Err(_) => {
-                            tracing::error!("auth row not found in DB");
-                            return Err(Error::Unauthorized);
+                        game.takebacks = stored.takebacks;
+                        if let Err(err) = game.restore(stored.moves) {
+                            tracing::error!("persisted moves of {game_id} are not legal");
+                            return Err(err.into());
                         }
+                        game.store_writer =
+                            Some(StoreWriter::spawn(state.store.clone(), game_uuid));
+                        let _ = state.active_games.insert(game_id.clone(), game);
+
+                        tracing::info!("adding to app state");
+                        return get_connection_from_map(state, &game_id, &user_id, spectate)
+                            .expect("game should exist")
+                            .map(|conn| (conn, last_seq, protocol_version, negotiated))
+                            .map_err(AuthError::from);
+                    }
+                    Ok(None) => {
+                        tracing::error!("game {game_id} not found in the store");
+                        return Err(game_not_found(&game_id).into());
+                    }
+                    Err(e) => {
+                        tracing::error!("loading game {game_id} failed: {e}");
+                        return Err(Error::new(
+                            ErrorCode::GameUnavailable,
+                            "the game could not be loaded, try again",
+                        )
+                        .into());
                     }
                 }
             }
@@ -235,124 +506,331 @@ async fn auth_socket(socket: &mut SplitStream<WebSocket>, state: &AppState) -> R
     }
 
     tracing::error!("auth failed outside while let");
-    Err(Error::Unauthorized)
+    Err(Error::new(
+        ErrorCode::NotAuthenticated,
+        "the socket closed before sending Auth or Spectate",
+    )
+    .into())
 }
 
+fn malformed_message(err: serde_json::Error) -> Error {
+    Error::new(ErrorCode::MalformedMessage, err.to_string())
+}
+
+fn game_not_found(game_id: &str) -> Error {
+    Error::new(
+        ErrorCode::GameNotFound,
+        "there is no ongoing game with this id",
+    )
+    .with_input(game_id)
+}
+
+fn unsupported_protocol_version(client_version: Option<u32>) -> Error {
+    let mut err = Error::new(
+        ErrorCode::UnsupportedProtocolVersion,
+        "the client is too old for this server, update it",
+    )
+    .with_protocol_versions(MIN_PROTOCOL_VERSION, PROTOCOL_VERSION);
+    if let Some(version) = client_version {
+        err = err.with_input(version.to_string());
+    }
+    err
+}
+
+fn server_restarting() -> Error {
+    Error::new(
+        ErrorCode::ServerRestarting,
+        "the server is restarting, reconnect shortly",
+    )
+}
+
+/// Handles client messages until the socket closes. Returns the error to
+/// close the socket with when the server ends the connection itself.
 async fn handle_socket_read(
     mut reader: SplitStream<WebSocket>,
-    state: ActiveGameMap,
+    state: AppState,
     connection: Arc<Connection>,
-    #[allow(unused_variables)] tx_local: Sender<ServerMessage>,
-) {
-    while let Some(Ok(Message::Text(text))) = reader.next().await {
+    tx_local: Sender<ServerMessage>,
+) -> Option<Error> {
+    let limits = &state.config.connection;
+    let rate_window = Duration::from_secs(limits.rate_window_secs);
+    let mut limiter = RateLimiter::new(limits.rate_limit, rate_window);
+    // violations are counted per window, starting at the first one
+    let mut violations = 0;
+    let mut violations_since = Instant::now();
+    let mut chat_limiter = RateLimiter::new(
+        state.config.chat.rate_limit,
+        Duration::from_secs(state.config.chat.rate_window_secs),
+    );
+
+    let idle_timeout = Duration::from_secs(limits.idle_timeout_secs);
+
+    loop {
+        let text = match tokio::time::timeout(idle_timeout, reader.next()).await {
+            Ok(Some(Ok(Message::Text(text)))) => text,
+            // answers to the server's pings only count as a sign of life
+            Ok(Some(Ok(Message::Ping(_) | Message::Pong(_)))) => continue,
+            Ok(_) => break,
+            Err(_) => {
+                tracing::warn!(
+                    "nothing received for {}s, closing dead connection",
+                    limits.idle_timeout_secs
+                );
+                state.metrics.timed_out_connections.inc();
+                break;
+            }
+        };
+
+        if !limiter.try_acquire() {
+            if violations_since.elapsed() > rate_window {
+                violations = 0;
+                violations_since = Instant::now();
+            }
+            violations += 1;
+            if violations >= limits.max_rate_violations {
+                tracing::warn!("closing socket after {violations} rate limit violations");
+                state
+                    .metrics
+                    .rejected_messages
+                    .with_label_values(&[&format!("{:?}", ErrorCode::Flooding)])
+                    .inc();
+                return Some(Error::new(
+                    ErrorCode::Flooding,
+                    "too many messages, closing the connection",
+                ));
+            }
+            let err = Error::new(
+                ErrorCode::RateLimited,
+                "you are sending messages too quickly",
+            );
+            reject(&state, &tx_local, err).await;
+            continue;
+        }
+
         let client_msg: ClientMessage = match serde_json::from_str(&text.to_string()) {
             Ok(msg) => msg,
-            Err(_) => {
+            Err(e) => {
                 let _ = tx_local
-                    .send(ServerMessage::Error(Error::Deserialization))
+                    .send(ServerMessage::Error(malformed_message(e)))
                     .await;
                 tracing::error!("deserialization failed");
                 continue;
             }
         };
 
-        match client_msg {
-            ClientMessage::Move(san_str) => {
-                // check if is current player turn
-                if !state
-                    .read(&connection.game_id, |_, v| {
-                        connection.color == v.board.turn()
-                    })
-                    .expect("game should exist")
-                // game existence is validated from auth step
-                {
-                    let _ = tx_local
-                        .send(ServerMessage::Error(Error::InvalidTurn))
-                        .await;
-                    tracing::error!("invalid turn");
-                    continue;
-                }
-
-                let san: San = match san_str.parse() {
-                    Ok(san) => san,
-                    Err(_) => {
-                        let _ = tx_local
-                            .send(ServerMessage::Error(Error::InvalidMove))
-                            .await;
-                        tracing::error!("invalid move");
-                        continue;
-                    }
-                };
-
-                let m = match state
-                    .read(&connection.game_id, |_, v| san.to_move(&v.board))
-                    .expect("game should exist")
-                {
-                    Ok(m) => m,
-                    Err(_) => {
-                        let _ = tx_local
-                            .send(ServerMessage::Error(Error::InvalidMove))
-                            .await;
-                        tracing::error!("invalid move");
-                        continue;
-                    }
-                };
-
-                state
-                    .get(&connection.game_id)
-                    .expect("game should exist")
-                    .board
-                    .play_unchecked(&m); // move is already validated when calling `san.to_move`
+        // held until the message is handled so that shutdown waits for it
+        let in_flight = state.drain.enter();
+        if in_flight.is_none() && !matches!(client_msg, ClientMessage::Ping) {
+            let _ = tx_local
+                .send(ServerMessage::Error(server_restarting()))
+                .await;
+            continue;
+        }
 
-                tracing::info!("broadcasting move {san_str}");
-                connection
-                    .tx_broadcast
-                    .send(ServerMessage::Move(san_str.clone()))
-                    .unwrap();
+        let timer = state.metrics.message_handling_seconds.start_timer();
+        let result = match client_msg {
+            ClientMessage::Move(san_str) => connection.seat().and_then(|color| {
+                let mut game = get_game(&state, &connection)?;
 
+                let played = game.moves.len();
+                game.play_move(color, &san_str)?;
+                // a queued premove may have been played as well
                 state
-                    .get(&connection.game_id)
-                    .expect("game should exist")
+                    .metrics
                     .moves
-                    .push(san_str.clone());
-
-                let outcome = state
-                    .read(&connection.game_id, |_, v| v.board.outcome())
-                    .expect("game should exist");
-
-                if let Some(outcome) = outcome {
-                    connection
-                        .tx_broadcast
-                        .send(ServerMessage::GameEnd(outcome))
-                        .unwrap();
-                    tracing::info!("game ended {} {}", connection.game_id, outcome);
+                    .inc_by((game.moves.len() - played) as u64);
+                if let Some(outcome) = game.board.outcome() {
+                    tracing::info!("game ended {outcome}");
                 }
-            }
+                Ok(())
+            }),
+            ClientMessage::Premove(san_str) => connection
+                .seat()
+                .and_then(|color| get_game(&state, &connection)?.set_premove(color, san_str)),
+            ClientMessage::CancelPremove => connection.seat().and_then(|color| {
+                get_game(&state, &connection)?.cancel_premove(color);
+                Ok(())
+            }),
+            ClientMessage::RequestTakeback
+            | ClientMessage::AcceptTakeback
+            | ClientMessage::DeclineTakeback => connection.seat().and_then(|color| {
+                let mut game = get_game(&state, &connection)?;
+
+                match client_msg {
+                    ClientMessage::RequestTakeback => game.request_takeback(color),
+                    ClientMessage::AcceptTakeback => game.accept_takeback(color),
+                    _ => game.decline_takeback(color),
+                }
+            }),
+            ClientMessage::Chat(text) => handle_chat(&state, &connection, &mut chat_limiter, text),
+            ClientMessage::Mute(user_id) => get_game(&state, &connection).map(|mut game| {
+                game.mute(&connection.user_id, user_id);
+            }),
+            ClientMessage::Unmute(user_id) => get_game(&state, &connection).map(|mut game| {
+                game.unmute(&connection.user_id, &user_id);
+            }),
             ClientMessage::Ping => {
                 tx_local.send(ServerMessage::Pong).await.unwrap();
+                Ok(())
             }
             _ => {
                 continue;
             }
+        };
+
+        timer.observe_duration();
+
+        match result {
+            // the game was cleaned up underneath a spectator
+            Err(err) if err.code == ErrorCode::GameNotFound => return Some(err),
+            Err(err) => reject(&state, &tx_local, err).await,
+            Ok(()) => {}
         }
     }
+    None
+}
+
+/// The game of `connection`, which spectators can outlive.
+fn get_game<'a>(
+    state: &'a AppState,
+    connection: &Connection,
+) -> Result<scc::hash_map::OccupiedEntry<'a, String, ActiveGame>> {
+    state
+        .active_games
+        .get(&connection.game_id)
+        .ok_or_else(|| game_not_found(&connection.game_id))
+}
+
+async fn reject(state: &AppState, tx_local: &Sender<ServerMessage>, err: Error) {
+    tracing::error!("rejected message: {err}");
+    state
+        .metrics
+        .rejected_messages
+        .with_label_values(&[&format!("{:?}", err.code)])
+        .inc();
+    let _ = tx_local.send(ServerMessage::Error(err)).await;
+}
+
+fn handle_chat(
+    state: &AppState,
+    connection: &Connection,
+    limiter: &mut RateLimiter,
+    text: String,
+) -> Result<()> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(Error::new(ErrorCode::InvalidChat, "chat message is empty"));
+    }
+    if text.chars().count() > state.config.chat.max_length {
+        return Err(
+            Error::new(ErrorCode::InvalidChat, "chat message is too long")
+                .with_max_length(state.config.chat.max_length),
+        );
+    }
+    if !limiter.try_acquire() {
+        return Err(Error::new(
+            ErrorCode::ChatRateLimited,
+            "you are sending chat messages too quickly",
+        ));
+    }
+    let (delivered, censored) = match state.chat_filter.check(text) {
+        FilterVerdict::Allow => (text.to_owned(), false),
+        FilterVerdict::Censor(censored) => (censored, true),
+        FilterVerdict::Reject => {
+            return Err(Error::new(
+                ErrorCode::ChatRejected,
+                "chat message was rejected by the chat filter",
+            ))
+        }
+    };
+
+    let channel = connection.chat_channel();
+    get_game(state, connection)?.broadcast(ServerMessage::Chat {
+        from: connection.user_id.clone(),
+        text: delivered,
+        ts: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64),
+        channel,
+    });
+
+    // games created through `/init` are not in the database
+    let game_uuid = Uuid::parse_str(&connection.game_id).ok();
+    if let Some(game_uuid) = game_uuid.filter(|_| state.has_database()) {
+        let pool = state.pool.clone();
+        let user_id = connection.user_id.clone();
+        let original = text.to_owned();
+        tokio::spawn(
+            async move {
+                if let Err(e) =
+                    store_chat(&pool, game_uuid, &user_id, channel, &original, censored).await
+                {
+                    tracing::error!("storing chat message failed: {e}");
+                }
+            }
+            .in_current_span(),
+        );
+    }
+    Ok(())
 }
 
 async fn handle_socket_write(
     mut writer: SplitSink<WebSocket, Message>,
-    mut rx_broadcast: broadcast::Receiver<ServerMessage>,
+    state: AppState,
+    connection: Arc<Connection>,
+    catch_up: CatchUp,
+    mut rx_broadcast: broadcast::Receiver<SequencedMessage>,
     mut rx_local: Receiver<ServerMessage>,
+    mut close: CloseSignals,
 ) {
+    let sent = match catch_up {
+        CatchUp::Replay(missed) => {
+            tracing::info!("resuming with {} missed messages", missed.len());
+            let mut sent = Ok(());
+            for msg in missed {
+                if !connection.should_receive(&state.active_games, &msg.message) {
+                    continue;
+                }
+                sent = send_msg(&mut writer, &msg).await;
+                if sent.is_err() {
+                    break;
+                }
+            }
+            sent
+        }
+        CatchUp::Snapshot(snapshot) => {
+            send_msg(&mut writer, &ServerMessage::GameSnapshot(snapshot)).await
+        }
+    };
+    if sent.is_err() {
+        tracing::error!("socket send failed");
+        return;
+    }
+
+    // the client's pongs keep the read side from timing out
+    let ping_interval = Duration::from_secs(state.config.connection.ping_interval_secs);
+    let mut ping = tokio::time::interval_at(Instant::now() + ping_interval, ping_interval);
+    ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
     loop {
         tokio::select! {
+            _ = ping.tick() => {
+                if writer.send(Message::Ping(Bytes::new())).await.is_err() {
+                    tracing::error!("socket ping failed");
+                    break;
+                }
+            }
             Ok(msg) = rx_broadcast.recv() => {
+                if !connection.should_receive(&state.active_games, &msg.message) {
+                    continue;
+                }
                 if send_msg(&mut writer, &msg).await
                     .is_err()
                 {
                     tracing::error!("socket send failed");
                     break;
                 }
-                if msg.is_game_end() {
+                if msg.message.is_game_end() {
                     break;
                 }
             }
@@ -364,6 +842,21 @@ async fn handle_socket_write(
                     break;
                 }
             }
+            replaced = &mut close.replaced => {
+                // an error means the seat was dropped without a takeover, the
+                // socket has nothing left to serve either way
+                if replaced.is_ok() {
+                    tracing::info!("session replaced, closing socket");
+                    let _ = send_msg(&mut writer, &ServerMessage::SessionReplaced).await;
+                    let _ = writer.close().await;
+                }
+                break;
+            }
+            Ok(err) = &mut close.error => {
+                let _ = send_msg(&mut writer, &ServerMessage::Error(err)).await;
+                let _ = writer.close().await;
+                break;
+            }
         }
     }
 }
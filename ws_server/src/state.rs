@@ -1,73 +1,701 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap as StdHashMap, HashSet, VecDeque},
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
+use chessclouds_protocol::game::{
+    Error, ErrorCode, GameSettings, GameSnapshot, GameStatus, SequencedMessage, ServerMessage,
+};
+use chessclouds_rate_limit::TokenBuckets;
+use chessclouds_store::{GameStore, MemoryGameStore, PgGameStore, Takeback};
+use chrono::{DateTime, Utc};
 use scc::HashMap;
-use shakmaty::{Chess, Color};
+use shakmaty::{fen::Fen, san::San, Chess, Color, EnPassantMode, Move, Outcome, Position};
 use sqlx::{Pool, Postgres};
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc, oneshot, Notify};
+use tracing::Instrument;
+use uuid::Uuid;
 
-use crate::{message::ServerMessage, MAX_CHANNEL_CAPACITY};
+use crate::{
+    chat::{ChatFilter, WordListFilter},
+    cluster::Node,
+    config::{Config, GameConfig, StoreKind},
+    metrics::Metrics,
+    render::GifCache,
+};
+
+/// A connected player or spectator socket.
+pub struct Session {
+    pub id: Uuid,
+    /// Fired when another connection of the same player takes over the seat.
+    /// Dropping it without firing closes the socket as well.
+    pub replaced: oneshot::Sender<()>,
+    /// Messages addressed to this connection only.
+    pub tx_local: mpsc::Sender<ServerMessage>,
+}
+
+enum StoreWrite {
+    Move(String),
+    Takeback(Takeback),
+    Finish(Outcome),
+    /// Answered once every write queued before it is done.
+    Flush(oneshot::Sender<()>),
+}
+
+/// Persists the changes to a game kept in the store, one at a time and in
+/// the order they were made, so moves queued by both players never race.
+pub struct StoreWriter {
+    tx: mpsc::UnboundedSender<StoreWrite>,
+}
+
+impl StoreWriter {
+    /// Spawns the task writing to `store`, which stops with the game.
+    pub fn spawn(store: Arc<dyn GameStore>, game_id: Uuid) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let write = async move {
+            while let Some(write) = rx.recv().await {
+                let written = match write {
+                    StoreWrite::Move(san) => store.append_move(game_id, &san).await,
+                    StoreWrite::Takeback(takeback) => store.take_back(game_id, &takeback).await,
+                    StoreWrite::Finish(outcome) => store.finish_game(game_id, outcome).await,
+                    StoreWrite::Flush(done) => {
+                        let _ = done.send(());
+                        continue;
+                    }
+                };
+                if let Err(e) = written {
+                    tracing::error!("persisting game {game_id} failed: {e}");
+                }
+            }
+        };
+        tokio::spawn(write.in_current_span());
+        StoreWriter { tx }
+    }
+
+    fn send(&self, write: StoreWrite) {
+        // the task only stops once this writer is dropped
+        let _ = self.tx.send(write);
+    }
+
+    /// Resolves once the writes queued so far are done.
+    pub fn flushed(&self) -> impl std::future::Future<Output = ()> {
+        let (tx, rx) = oneshot::channel();
+        self.send(StoreWrite::Flush(tx));
+        async move {
+            let _ = rx.await;
+        }
+    }
+}
 
 pub struct ActiveGame {
     pub white_user_id: String,
     pub black_user_id: String,
-    pub white_connected: bool,
-    pub black_connected: bool,
+    pub white_rating: Option<u32>,
+    pub black_rating: Option<u32>,
+    pub created_at: DateTime<Utc>,
+    pub white_session: Option<Session>,
+    pub black_session: Option<Session>,
+    pub spectators: StdHashMap<Uuid, Session>,
+    /// Users each user has muted in this game's chat.
+    pub mutes: StdHashMap<String, HashSet<String>>,
     pub clean_up_task: Option<tokio::task::JoinHandle<()>>,
+    pub settings: GameSettings,
     pub board: Chess,
-    pub tx_broadcast: broadcast::Sender<ServerMessage>,
+    pub tx_broadcast: broadcast::Sender<SequencedMessage>,
     pub moves: Vec<String>,
+    /// Move queued by the side not to move, tried right after the opponent moves.
+    pub premove: Option<String>,
+    pub takeback_requested_by: Option<Color>,
+    /// Accepted takebacks, oldest first.
+    pub takebacks: Vec<Takeback>,
+    pub seq: u64,
+    pub replay_log: VecDeque<SequencedMessage>,
+    pub replay_log_length: usize,
+    /// Set for games loaded from the store, `/init` games only live here.
+    pub store_writer: Option<StoreWriter>,
 }
 
 impl ActiveGame {
-    pub fn new(white_user_id: String, black_user_id: String) -> Self {
-        let (tx, _) = broadcast::channel(MAX_CHANNEL_CAPACITY);
+    pub fn new(
+        white_user_id: String,
+        black_user_id: String,
+        settings: GameSettings,
+        config: &GameConfig,
+    ) -> Self {
+        let (tx, _) = broadcast::channel(config.channel_capacity);
         ActiveGame {
             white_user_id,
             black_user_id,
-            white_connected: false,
-            black_connected: false,
+            white_rating: None,
+            black_rating: None,
+            created_at: Utc::now(),
+            white_session: None,
+            black_session: None,
+            spectators: StdHashMap::new(),
+            mutes: StdHashMap::new(),
             clean_up_task: None,
+            settings,
             board: Chess::default(),
             tx_broadcast: tx,
             moves: Vec::new(),
+            premove: None,
+            takeback_requested_by: None,
+            takebacks: Vec::new(),
+            seq: first_seq(),
+            replay_log: VecDeque::with_capacity(config.replay_log_length),
+            replay_log_length: config.replay_log_length,
+            store_writer: None,
         }
     }
 
-    pub fn connect(&mut self, color: Color) {
+    fn persist(&mut self, write: StoreWrite) {
+        if let Some(writer) = &self.store_writer {
+            writer.send(write);
+        }
+    }
+
+    /// Numbers `message`, records it in the replay log and sends it to every
+    /// subscribed connection.
+    pub fn broadcast(&mut self, message: ServerMessage) {
+        self.seq += 1;
+        let msg = SequencedMessage {
+            seq: self.seq,
+            message,
+        };
+        if self.replay_log.len() == self.replay_log_length {
+            self.replay_log.pop_front();
+        }
+        self.replay_log.push_back(msg.clone());
+        // no receivers is fine, everyone may be disconnected
+        let _ = self.tx_broadcast.send(msg);
+    }
+
+    /// Messages broadcast after `last_seq`, or `None` if some of them have
+    /// already been dropped from the replay log.
+    pub fn replay_since(&self, last_seq: u64) -> Option<Vec<SequencedMessage>> {
+        if last_seq > self.seq {
+            return None;
+        }
+        let first_seq = self.replay_log.front().map_or(self.seq + 1, |m| m.seq);
+        if last_seq + 1 < first_seq {
+            return None;
+        }
+        Some(
+            self.replay_log
+                .iter()
+                .filter(|m| m.seq > last_seq)
+                .cloned()
+                .collect(),
+        )
+    }
+
+    fn session_mut(&mut self, color: Color) -> &mut Option<Session> {
+        match color {
+            Color::Black => &mut self.black_session,
+            Color::White => &mut self.white_session,
+        }
+    }
+
+    pub fn is_connected(&self, color: Color) -> bool {
+        match color {
+            Color::Black => self.black_session.is_some(),
+            Color::White => self.white_session.is_some(),
+        }
+    }
+
+    /// Seats `session`, taking over from any connection already holding it.
+    pub fn connect(&mut self, color: Color, session: Session) {
         if let Some(task) = self.clean_up_task.take() {
             tracing::info!("aborting deferred clean up");
             task.abort();
         }
-        match color {
-            Color::Black => {
-                assert!(!self.black_connected);
-                self.black_connected = true;
-            }
-            Color::White => {
-                assert!(!self.white_connected);
-                self.white_connected = true;
-            }
+        if let Some(old) = self.session_mut(color).replace(session) {
+            tracing::info!("session {} replaced for {color}", old.id);
+            // the old connection may already be gone
+            let _ = old.replaced.send(());
+        }
+    }
+
+    pub fn connect_spectator(&mut self, session: Session) {
+        self.spectators.insert(session.id, session);
+    }
+
+    pub fn disconnect_spectator(&mut self, session_id: Uuid) {
+        self.spectators.remove(&session_id);
+    }
+
+    pub fn mute(&mut self, user_id: &str, muted_user_id: String) {
+        self.mutes
+            .entry(user_id.to_owned())
+            .or_default()
+            .insert(muted_user_id);
+    }
+
+    pub fn unmute(&mut self, user_id: &str, muted_user_id: &str) {
+        if let Some(muted) = self.mutes.get_mut(user_id) {
+            muted.remove(muted_user_id);
         }
     }
 
-    pub fn disconnect(&mut self, color: Color) {
+    pub fn is_muted(&self, user_id: &str, from: &str) -> bool {
+        self.mutes
+            .get(user_id)
+            .is_some_and(|muted| muted.contains(from))
+    }
+
+    fn session(&self, color: Color) -> Option<&Session> {
         match color {
-            Color::Black => {
-                assert!(self.black_connected);
-                self.black_connected = false;
+            Color::Black => self.black_session.as_ref(),
+            Color::White => self.white_session.as_ref(),
+        }
+    }
+
+    fn legal_move(&self, san_str: &str) -> Result<Move, Error> {
+        let san: San = san_str.parse().map_err(|_| malformed_move(san_str))?;
+        san.to_move(&self.board).map_err(|_| {
+            Error::new(
+                ErrorCode::IllegalMove,
+                "move is not legal in the current position",
+            )
+            .with_input(san_str)
+            .with_legal_moves(self.board.legal_moves().len())
+        })
+    }
+
+    fn apply_move(&mut self, m: &Move, san_str: String) {
+        self.board.play_unchecked(m); // move is already validated when calling `san.to_move`
+        self.moves.push(san_str.clone());
+        // a pending takeback request refers to the previous position
+        self.takeback_requested_by = None;
+        self.persist(StoreWrite::Move(san_str.clone()));
+
+        tracing::info!("broadcasting move {san_str}");
+        self.broadcast(ServerMessage::Move(san_str));
+
+        if let Some(outcome) = self.board.outcome() {
+            self.persist(StoreWrite::Finish(outcome));
+            self.broadcast(ServerMessage::GameEnd(outcome.into()));
+        }
+    }
+
+    /// Plays and broadcasts a move for `color`, then the opponent's premove
+    /// if one is queued and still legal.
+    ///
+    /// The premove is played in the same critical section as the move that
+    /// triggered it, so it never waits on the premoving player.
+    pub fn play_move(&mut self, color: Color, san_str: &str) -> Result<(), Error> {
+        if self.board.outcome().is_some() {
+            return Err(game_over());
+        }
+        if color != self.board.turn() {
+            return Err(
+                Error::new(ErrorCode::NotYourTurn, "it is your opponent's turn")
+                    .with_input(san_str),
+            );
+        }
+        let m = self.legal_move(san_str)?;
+        self.apply_move(&m, san_str.to_owned());
+
+        let premove = self.premove.take();
+        if self.board.outcome().is_some() {
+            // the game ended with this move, the premove is simply dropped
+            return Ok(());
+        }
+        if let Some(premove) = premove {
+            match self.legal_move(&premove) {
+                Ok(m) => self.apply_move(&m, premove),
+                Err(_) => {
+                    tracing::info!("discarding illegal premove {premove}");
+                    if let Some(session) = self.session(!color) {
+                        let _ = session
+                            .tx_local
+                            .try_send(ServerMessage::PremoveDiscarded(premove));
+                    }
+                }
             }
-            Color::White => {
-                assert!(self.white_connected);
-                self.white_connected = false;
+        }
+        Ok(())
+    }
+
+    /// Queues a premove for `color`, replacing any previous one. Only
+    /// syntax can be checked until the opponent has moved.
+    pub fn set_premove(&mut self, color: Color, san_str: String) -> Result<(), Error> {
+        if self.board.outcome().is_some() {
+            return Err(game_over());
+        }
+        if color == self.board.turn() {
+            return Err(Error::new(
+                ErrorCode::NotYourTurn,
+                "premoves can only be queued on your opponent's turn, play the move instead",
+            )
+            .with_input(san_str));
+        }
+        san_str
+            .parse::<San>()
+            .map_err(|_| malformed_move(&san_str))?;
+        self.premove = Some(san_str);
+        Ok(())
+    }
+
+    pub fn cancel_premove(&mut self, color: Color) {
+        if color != self.board.turn() {
+            self.premove = None;
+        }
+    }
+
+    /// Number of plies to undo so that it is `color`'s turn again with their
+    /// last move taken back.
+    fn takeback_plies(&self, color: Color) -> Result<usize, Error> {
+        if !self.settings.allow_takebacks {
+            return Err(Error::new(
+                ErrorCode::TakebackNotAllowed,
+                "takebacks are disabled for this game",
+            ));
+        }
+        let plies = if self.board.turn() == color { 2 } else { 1 };
+        if self.board.outcome().is_some() {
+            return Err(game_over());
+        }
+        if self.moves.len() < plies {
+            return Err(Error::new(
+                ErrorCode::InvalidTakeback,
+                "there is no move to take back",
+            ));
+        }
+        Ok(plies)
+    }
+
+    pub fn request_takeback(&mut self, color: Color) -> Result<(), Error> {
+        self.takeback_plies(color)?;
+        if self.takeback_requested_by.is_some() {
+            return Err(Error::new(
+                ErrorCode::InvalidTakeback,
+                "a takeback request is already pending",
+            ));
+        }
+        self.takeback_requested_by = Some(color);
+        self.broadcast(ServerMessage::TakebackRequested(color.into()));
+        Ok(())
+    }
+
+    /// Undoes the requester's last move (and the opponent's reply, if any),
+    /// rebuilding the board by replaying the remaining moves.
+    pub fn accept_takeback(&mut self, color: Color) -> Result<(), Error> {
+        let requested_by = match self.takeback_requested_by {
+            Some(requested_by) if requested_by != color => requested_by,
+            _ => return Err(no_takeback_request()),
+        };
+        let plies = self.takeback_plies(requested_by)?;
+
+        let undone = self.moves.split_off(self.moves.len() - plies);
+        self.board = replay(&self.moves).expect("moves should have been validated when played");
+        self.premove = None;
+        self.takeback_requested_by = None;
+
+        tracing::info!("takeback by {requested_by} undoing {undone:?}");
+        let takeback = Takeback {
+            requested_by,
+            undone,
+        };
+        self.persist(StoreWrite::Takeback(takeback.clone()));
+        self.takebacks.push(takeback);
+        self.broadcast(ServerMessage::Takeback {
+            fen: Fen::from_position(self.board.clone(), EnPassantMode::Legal).to_string(),
+            moves: self.moves.clone(),
+        });
+        Ok(())
+    }
+
+    pub fn decline_takeback(&mut self, color: Color) -> Result<(), Error> {
+        match self.takeback_requested_by {
+            Some(requested_by) if requested_by != color => {
+                self.takeback_requested_by = None;
+                self.broadcast(ServerMessage::TakebackDeclined);
+                Ok(())
             }
+            _ => Err(no_takeback_request()),
         }
     }
+
+    /// Replaces the position with the one reached by playing `moves`, used
+    /// when resuming a game persisted by another node.
+    pub fn restore(&mut self, moves: Vec<String>) -> Result<(), Error> {
+        self.board = replay(&moves).ok_or_else(|| {
+            Error::new(ErrorCode::GameUnavailable, "the saved moves are not legal")
+        })?;
+        self.moves = moves;
+        Ok(())
+    }
+
+    /// Everything a (re)connecting player or spectator needs to resume the game.
+    pub fn snapshot(&self, color: Option<Color>) -> GameSnapshot {
+        GameSnapshot {
+            fen: Fen::from_position(self.board.clone(), EnPassantMode::Legal).to_string(),
+            moves: self.moves.clone(),
+            turn: self.board.turn().into(),
+            white_user_id: self.white_user_id.clone(),
+            black_user_id: self.black_user_id.clone(),
+            status: match self.board.outcome() {
+                Some(outcome) => GameStatus::Ended(outcome.into()),
+                None => GameStatus::OnGoing,
+            },
+            color: color.map(Into::into),
+            premove: if color.is_some_and(|c| c != self.board.turn()) {
+                self.premove.clone()
+            } else {
+                None
+            },
+            takeback_requested_by: self.takeback_requested_by.map(Into::into),
+            seq: self.seq,
+        }
+    }
+
+    /// Frees the seat unless it has already been taken over by another session.
+    pub fn disconnect(&mut self, color: Color, session_id: Uuid) {
+        let session = self.session_mut(color);
+        if session.as_ref().is_some_and(|s| s.id == session_id) {
+            *session = None;
+        }
+    }
+}
+
+fn malformed_move(san_str: &str) -> Error {
+    Error::new(
+        ErrorCode::MalformedMove,
+        "move is not in standard algebraic notation",
+    )
+    .with_input(san_str)
+}
+
+fn game_over() -> Error {
+    Error::new(ErrorCode::GameOver, "the game has already ended")
+}
+
+fn no_takeback_request() -> Error {
+    Error::new(
+        ErrorCode::InvalidTakeback,
+        "there is no takeback request from your opponent",
+    )
+}
+
+/// Where the sequence numbers of a game loaded now start: the time in
+/// milliseconds, times a thousand. A game resumed on another node or after a
+/// restart thus numbers its messages above those of the earlier load, unless
+/// that one broadcast over a thousand messages per millisecond it lasted, so
+/// a `last_seq` from before never matches the new replay log and the client
+/// gets a snapshot instead. Stays below 2^53, the largest integer JavaScript
+/// clients read exactly.
+fn first_seq() -> u64 {
+    Utc::now().timestamp_millis().unsigned_abs() * 1000
+}
+
+/// The position after `moves`, `None` if one of them is illegal.
+pub fn replay(moves: &[String]) -> Option<Chess> {
+    let mut board = Chess::default();
+    for san_str in moves {
+        let m = san_str.parse::<San>().ok()?.to_move(&board).ok()?;
+        board.play_unchecked(&m);
+    }
+    Some(board)
 }
 
 pub type ActiveGameMap = Arc<HashMap<String, ActiveGame>>;
 
+/// Tracks client requests that mutate games so shutdown can wait for them.
+#[derive(Default)]
+pub struct Drain {
+    draining: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+}
+
+/// Marks a request as in flight until dropped.
+pub struct InFlight<'a>(&'a Drain);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if self.0.in_flight.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+impl Drain {
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Acquire)
+    }
+
+    /// Registers an in-flight request, or returns `None` once draining started.
+    pub fn enter(&self) -> Option<InFlight<'_>> {
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+        let guard = InFlight(self);
+        if self.is_draining() {
+            return None;
+        }
+        Some(guard)
+    }
+
+    /// Stops admitting requests and waits for the in-flight ones to finish.
+    pub async fn start(&self) {
+        self.draining.store(true, Ordering::Release);
+        loop {
+            let idle = self.idle.notified();
+            if self.in_flight.load(Ordering::Acquire) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct AppState {
+    pub config: Arc<Config>,
     pub active_games: ActiveGameMap,
     pub pool: Pool<Postgres>,
+    pub store: Arc<dyn GameStore>,
+    pub chat_filter: Arc<dyn ChatFilter>,
+    pub node: Arc<Node>,
+    pub drain: Arc<Drain>,
+    pub metrics: Arc<Metrics>,
+    pub gif_cache: Arc<GifCache>,
+    /// Per client IP, for GIFs that are not cached.
+    pub gif_limiter: Arc<TokenBuckets<IpAddr>>,
+}
+
+impl AppState {
+    /// State of a fresh node with a random id and no games loaded, keeping
+    /// games in the store `config.database.store` selects.
+    pub fn new(config: Config, pool: Pool<Postgres>) -> Self {
+        let store: Arc<dyn GameStore> = match config.database.store {
+            StoreKind::Postgres => Arc::new(PgGameStore::new(pool.clone())),
+            StoreKind::Memory => Arc::new(MemoryGameStore::default()),
+        };
+        AppState {
+            store,
+            chat_filter: Arc::new(WordListFilter::new(config.chat.banned_words.clone())),
+            gif_cache: Arc::new(GifCache::new(config.render.gif_cache_bytes)),
+            gif_limiter: Arc::new(TokenBuckets::new(
+                config.render.gif_burst,
+                config.render.gif_per_minute,
+            )),
+            node: Arc::new(Node {
+                id: Uuid::new_v4().to_string(),
+                url: config.node_url(),
+            }),
+            config: Arc::new(config),
+            active_games: Arc::new(HashMap::default()),
+            pool,
+            drain: Arc::new(Drain::default()),
+            metrics: Arc::new(Metrics::new()),
+        }
+    }
+
+    /// Whether the node runs with a database, which chat persistence, game
+    /// history and imports need. Nodes keeping games in memory run without.
+    pub fn has_database(&self) -> bool {
+        self.config.database.store == StoreKind::Postgres
+    }
+
+    /// Keeps games in `store` instead, e.g. one shared with matchmaking.
+    pub fn with_store(mut self, store: Arc<dyn GameStore>) -> Self {
+        self.store = store;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_game() -> ActiveGame {
+        let config = Config::default();
+        ActiveGame::new(
+            "white".to_string(),
+            "black".to_string(),
+            GameSettings::default(),
+            &config.game,
+        )
+    }
+
+    #[test]
+    fn replays_messages_after_last_seq() {
+        let mut game = new_game();
+        let start = game.seq;
+        assert_eq!(game.replay_since(start).unwrap().len(), 0);
+
+        game.play_move(Color::White, "e4").unwrap();
+        game.play_move(Color::Black, "e5").unwrap();
+        let missed = game.replay_since(start + 1).unwrap();
+        assert_eq!(missed.len(), 1);
+        assert_eq!(missed[0].seq, start + 2);
+        assert!(matches!(&missed[0].message, ServerMessage::Move(san) if san == "e5"));
+
+        // from the future
+        assert!(game.replay_since(start + 3).is_none());
+    }
+
+    #[test]
+    fn falls_back_to_a_snapshot_once_messages_are_dropped() {
+        let mut config = Config::default();
+        config.game.replay_log_length = 2;
+        let mut game = ActiveGame::new(
+            "white".to_string(),
+            "black".to_string(),
+            GameSettings::default(),
+            &config.game,
+        );
+        let start = game.seq;
+        for san in ["e4", "e5", "Nf3"] {
+            game.play_move(game.board.turn(), san).unwrap();
+        }
+        assert!(game.replay_since(start).is_none());
+        assert_eq!(game.replay_since(start + 1).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn sequence_numbers_of_an_earlier_load_are_not_replayed() {
+        let mut first = new_game();
+        for san in ["e4", "e5", "Nf3"] {
+            first.play_move(first.board.turn(), san).unwrap();
+        }
+        std::thread::sleep(std::time::Duration::from_millis(2));
+
+        // resumed on another node, which goes on to broadcast more messages
+        let mut second = new_game();
+        second.restore(first.moves.clone()).unwrap();
+        for san in ["Nc6", "Bb5", "a6", "Ba4"] {
+            second.play_move(second.board.turn(), san).unwrap();
+        }
+        assert!(second.seq > first.seq);
+        for last_seq in [first.seq - 1, first.seq] {
+            assert!(second.replay_since(last_seq).is_none());
+        }
+    }
+
+    #[test]
+    fn premoves_are_dropped_silently_when_the_game_ends() {
+        let mut game = new_game();
+        let (tx_local, mut rx_local) = mpsc::channel(8);
+        let (replaced, _) = oneshot::channel();
+        game.white_session = Some(Session {
+            id: Uuid::new_v4(),
+            replaced,
+            tx_local,
+        });
+        for san in ["f3", "e5", "g4"] {
+            game.play_move(game.board.turn(), san).unwrap();
+        }
+        game.set_premove(Color::White, "a3".to_string()).unwrap();
+
+        game.play_move(Color::Black, "Qh4#").unwrap();
+        assert!(game.board.is_checkmate());
+        assert_eq!(game.premove, None);
+        assert_eq!(game.moves, ["f3", "e5", "g4", "Qh4#"]);
+        assert!(rx_local.try_recv().is_err());
+    }
 }
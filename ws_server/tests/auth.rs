@@ -0,0 +1,152 @@
+mod common;
+
+use std::time::Duration;
+
+use chessclouds_client::protocol::{
+    game::{ClientMessage, Color, ErrorCode, GameStatus, ServerMessage},
+    PROTOCOL_VERSION,
+};
+use common::{expect_error, receive, spawn_server, BLACK_ID, WHITE_ID};
+use futures_util::{SinkExt, StreamExt};
+use sqlx::PgPool;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use uuid::Uuid;
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn players_join_with_a_snapshot(pool: PgPool) {
+    let server = spawn_server(pool).await;
+    let game_id = server.init_game().await;
+
+    let (white, snapshot) = server.join(&game_id, WHITE_ID).await;
+    assert_eq!(white.protocol_version(), PROTOCOL_VERSION);
+    assert_eq!(snapshot.color, Some(Color::White));
+    assert_eq!(snapshot.turn, Color::White);
+    assert!(snapshot.moves.is_empty());
+    assert!(matches!(snapshot.status, GameStatus::OnGoing));
+
+    let (_black, snapshot) = server.join(&game_id, BLACK_ID).await;
+    assert_eq!(snapshot.color, Some(Color::Black));
+    assert_eq!(snapshot.white_user_id, WHITE_ID);
+    assert_eq!(snapshot.black_user_id, BLACK_ID);
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn unknown_games_and_users_are_rejected(pool: PgPool) {
+    let server = spawn_server(pool).await;
+    let game_id = server.init_game().await;
+
+    let unknown = Uuid::new_v4().to_string();
+    assert_eq!(
+        server.join_rejected(&unknown, WHITE_ID).await,
+        ErrorCode::GameNotFound
+    );
+    assert_eq!(
+        server.join_rejected("not-a-uuid", WHITE_ID).await,
+        ErrorCode::GameNotFound
+    );
+    assert_eq!(
+        server.join_rejected(&game_id, "brown").await,
+        ErrorCode::NotAPlayer
+    );
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn spectators_cannot_move(pool: PgPool) {
+    let server = spawn_server(pool).await;
+    let game_id = server.init_game().await;
+
+    let mut spectator = server.client.spectate(&game_id, "brown").await.unwrap();
+    match receive(&mut spectator).await {
+        ServerMessage::GameSnapshot(snapshot) => assert_eq!(snapshot.color, None),
+        msg => panic!("expected a snapshot, got {msg:?}"),
+    }
+    spectator.play("e4").unwrap();
+    expect_error(&mut spectator, ErrorCode::SpectatorNotAllowed).await;
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn a_second_session_replaces_the_first(pool: PgPool) {
+    let server = spawn_server(pool).await;
+    let game_id = server.init_game().await;
+
+    let (mut first, _) = server.join(&game_id, WHITE_ID).await;
+    let (_second, _) = server.join(&game_id, WHITE_ID).await;
+    assert!(matches!(
+        receive(&mut first).await,
+        ServerMessage::SessionReplaced
+    ));
+    assert!(first.next_message().await.is_none());
+}
+
+/// Sends `frames` on a raw socket, as the client library only sends valid
+/// messages, and returns the first reply.
+async fn first_reply(url: &str, frames: Vec<ClientMessage>) -> ServerMessage {
+    let (mut socket, _) = connect_async(url).await.unwrap();
+    for frame in frames {
+        let text = serde_json::to_string(&frame).unwrap();
+        socket.send(Message::text(text)).await.unwrap();
+    }
+    let reply = tokio::time::timeout(Duration::from_secs(5), socket.next())
+        .await
+        .expect("no reply received in time");
+    match reply {
+        Some(Ok(Message::Text(text))) => serde_json::from_str(&text).unwrap(),
+        reply => panic!("expected a text message, got {reply:?}"),
+    }
+}
+
+fn auth(game_id: &str, protocol_version: Option<u32>) -> ClientMessage {
+    ClientMessage::Auth {
+        game_id: game_id.to_string(),
+        user_id: WHITE_ID.to_string(),
+        last_seq: None,
+        protocol_version,
+    }
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn protocol_versions_are_negotiated(pool: PgPool) {
+    let server = spawn_server(pool).await;
+    let game_id = server.init_game().await;
+    let url = server.client.ws_url();
+
+    // clients that predate negotiation speak version 1 and get no version
+    let reply = first_reply(&url, vec![auth(&game_id, None)]).await;
+    assert!(matches!(reply, ServerMessage::AuthSuccess(None)));
+
+    let reply = first_reply(&url, vec![auth(&game_id, Some(PROTOCOL_VERSION + 1))]).await;
+    assert!(matches!(
+        reply,
+        ServerMessage::AuthSuccess(Some(negotiated))
+            if negotiated.protocol_version == PROTOCOL_VERSION
+    ));
+
+    match first_reply(&url, vec![auth(&game_id, Some(0))]).await {
+        ServerMessage::Error(err) => {
+            assert_eq!(err.code, ErrorCode::UnsupportedProtocolVersion);
+            let context = err.context.expect("the supported range");
+            assert_eq!(context.max_protocol_version, Some(PROTOCOL_VERSION));
+        }
+        msg => panic!("expected an error, got {msg:?}"),
+    }
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn messages_before_auth_are_ignored(pool: PgPool) {
+    let server = spawn_server(pool).await;
+    let game_id = server.init_game().await;
+
+    let frames = vec![ClientMessage::Move("e4".to_string()), auth(&game_id, None)];
+    let reply = first_reply(&server.client.ws_url(), frames).await;
+    assert!(matches!(reply, ServerMessage::AuthSuccess(_)));
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn sockets_that_do_not_authenticate_time_out(pool: PgPool) {
+    let server = spawn_server(pool).await;
+
+    match first_reply(&server.client.ws_url(), Vec::new()).await {
+        ServerMessage::Error(err) => assert_eq!(err.code, ErrorCode::AuthTimeout),
+        msg => panic!("expected an error, got {msg:?}"),
+    }
+}
@@ -0,0 +1,184 @@
+//! Starts ws_server and matchmaking in-process against the per-test database
+//! `#[sqlx::test]` creates, and talks to them with `chessclouds-client`.
+
+// each test binary uses a different part of the harness
+#![allow(dead_code)]
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use chessclouds_client::{
+    protocol::game::{ErrorCode, GameSnapshot, InitBody, ServerMessage},
+    Error, GameClient, GameConnection, MatchmakingClient,
+};
+use chessclouds_store::{GameStore, PgGameStore};
+use sqlx::PgPool;
+use tokio::{net::TcpListener, time::timeout};
+use uuid::Uuid;
+use ws_server::{config::Config, route, state::AppState};
+
+pub const WHITE_ID: &str = "white";
+pub const BLACK_ID: &str = "black";
+
+/// How long a test waits for a message that should arrive.
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A ws_server listening on an ephemeral port.
+pub struct TestServer {
+    pub state: AppState,
+    pub client: GameClient,
+}
+
+/// Defaults with timings short enough for tests.
+pub fn test_config() -> Config {
+    let mut config = Config {
+        host: "127.0.0.1:0".to_string(),
+        ..Config::default()
+    };
+    config.game.clean_up_after_secs = 1;
+    config.connection.auth_timeout_secs = 1;
+    config
+}
+
+pub async fn spawn_server(pool: PgPool) -> TestServer {
+    spawn_server_with(test_config(), pool).await
+}
+
+pub async fn spawn_server_with(config: Config, pool: PgPool) -> TestServer {
+    spawn_server_on(config, |config| AppState::new(config, pool)).await
+}
+
+/// Starts a server with the state `new_state` builds from the final config.
+pub async fn spawn_server_on(
+    mut config: Config,
+    new_state: impl FnOnce(Config) -> AppState,
+) -> TestServer {
+    let listener = TcpListener::bind(&config.host).await.unwrap();
+    // redirects and the node URL need the real port
+    config.host = listener.local_addr().unwrap().to_string();
+    let state = new_state(config);
+    let app = route::router(state.clone());
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap()
+    });
+
+    let client = GameClient::new(format!("http://{}", state.config.host));
+    TestServer { state, client }
+}
+
+/// A matchmaking service listening on an ephemeral port.
+pub async fn spawn_matchmaking(pool: PgPool) -> MatchmakingClient {
+    let store = Arc::new(PgGameStore::new(pool.clone()));
+    spawn_matchmaking_with(pool, store).await
+}
+
+pub async fn spawn_matchmaking_with(pool: PgPool, store: Arc<dyn GameStore>) -> MatchmakingClient {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = matchmaking::app(&matchmaking::config::Config::default(), pool, store);
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap()
+    });
+    MatchmakingClient::new(format!("http://{addr}"))
+}
+
+impl TestServer {
+    /// Starts a game between [`WHITE_ID`] and [`BLACK_ID`] through `/init`.
+    pub async fn init_game(&self) -> String {
+        let game_id = Uuid::new_v4().to_string();
+        self.client
+            .init_game(&InitBody {
+                game_id: game_id.clone(),
+                white_user_id: WHITE_ID.to_string(),
+                black_user_id: BLACK_ID.to_string(),
+                white_rating: None,
+                black_rating: None,
+                settings: None,
+            })
+            .await
+            .unwrap();
+        game_id
+    }
+
+    /// Joins `game_id` and consumes the snapshot every new session starts with.
+    pub async fn join(&self, game_id: &str, user_id: &str) -> (GameConnection, GameSnapshot) {
+        let mut conn = self.client.join(game_id, user_id).await.unwrap();
+        match receive(&mut conn).await {
+            ServerMessage::GameSnapshot(snapshot) => (conn, snapshot),
+            msg => panic!("expected a snapshot, got {msg:?}"),
+        }
+    }
+
+    /// Joins both players of `game_id`.
+    pub async fn join_both(&self, game_id: &str) -> (GameConnection, GameConnection) {
+        let (white, _) = self.join(game_id, WHITE_ID).await;
+        let (black, _) = self.join(game_id, BLACK_ID).await;
+        (white, black)
+    }
+
+    /// The code `user_id` is turned away with when joining `game_id`.
+    pub async fn join_rejected(&self, game_id: &str, user_id: &str) -> ErrorCode {
+        match self.client.join(game_id, user_id).await {
+            Err(Error::Rejected(err)) => err.code,
+            Err(e) => panic!("expected a rejection, got {e}"),
+            Ok(_) => panic!("{user_id} should not be able to join {game_id}"),
+        }
+    }
+
+    pub fn is_active(&self, game_id: &str) -> bool {
+        self.state.active_games.contains(game_id)
+    }
+}
+
+/// The next server message, failing the test if none arrives in time.
+pub async fn receive(conn: &mut GameConnection) -> ServerMessage {
+    timeout(RECEIVE_TIMEOUT, conn.next_message())
+        .await
+        .expect("no message received in time")
+        .expect("connection closed")
+}
+
+/// Asserts that the next message is an error with `code`.
+pub async fn expect_error(conn: &mut GameConnection, code: ErrorCode) {
+    match receive(conn).await {
+        ServerMessage::Error(err) => assert_eq!(err.code, code, "{err}"),
+        msg => panic!("expected {code:?}, got {msg:?}"),
+    }
+}
+
+/// Plays `san` on `mover` and asserts both players see it.
+pub async fn play(mover: &mut GameConnection, opponent: &mut GameConnection, san: &str) {
+    mover.play(san).unwrap();
+    for conn in [mover, opponent] {
+        match receive(conn).await {
+            ServerMessage::Move(played) => assert_eq!(played, san),
+            msg => panic!("expected {san}, got {msg:?}"),
+        }
+    }
+}
+
+/// Waits until the server noticed that nobody is connected to `game_id`
+/// anymore and scheduled its clean up.
+pub async fn wait_for_clean_up_task(server: &TestServer, game_id: &str) {
+    timeout(RECEIVE_TIMEOUT, async {
+        while !server
+            .state
+            .active_games
+            .read(game_id, |_, game| game.clean_up_task.is_some())
+            .unwrap_or(false)
+        {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("clean up was not scheduled");
+}
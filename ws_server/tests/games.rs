@@ -0,0 +1,96 @@
+mod common;
+
+use common::{spawn_server, spawn_server_with, test_config};
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn insert_owned_game(
+    pool: &PgPool,
+    pgn: &str,
+    status: &str,
+    heartbeat: &str,
+    spectators: i32,
+) -> Uuid {
+    let game_id = Uuid::new_v4();
+    sqlx::query(
+        r#"INSERT INTO GameState (GameID, White, Black, PGN, Status)
+        VALUES ($1, 'alice', 'bob', $2, $3::"GameStatus")"#,
+    )
+    .bind(game_id)
+    .bind(pgn)
+    .bind(status)
+    .execute(pool)
+    .await
+    .unwrap();
+    sqlx::query(&format!(
+        "INSERT INTO GameOwner (GameID, NodeID, NodeURL, Heartbeat, Spectators)
+        VALUES ($1, 'other', 'ws://other:8000', {heartbeat}, $2)"
+    ))
+    .bind(game_id)
+    .bind(spectators)
+    .execute(pool)
+    .await
+    .unwrap();
+    game_id
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn live_games_of_other_nodes_are_listed(pool: PgPool) {
+    let remote = insert_owned_game(&pool, "1. e4 e5", "On Going", "now()", 4).await;
+    // owned by a node that stopped heartbeating, or already over
+    insert_owned_game(&pool, "1. d4", "On Going", "now() - interval '1 hour'", 9).await;
+    insert_owned_game(&pool, "1. f3 e5 2. g4 Qh4#", "Black Wins", "now()", 9).await;
+
+    let server = spawn_server(pool).await;
+    let local = server.init_game().await;
+    let base_url = format!("http://{}", server.state.config.host);
+
+    let page: Value = reqwest::get(format!("{base_url}/games?sort=moves"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(page["total"], 2);
+    let games = page["games"].as_array().unwrap();
+    assert_eq!(games[0]["game_id"], remote.to_string());
+    assert_eq!(games[0]["move_count"], 2);
+    assert_eq!(games[1]["game_id"], local);
+
+    let featured: Value = reqwest::get(format!("{base_url}/games/featured"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(featured["game_id"], remote.to_string());
+    assert_eq!(featured["spectators"], 4);
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn gifs_are_cached_and_rate_limited(pool: PgPool) {
+    let game_id = Uuid::new_v4();
+    sqlx::query(
+        r#"INSERT INTO GameState (GameID, White, Black, PGN, Status)
+        VALUES ($1, 'alice', 'bob', '1. f3 e5 2. g4 Qh4#', 'Black Wins')"#,
+    )
+    .bind(game_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let mut config = test_config();
+    config.render.gif_burst = 1;
+    config.render.gif_per_minute = 1;
+    let server = spawn_server_with(config, pool).await;
+    let gif_url = format!("http://{}/games/{game_id}/gif", server.state.config.host);
+
+    for _ in 0..2 {
+        let response = reqwest::get(format!("{gif_url}?size=64")).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert!(response.bytes().await.unwrap().starts_with(b"GIF89a"));
+    }
+    let response = reqwest::get(format!("{gif_url}?size=65")).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key("retry-after"));
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::spawn_server;
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn imported_games_stay_out_of_player_histories(pool: PgPool) {
+    let played = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO GameState (GameID, White, Black, PGN) VALUES ($1, 'alice', 'bob', '1. e4')",
+    )
+    .bind(played)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let server = spawn_server(pool).await;
+    let base_url = format!("http://{}", server.state.config.host);
+    let http = reqwest::Client::new();
+
+    // a forged win for alice
+    let imported: Value = http
+        .post(format!("{base_url}/games/import"))
+        .body("[White \"alice\"]\n[Black \"bob\"]\n[Result \"1-0\"]\n\n1. e4 1-0\n")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let imported_id = imported["game_ids"][0].as_str().unwrap().to_string();
+
+    let game: Value = reqwest::get(format!("{base_url}/games/{imported_id}"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(game["imported"], true);
+    assert_eq!(game["status"], "White Wins");
+
+    let page: Value = reqwest::get(format!("{base_url}/users/alice/games"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let game_ids: Vec<&str> = page["games"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|game| game["game_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(game_ids, [played.to_string()]);
+
+    let pgn = reqwest::get(format!("{base_url}/users/alice/games/pgn"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(pgn.matches("[Event ").count(), 1);
+    assert!(pgn.contains("[Result \"*\"]"));
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn pgn_exports_page_through_every_game(pool: PgPool) {
+    // more games than an export page, some sharing a date and some undated
+    sqlx::query(
+        r#"INSERT INTO GameState (GameID, White, Black, PGN, CreatedAt, TimeControl)
+        SELECT gen_random_uuid(), 'alice', 'bob', '1. e4', CASE
+                WHEN i % 10 = 0 THEN NULL
+                ELSE TIMESTAMP '2026-01-01' + (i / 3) * INTERVAL '1 minute'
+            END, i::TEXT
+        FROM generate_series(1, 250) AS i"#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    let server = spawn_server(pool).await;
+
+    let url = format!("http://{}/users/alice/games/pgn", server.state.config.host);
+    let pgn = reqwest::get(url).await.unwrap().text().await.unwrap();
+    let mut exported: Vec<u32> = pgn
+        .lines()
+        .filter_map(|line| line.strip_prefix("[TimeControl \""))
+        .map(|value| value.trim_end_matches("\"]").parse().unwrap())
+        .collect();
+    exported.sort_unstable();
+    assert_eq!(exported, (1..=250).collect::<Vec<_>>());
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use std::time::Duration;
+
+use chessclouds_client::protocol::game::{Color, ErrorCode, ServerMessage};
+use common::{
+    play, receive, spawn_server, spawn_server_with, test_config, wait_for_clean_up_task, BLACK_ID,
+    WHITE_ID,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Inserts an ongoing game the way matchmaking does, with `pgn` played.
+async fn insert_game(pool: &PgPool, pgn: &str) -> String {
+    let game_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO GameState (GameID, Black, White, PGN) VALUES ($1, $2, $3, $4)")
+        .bind(game_id)
+        .bind(BLACK_ID)
+        .bind(WHITE_ID)
+        .bind(pgn)
+        .execute(pool)
+        .await
+        .unwrap();
+    game_id.to_string()
+}
+
+async fn status(pool: &PgPool, game_id: &str) -> String {
+    sqlx::query_scalar("SELECT Status::text FROM GameState WHERE GameID = $1")
+        .bind(Uuid::parse_str(game_id).unwrap())
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn reconnecting_players_resume_from_a_snapshot(pool: PgPool) {
+    let server = spawn_server(pool).await;
+    let game_id = server.init_game().await;
+    let (mut white, mut black) = server.join_both(&game_id).await;
+
+    play(&mut white, &mut black, "e4").await;
+    white.close();
+    assert!(white.next_message().await.is_none());
+
+    // the game goes on while white is away
+    black.play("e5").unwrap();
+    assert!(matches!(receive(&mut black).await, ServerMessage::Move(_)));
+
+    let (_white, snapshot) = server.join(&game_id, WHITE_ID).await;
+    assert_eq!(snapshot.moves, ["e4", "e5"]);
+    assert_eq!(snapshot.turn, Color::White);
+    assert_eq!(snapshot.color, Some(Color::White));
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn persisted_games_are_loaded_on_join(pool: PgPool) {
+    let game_id = insert_game(&pool, "1. e4 e5 2. Nf3").await;
+    let server = spawn_server(pool).await;
+    assert!(!server.is_active(&game_id));
+
+    let (mut black, snapshot) = server.join(&game_id, BLACK_ID).await;
+    assert_eq!(snapshot.moves, ["e4", "e5", "Nf3"]);
+    assert_eq!(snapshot.turn, Color::Black);
+    assert!(server.is_active(&game_id));
+
+    black.play("Nc6").unwrap();
+    assert!(matches!(receive(&mut black).await, ServerMessage::Move(_)));
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn abandoned_games_are_cleaned_up(pool: PgPool) {
+    let game_id = insert_game(&pool, "").await;
+    let server = spawn_server(pool.clone()).await;
+    let (white, black) = server.join_both(&game_id).await;
+
+    // one player leaving keeps the game
+    drop(white);
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert!(server.is_active(&game_id));
+
+    drop(black);
+    wait_for_clean_up_task(&server, &game_id).await;
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert!(!server.is_active(&game_id));
+    assert_eq!(status(&pool, &game_id).await, "Abort");
+
+    // aborted games can no longer be joined
+    assert_eq!(
+        server.join_rejected(&game_id, WHITE_ID).await,
+        ErrorCode::GameNotFound
+    );
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn rejoining_cancels_the_clean_up(pool: PgPool) {
+    let mut config = test_config();
+    config.game.clean_up_after_secs = 2;
+    let game_id = insert_game(&pool, "").await;
+    let server = spawn_server_with(config, pool.clone()).await;
+    let (white, black) = server.join_both(&game_id).await;
+
+    drop(white);
+    drop(black);
+    wait_for_clean_up_task(&server, &game_id).await;
+
+    let (_white, _) = server.join(&game_id, WHITE_ID).await;
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    assert!(server.is_active(&game_id));
+    assert_eq!(status(&pool, &game_id).await, "On Going");
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn finished_games_keep_their_moves_and_result(pool: PgPool) {
+    let game_id = insert_game(&pool, "1. f3 e5").await;
+    let server = spawn_server(pool.clone()).await;
+    let (mut white, mut black) = server.join_both(&game_id).await;
+
+    play(&mut white, &mut black, "g4").await;
+    play(&mut black, &mut white, "Qh4#").await;
+    for conn in [&mut white, &mut black] {
+        assert!(matches!(receive(conn).await, ServerMessage::GameEnd(_)));
+    }
+
+    drop(white);
+    drop(black);
+    wait_for_clean_up_task(&server, &game_id).await;
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert!(!server.is_active(&game_id));
+    assert_eq!(status(&pool, &game_id).await, "Black Wins");
+    let pgn: String = sqlx::query_scalar("SELECT PGN FROM GameState WHERE GameID = $1")
+        .bind(Uuid::parse_str(&game_id).unwrap())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(pgn, "1. f3 e5 2. g4 Qh4#");
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn spectators_do_not_keep_games_alive(pool: PgPool) {
+    let game_id = insert_game(&pool, "1. e4").await;
+    let server = spawn_server(pool.clone()).await;
+
+    // loaded by a spectator alone
+    let mut spectator = server.client.spectate(&game_id, "carol").await.unwrap();
+    assert!(matches!(
+        receive(&mut spectator).await,
+        ServerMessage::GameSnapshot(_)
+    ));
+    wait_for_clean_up_task(&server, &game_id).await;
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert!(!server.is_active(&game_id));
+    assert_eq!(status(&pool, &game_id).await, "Abort");
+
+    // the spectator is let go, and cannot come back to the aborted game
+    assert!(spectator.next_message().await.is_none());
+}
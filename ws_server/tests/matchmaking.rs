@@ -0,0 +1,48 @@
+mod common;
+
+use chessclouds_client::{protocol::Color, Error};
+use common::{play, spawn_matchmaking, spawn_server};
+use sqlx::PgPool;
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn matched_players_can_play(pool: PgPool) {
+    let matchmaking = spawn_matchmaking(pool.clone()).await;
+    let server = spawn_server(pool).await;
+
+    let (first, second) = tokio::join!(
+        matchmaking.find_match("alice"),
+        matchmaking.find_match("bob")
+    );
+    let (first, second) = (first.unwrap(), second.unwrap());
+    assert_eq!(first.game_id, second.game_id);
+    assert_ne!(first.color, second.color);
+
+    let (white, black) = if first.color == Color::White {
+        ("alice", "bob")
+    } else {
+        ("bob", "alice")
+    };
+    let (mut white, snapshot) = server.join(&first.game_id, white).await;
+    assert_eq!(snapshot.color, Some(Color::White));
+    let (mut black, _) = server.join(&first.game_id, black).await;
+
+    play(&mut white, &mut black, "e4").await;
+    play(&mut black, &mut white, "c5").await;
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn players_can_only_queue_once(pool: PgPool) {
+    let matchmaking = spawn_matchmaking(pool).await;
+
+    let waiting = tokio::spawn({
+        let matchmaking = matchmaking.clone();
+        async move { matchmaking.find_match("alice").await }
+    });
+    // give the first request time to be queued
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    match matchmaking.find_match("alice").await {
+        Err(Error::Matchmaking(_)) => {}
+        res => panic!("a queued player should be turned away, got {res:?}"),
+    }
+    waiting.abort();
+}
@@ -0,0 +1,214 @@
+//! A node keeping games in memory, which needs no Postgres at all.
+
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use chessclouds_client::protocol::{
+    game::{ErrorCode, ServerMessage},
+    Color,
+};
+use chessclouds_store::{GameStatus, GameStore, MemoryGameStore, NewGame, Takeback};
+use common::{
+    expect_error, play, receive, spawn_matchmaking_with, spawn_server_on, test_config,
+    wait_for_clean_up_task, BLACK_ID, WHITE_ID,
+};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use tokio::time::timeout;
+use uuid::Uuid;
+use ws_server::{config::StoreKind, state::AppState};
+
+#[tokio::test]
+async fn matched_games_are_played_and_cleaned_up_in_memory() {
+    // never connected, nothing in this test touches the database
+    let pool = PgPoolOptions::new().connect_lazy_with(PgConnectOptions::new());
+    let store = MemoryGameStore::default();
+
+    let mut config = test_config();
+    config.database.store = StoreKind::Memory;
+    let server = spawn_server_on(config, |config| {
+        AppState::new(config, pool.clone()).with_store(Arc::new(store.clone()))
+    })
+    .await;
+    let matchmaking = spawn_matchmaking_with(pool, Arc::new(store.clone())).await;
+
+    let (alice, bob) = tokio::join!(
+        matchmaking.find_match("alice"),
+        matchmaking.find_match("bob")
+    );
+    let (alice, bob) = (alice.unwrap(), bob.unwrap());
+    assert_eq!(alice.game_id, bob.game_id);
+    let (white, black) = if alice.color == Color::White {
+        ("alice", "bob")
+    } else {
+        ("bob", "alice")
+    };
+    let game_id = alice.game_id;
+
+    let (mut white, _) = server.join(&game_id, white).await;
+    let (mut black, _) = server.join(&game_id, black).await;
+    let game_uuid = Uuid::parse_str(&game_id).unwrap();
+
+    play(&mut white, &mut black, "f3").await;
+    play(&mut black, &mut white, "e5").await;
+    // moves are written behind the broadcast
+    timeout(Duration::from_secs(5), async {
+        while store.load_game(game_uuid).await.unwrap().unwrap().moves != ["f3", "e5"] {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("moves were not stored");
+
+    play(&mut white, &mut black, "g4").await;
+    play(&mut black, &mut white, "Qh4#").await;
+    for conn in [&mut white, &mut black] {
+        assert!(matches!(receive(conn).await, ServerMessage::GameEnd(_)));
+    }
+
+    drop(white);
+    drop(black);
+    wait_for_clean_up_task(&server, &game_id).await;
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert!(!server.is_active(&game_id));
+    // the result is kept, the clean up only aborts unfinished games
+    assert_eq!(store.status(game_uuid), Some(GameStatus::BlackWins));
+}
+
+#[tokio::test]
+async fn abandoned_games_are_aborted_in_memory() {
+    let pool = PgPoolOptions::new().connect_lazy_with(PgConnectOptions::new());
+    let store = MemoryGameStore::default();
+
+    let mut config = test_config();
+    config.database.store = StoreKind::Memory;
+    let server = spawn_server_on(config, |config| {
+        AppState::new(config, pool).with_store(Arc::new(store.clone()))
+    })
+    .await;
+
+    let game_uuid = Uuid::new_v4();
+    store
+        .create_game(NewGame {
+            game_id: game_uuid,
+            white: WHITE_ID.to_string(),
+            black: BLACK_ID.to_string(),
+            time_control: None,
+            allow_takebacks: true,
+        })
+        .await
+        .unwrap();
+    let game_id = game_uuid.to_string();
+    let (mut white, mut black) = server.join_both(&game_id).await;
+    play(&mut white, &mut black, "e4").await;
+
+    drop(white);
+    drop(black);
+    wait_for_clean_up_task(&server, &game_id).await;
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert!(!server.is_active(&game_id));
+    assert_eq!(store.status(game_uuid), Some(GameStatus::Aborted));
+}
+
+#[tokio::test]
+async fn memory_nodes_are_ready_without_a_database() {
+    let pool = PgPoolOptions::new().connect_lazy_with(PgConnectOptions::new());
+    let mut config = test_config();
+    config.database.store = StoreKind::Memory;
+    let server = spawn_server_on(config, |config| AppState::new(config, pool)).await;
+
+    let url = format!("http://{}/readyz", server.state.config.host);
+    let response = reqwest::get(url).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn takebacks_are_stored() {
+    let pool = PgPoolOptions::new().connect_lazy_with(PgConnectOptions::new());
+    let store = MemoryGameStore::default();
+
+    let mut config = test_config();
+    config.database.store = StoreKind::Memory;
+    let server = spawn_server_on(config, |config| {
+        AppState::new(config, pool).with_store(Arc::new(store.clone()))
+    })
+    .await;
+
+    let game_uuid = Uuid::new_v4();
+    store
+        .create_game(NewGame {
+            game_id: game_uuid,
+            white: WHITE_ID.to_string(),
+            black: BLACK_ID.to_string(),
+            time_control: None,
+            allow_takebacks: true,
+        })
+        .await
+        .unwrap();
+    let game_id = game_uuid.to_string();
+    let (mut white, mut black) = server.join_both(&game_id).await;
+    play(&mut white, &mut black, "e4").await;
+    play(&mut black, &mut white, "e5").await;
+
+    black.request_takeback().unwrap();
+    for conn in [&mut white, &mut black] {
+        assert!(matches!(
+            receive(conn).await,
+            ServerMessage::TakebackRequested(Color::Black)
+        ));
+    }
+    white.accept_takeback().unwrap();
+    for conn in [&mut white, &mut black] {
+        match receive(conn).await {
+            ServerMessage::Takeback { moves, .. } => assert_eq!(moves, ["e4"]),
+            other => panic!("expected the takeback, got {other:?}"),
+        }
+    }
+
+    timeout(Duration::from_secs(5), async {
+        while store.load_game(game_uuid).await.unwrap().unwrap().moves != ["e4"] {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("the takeback was not stored");
+    let stored = store.load_game(game_uuid).await.unwrap().unwrap();
+    assert_eq!(
+        stored.takebacks,
+        [Takeback {
+            requested_by: shakmaty::Color::Black,
+            undone: vec!["e5".to_string()],
+        }]
+    );
+}
+
+#[tokio::test]
+async fn loaded_games_keep_takebacks_disabled() {
+    let pool = PgPoolOptions::new().connect_lazy_with(PgConnectOptions::new());
+    let store = MemoryGameStore::default();
+
+    let mut config = test_config();
+    config.database.store = StoreKind::Memory;
+    let server = spawn_server_on(config, |config| {
+        AppState::new(config, pool).with_store(Arc::new(store.clone()))
+    })
+    .await;
+
+    let game_uuid = Uuid::new_v4();
+    store
+        .create_game(NewGame {
+            game_id: game_uuid,
+            white: WHITE_ID.to_string(),
+            black: BLACK_ID.to_string(),
+            time_control: None,
+            allow_takebacks: false,
+        })
+        .await
+        .unwrap();
+    let game_id = game_uuid.to_string();
+    let (mut white, mut black) = server.join_both(&game_id).await;
+    play(&mut white, &mut black, "e4").await;
+
+    white.request_takeback().unwrap();
+    expect_error(&mut white, ErrorCode::TakebackNotAllowed).await;
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use chessclouds_client::protocol::game::{Color, ErrorCode, GameStatus, Outcome, ServerMessage};
+use common::{expect_error, play, receive, spawn_server, wait_for_clean_up_task, WHITE_ID};
+use sqlx::PgPool;
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn moves_are_broadcast_to_both_players(pool: PgPool) {
+    let server = spawn_server(pool).await;
+    let game_id = server.init_game().await;
+    let (mut white, mut black) = server.join_both(&game_id).await;
+
+    play(&mut white, &mut black, "e4").await;
+    play(&mut black, &mut white, "e5").await;
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn only_the_side_to_move_may_move(pool: PgPool) {
+    let server = spawn_server(pool).await;
+    let game_id = server.init_game().await;
+    let (mut white, mut black) = server.join_both(&game_id).await;
+
+    black.play("e5").unwrap();
+    expect_error(&mut black, ErrorCode::NotYourTurn).await;
+
+    play(&mut white, &mut black, "d4").await;
+    white.play("e4").unwrap();
+    expect_error(&mut white, ErrorCode::NotYourTurn).await;
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn malformed_and_illegal_moves_are_rejected(pool: PgPool) {
+    let server = spawn_server(pool).await;
+    let game_id = server.init_game().await;
+    let (mut white, mut black) = server.join_both(&game_id).await;
+
+    white.play("abc").unwrap();
+    expect_error(&mut white, ErrorCode::MalformedMove).await;
+
+    white.play("e5").unwrap();
+    match receive(&mut white).await {
+        ServerMessage::Error(err) => {
+            assert_eq!(err.code, ErrorCode::IllegalMove);
+            assert_eq!(err.input.as_deref(), Some("e5"));
+        }
+        msg => panic!("expected an illegal move error, got {msg:?}"),
+    }
+
+    // rejected moves change nothing
+    play(&mut white, &mut black, "e4").await;
+}
+
+#[sqlx::test(migrator = "ws_server::db::MIGRATOR")]
+async fn checkmate_ends_the_game(pool: PgPool) {
+    let server = spawn_server(pool).await;
+    let game_id = server.init_game().await;
+    let (mut white, mut black) = server.join_both(&game_id).await;
+
+    play(&mut white, &mut black, "e4").await;
+    play(&mut black, &mut white, "e5").await;
+    play(&mut white, &mut black, "Qh5").await;
+    play(&mut black, &mut white, "Nc6").await;
+    play(&mut white, &mut black, "Bc4").await;
+    play(&mut black, &mut white, "Nf6").await;
+    play(&mut white, &mut black, "Qxf7#").await;
+
+    let white_wins = Outcome::Decisive {
+        winner: Color::White,
+    };
+    for conn in [&mut white, &mut black] {
+        match receive(conn).await {
+            ServerMessage::GameEnd(outcome) => assert_eq!(outcome, white_wins),
+            msg => panic!("expected the game to end, got {msg:?}"),
+        }
+        // finished games close every socket
+        assert!(conn.next_message().await.is_none());
+    }
+
+    // rejoining only shows the result and does not keep the game alive
+    let (mut white, snapshot) = server.join(&game_id, WHITE_ID).await;
+    assert!(matches!(snapshot.status, GameStatus::Ended(outcome) if outcome == white_wins));
+    assert!(white.next_message().await.is_none());
+    wait_for_clean_up_task(&server, &game_id).await;
+}